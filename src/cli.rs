@@ -1,27 +1,48 @@
 use std::path::PathBuf;
 
+use chrono::{DateTime, Utc};
 use clap::{Parser, Subcommand};
+use ipnet::IpNet;
 
-use crate::core::keys::Scope;
+use crate::{
+    core::keys::{KeyDelegate, Scope},
+    telemetry::LogOptions,
+};
 
 #[derive(Debug, Parser)]
 #[command(version, about, long_about = None)]
 pub struct CliArgs {
     #[command(subcommand)]
     pub command: CommandArgs,
+
+    #[command(flatten)]
+    pub log: LogOptions,
 }
 
 #[derive(Debug, Subcommand)]
 pub enum CommandArgs {
     /// Run AiMo Network node service
     Serve {
-        /// The port the server listens on
-        #[arg(long, short, default_value_t = 8000)]
-        port: u16,
+        /// Path to the node's TOML config file
+        #[arg(
+            long,
+            short,
+            value_name = "FILE",
+            long_help = "Specify a TOML config file. Settings from the file are overridden by `AIMO_*` environment variables, which are in turn overridden by command line flags."
+        )]
+        config: Option<PathBuf>,
+
+        /// Print the effective configuration and exit
+        #[arg(long)]
+        print_config: bool,
+
+        /// The port the server listens on [default: 8000]
+        #[arg(long, short)]
+        port: Option<u16>,
 
-        /// The host address the server runs on
-        #[arg(long, short, default_value = "0.0.0.0")]
-        addr: String,
+        /// The host address the server runs on [default: 0.0.0.0]
+        #[arg(long, short)]
+        addr: Option<String>,
 
         /// Path to the node's Solana wallet id file
         #[arg(
@@ -34,6 +55,10 @@ pub enum CommandArgs {
         /// Path to the state db storage directory.
         #[arg(long, value_name = "DIR")]
         state_db_dir: Option<PathBuf>,
+
+        /// Seconds a finished completion stream can still be resumed [default: 300]
+        #[arg(long, value_name = "SECS")]
+        stream_resume_window: Option<u64>,
     },
 
     /// Generate a secret key for your wallet
@@ -43,7 +68,7 @@ pub enum CommandArgs {
             long,
             short,
             default_value = "dev",
-            long_help = "Scope tag is set to `dev` by default. By specifying this, you get a secret key like: `aimo-sk-<tag>-xxxxxxx`"
+            long_help = "Scope tag is set to `dev` by default. By specifying this, you get a secret key like: `aimo-sk-<tag>-xxxxxxx`. The tag is signed into the key, so it can't be changed afterwards, and nodes only accept the tags they're configured for."
         )]
         tag: String,
 
@@ -56,15 +81,44 @@ pub enum CommandArgs {
             long,
             short,
             value_delimiter = ',',
-            long_help = "Specify which scopes to enable with comma-seperated values. Current supported values are: \"completion_model\"",
+            long_help = "Specify which scopes to enable with comma-seperated values. Supported values are: \"completion_model\", \"embedding_model\", \"provider\", \"revoke\" and \"admin\". Keys of service providers need the \"provider\" scope.",
             default_value = "completion_model"
         )]
         scopes: Vec<Scope>,
 
         /// Usage limit of the secret key
-        #[arg(long, short, default_value_t = 0)]
+        #[arg(
+            long,
+            short,
+            default_value_t = 0,
+            long_help = "Total tokens the secret key may consume across all requests. Nodes reject the key once it's used up. Set to 0 for unlimited usage."
+        )]
         usage_limit: u64,
 
+        /// Human-readable label of the secret key, e.g. its owner or purpose
+        #[arg(long)]
+        label: Option<String>,
+
+        /// Restrict the secret key to these service ids
+        #[arg(long, value_delimiter = ',', value_name = "IDS")]
+        services: Option<Vec<String>>,
+
+        /// Restrict the secret key to these model names, without the `<target>:` prefix
+        #[arg(long, value_delimiter = ',', value_name = "NAMES")]
+        models: Option<Vec<String>>,
+
+        /// Restrict the secret key to clients in these networks, e.g. 10.0.0.0/8
+        #[arg(long, value_delimiter = ',', value_name = "CIDRS")]
+        allow_from: Option<Vec<IpNet>>,
+
+        /// Reject the secret key before this time, e.g. 2025-01-01T00:00:00Z
+        #[arg(long, value_name = "RFC3339")]
+        not_before: Option<DateTime<Utc>>,
+
+        /// Maximum completion requests the secret key may have in flight at the same time
+        #[arg(long)]
+        max_streams: Option<u32>,
+
         /// Path to secret key signer's Solana wallet id file
         #[arg(
             long,
@@ -72,6 +126,84 @@ pub enum CommandArgs {
             long_help = "Specify a Solana wallet id file (id.json, generated with `solana-keygen new`) to sign the secret key. Defaults to `~/.config/solana/id.json`."
         )]
         id: Option<PathBuf>,
+
+        /// Path to an Ethereum private key file to sign the secret key with instead
+        #[arg(
+            long,
+            value_name = "FILE",
+            conflicts_with = "id",
+            long_help = "Sign the secret key with an Ethereum wallet instead of a Solana one. The file holds the wallet's private key as hex, as exported by most wallets. The key is signed with `personal_sign` (EIP-191)."
+        )]
+        eth_key: Option<PathBuf>,
+
+        /// Signer allowed to sign delegated keys of this key, besides its own wallet
+        #[arg(
+            long,
+            value_name = "SIGNER",
+            long_help = "Allow another wallet to sign delegated keys of this key, e.g. a backend's hot wallet, so the signing wallet isn't needed for each one. The key then only serves to sign delegated keys: they carry its signature, so nodes don't accept it for requests. Takes a base58 Solana public key or a `0x` prefixed Ethereum address."
+        )]
+        delegate: Option<KeyDelegate>,
+
+        /// Secret key to delegate this key from
+        #[arg(
+            long,
+            value_name = "SECRET_KEY",
+            long_help = "Generate a delegated key of this secret key, signed by its wallet or delegate. A delegated key can only narrow its parent: it can't add scopes, outlive it, raise its usage limit or loosen its restrictions. Usage is counted against the whole chain, and revoking the parent revokes it too. The delegated key embeds its parent without the parent's signature, so the parent can't be recovered from it."
+        )]
+        parent: Option<String>,
+    },
+
+    /// Revoke a secret key signed by your wallet, or every key it issued before a time
+    Revoke {
+        /// Url to an AiMo Network node
+        #[arg(long)]
+        node_url: String,
+
+        /// The secret key to revoke
+        #[arg(long, required_unless_present_any = ["issued_before", "all_keys"])]
+        secret_key: Option<String>,
+
+        /// Revoke every key your wallet created before this time, e.g. 2025-01-01T00:00:00Z
+        #[arg(long, value_name = "RFC3339", conflicts_with_all = ["secret_key", "all_keys"])]
+        issued_before: Option<DateTime<Utc>>,
+
+        /// Revoke every key your wallet signed, including future ones
+        #[arg(
+            long,
+            conflicts_with = "secret_key",
+            long_help = "Revoke every key your wallet signed, whatever its creation time, including keys signed later. Use it when the wallet is compromised: creation times are set by the signer, so they can't be trusted anymore. Only node admins can lift this."
+        )]
+        all_keys: bool,
+
+        /// Secret key of your wallet with the `revoke` scope
+        #[arg(
+            long,
+            value_name = "SECRET_KEY",
+            required_unless_present = "secret_key",
+            conflicts_with = "secret_key",
+            long_help = "Secret key with the `revoke` scope, signed by the same wallet, to authenticate revocations with `--issued-before` or `--all-keys`. See `aimo keygen --scopes revoke`."
+        )]
+        revoke_key: Option<String>,
+
+        /// Id of the network the node belongs to
+        #[arg(
+            long,
+            default_value = "aimo",
+            long_help = "Id of the network the node belongs to. The revocation is signed for this network only, so it can't be replayed to nodes of other networks."
+        )]
+        network: String,
+
+        /// Path to the Solana wallet id file that signed the secret key
+        #[arg(
+            long,
+            value_name = "FILE",
+            long_help = "Specify the Solana wallet id file (id.json) that signed the secret key. Defaults to `~/.config/solana/id.json`."
+        )]
+        id: Option<PathBuf>,
+
+        /// Path to the Ethereum private key file that signed the secret key instead
+        #[arg(long, value_name = "FILE", conflicts_with = "id")]
+        eth_key: Option<PathBuf>,
     },
 
     /// Run a proxy to connect your endpoint to AiMo Network directly
@@ -94,5 +226,12 @@ pub enum CommandArgs {
         /// If your service endpoint requires an API key, specify the key here.
         #[arg(long)]
         api_key: Option<String>,
+
+        /// OTLP/HTTP collector url to export traces to, e.g. http://localhost:4318
+        #[arg(
+            long,
+            long_help = "Export traces to an OTLP/HTTP collector, e.g. http://localhost:4318. Defaults to `OTEL_EXPORTER_OTLP_ENDPOINT`."
+        )]
+        otlp_endpoint: Option<String>,
     },
 }
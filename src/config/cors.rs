@@ -0,0 +1,140 @@
+use anyhow::{anyhow, bail};
+use http::{HeaderName, HeaderValue, Method};
+use serde::{Deserialize, Serialize};
+
+/// Wildcard accepted in origins, methods and headers lists
+pub const CORS_WILDCARD: &str = "*";
+
+/// CORS policy of a route group
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct CorsOptions {
+    /// Origins allowed to access the routes, e.g. `https://app.aimo.network`, or `*`
+    pub allowed_origins: Vec<String>,
+
+    /// Methods allowed in cross-origin requests, or `*`
+    pub allowed_methods: Vec<String>,
+
+    /// Request headers allowed in cross-origin requests, or `*`
+    pub allowed_headers: Vec<String>,
+
+    /// Response headers exposed to browser scripts, e.g. `x-ratelimit-remaining-requests`
+    pub exposed_headers: Vec<String>,
+
+    /// Allow cookies and authorization headers. Can't be combined with wildcards.
+    pub allow_credentials: bool,
+
+    /// Seconds browsers may cache preflight responses
+    pub max_age: Option<u64>,
+}
+
+impl Default for CorsOptions {
+    /// Any origin and header, `GET` and `POST` only
+    fn default() -> Self {
+        Self {
+            allowed_origins: vec![CORS_WILDCARD.to_string()],
+            allowed_methods: vec!["GET".to_string(), "POST".to_string()],
+            allowed_headers: vec![CORS_WILDCARD.to_string()],
+            exposed_headers: vec![],
+            allow_credentials: false,
+            max_age: None,
+        }
+    }
+}
+
+impl CorsOptions {
+    pub fn validate(&self) -> anyhow::Result<()> {
+        let has_wildcard = |values: &[String]| values.iter().any(|v| v == CORS_WILDCARD);
+
+        if self.allow_credentials
+            && [
+                &self.allowed_origins,
+                &self.allowed_methods,
+                &self.allowed_headers,
+                &self.exposed_headers,
+            ]
+            .into_iter()
+            .any(|values| has_wildcard(values))
+        {
+            bail!("`allow_credentials` can't be combined with `*`");
+        }
+
+        for origin in self.allowed_origins.iter().filter(|v| *v != CORS_WILDCARD) {
+            HeaderValue::from_str(origin).map_err(|_| anyhow!("Invalid origin {origin}"))?;
+        }
+        for method in self.allowed_methods.iter().filter(|v| *v != CORS_WILDCARD) {
+            Method::from_bytes(method.as_bytes())
+                .map_err(|_| anyhow!("Invalid method {method}"))?;
+        }
+        for header in self
+            .allowed_headers
+            .iter()
+            .chain(&self.exposed_headers)
+            .filter(|v| *v != CORS_WILDCARD)
+        {
+            HeaderName::from_bytes(header.as_bytes())
+                .map_err(|_| anyhow!("Invalid header {header}"))?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Groups of routes sharing a CORS policy
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RouteGroup {
+    /// `/chat/*` and `/ws`
+    Chat,
+
+    /// `/keys/*`
+    Keys,
+
+    /// `/providers/*`
+    Providers,
+
+    /// `/admin/*`
+    Admin,
+}
+
+/// CORS policies of the API server. Route groups without their own policy use `default`.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct CorsPolicies {
+    pub default: CorsOptions,
+    pub chat: Option<CorsOptions>,
+    pub keys: Option<CorsOptions>,
+    pub providers: Option<CorsOptions>,
+    pub admin: Option<CorsOptions>,
+}
+
+impl CorsPolicies {
+    pub fn for_group(&self, group: Option<RouteGroup>) -> &CorsOptions {
+        let policy = match group {
+            Some(RouteGroup::Chat) => &self.chat,
+            Some(RouteGroup::Keys) => &self.keys,
+            Some(RouteGroup::Providers) => &self.providers,
+            Some(RouteGroup::Admin) => &self.admin,
+            None => &None,
+        };
+
+        policy.as_ref().unwrap_or(&self.default)
+    }
+
+    pub fn validate(&self) -> anyhow::Result<()> {
+        for (name, policy) in [
+            ("default", Some(&self.default)),
+            ("chat", self.chat.as_ref()),
+            ("keys", self.keys.as_ref()),
+            ("providers", self.providers.as_ref()),
+            ("admin", self.admin.as_ref()),
+        ] {
+            if let Some(policy) = policy {
+                policy
+                    .validate()
+                    .map_err(|err| anyhow!("Invalid `{name}` CORS policy: {err}"))?;
+            }
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,33 @@
+use serde::{Deserialize, Serialize};
+
+/// Limits applied to one subject: a secret key, a signer wallet or a provider.
+///
+/// Every limit is disabled when set to 0.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RateLimitOptions {
+    /// Sustained requests per second
+    pub requests_per_second: f64,
+
+    /// Requests allowed at once above the sustained rate
+    pub burst: u32,
+
+    /// Requests being served at the same time, streams included
+    pub max_concurrent_streams: u32,
+
+    /// Tokens consumed per minute, as reported by providers' `usage` blocks
+    pub tokens_per_minute: u64,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RateLimits {
+    /// Limits of every secret key, by key hash
+    pub per_key: RateLimitOptions,
+
+    /// Limits shared by all keys signed by the same wallet
+    pub per_signer: RateLimitOptions,
+
+    /// Limits of requests routed to the same provider
+    pub per_provider: RateLimitOptions,
+}
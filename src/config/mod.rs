@@ -1,3 +1,22 @@
+mod cors;
+mod limits;
+mod node;
+mod reload;
+mod router;
 mod server;
+mod telemetry;
+mod timeouts;
 
+pub use cors::{CORS_WILDCARD, CorsPolicies, RouteGroup};
+pub use limits::{RateLimitOptions, RateLimits};
+pub use node::{ConfigOverrides, ConfigSource, NodeConfig};
+pub use reload::{ConfigReloader, ReloadReport};
+pub use router::RouterOptions;
 pub use server::ServerOptions;
+pub use telemetry::TelemetryOptions;
+pub use timeouts::TimeoutOptions;
+
+#[cfg(test)]
+mod node_test;
+#[cfg(test)]
+mod reload_test;
@@ -0,0 +1,115 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use anyhow::anyhow;
+use serde::{Deserialize, Serialize};
+
+use crate::config::{RouterOptions, ServerOptions, TelemetryOptions, server::parse_env};
+
+/// Configuration of `aimo serve`.
+///
+/// Layered from lowest to highest priority: defaults, the TOML config file, `AIMO_*`
+/// environment variables, and command line flags.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct NodeConfig {
+    /// The node's Solana wallet id file, `~/.config/solana/id.json` if not set
+    pub id: Option<PathBuf>,
+
+    /// The state db storage directory, a platform-specific data directory if not set
+    pub state_db_dir: Option<PathBuf>,
+
+    pub server: ServerOptions,
+
+    pub router: RouterOptions,
+
+    pub telemetry: TelemetryOptions,
+}
+
+impl NodeConfig {
+    /// Load the config file, if any, and apply environment variables on top of it
+    pub fn load(path: Option<&Path>) -> anyhow::Result<Self> {
+        let mut config = match path {
+            Some(path) => {
+                let content = fs::read_to_string(path)
+                    .map_err(|err| anyhow!("Failed to read {}: {err}", path.display()))?;
+                toml::from_str::<Self>(&content)
+                    .map_err(|err| anyhow!("Failed to parse {}: {err}", path.display()))?
+            }
+            None => Self::default(),
+        };
+
+        config.apply_env()?;
+        Ok(config)
+    }
+
+    /// Override options with `AIMO_*` environment variables
+    pub fn apply_env(&mut self) -> anyhow::Result<()> {
+        if let Some(id) = parse_env::<PathBuf>("AIMO_ID")? {
+            self.id = Some(id);
+        }
+
+        if let Some(dir) = parse_env::<PathBuf>("AIMO_STATE_DB_DIR")? {
+            self.state_db_dir = Some(dir);
+        }
+
+        self.server.apply_env()?;
+        self.telemetry.apply_env()
+    }
+
+    pub fn validate(&self) -> anyhow::Result<()> {
+        self.server.validate()?;
+        self.router.validate()?;
+        self.telemetry.validate()
+    }
+
+    /// The effective configuration in the config file format
+    pub fn to_toml(&self) -> anyhow::Result<String> {
+        // Going through `toml::Value` puts plain values before tables, as TOML requires
+        Ok(toml::to_string_pretty(&toml::Value::try_from(self)?)?)
+    }
+}
+
+/// Command line flags overriding the config file and environment
+#[derive(Debug, Clone, Default)]
+pub struct ConfigOverrides {
+    pub addr: Option<String>,
+    pub port: Option<u16>,
+    pub stream_resume_window: Option<u64>,
+    pub id: Option<PathBuf>,
+    pub state_db_dir: Option<PathBuf>,
+}
+
+/// Where the node configuration comes from, kept to load it again on reload
+#[derive(Debug, Clone, Default)]
+pub struct ConfigSource {
+    pub path: Option<PathBuf>,
+    pub overrides: ConfigOverrides,
+}
+
+impl ConfigSource {
+    /// Load and validate the configuration with all layers applied
+    pub fn load(&self) -> anyhow::Result<NodeConfig> {
+        let mut config = NodeConfig::load(self.path.as_deref())?;
+        let overrides = self.overrides.clone();
+
+        if let Some(addr) = overrides.addr {
+            config.server.addr = addr;
+        }
+        if let Some(port) = overrides.port {
+            config.server.port = port;
+        }
+        if let Some(window) = overrides.stream_resume_window {
+            config.server.stream_resume_window = window;
+        }
+        config.id = overrides.id.or(config.id);
+        config.state_db_dir = overrides.state_db_dir.or(config.state_db_dir);
+
+        config
+            .validate()
+            .map_err(|err| anyhow!("Invalid configuration: {err}"))?;
+        Ok(config)
+    }
+}
@@ -0,0 +1,114 @@
+use chrono::Utc;
+use solana_sdk::{signature::Keypair, signer::Signer};
+
+use crate::{
+    config::NodeConfig,
+    core::keys::{MetadataRawV1, MetadataV1, Scope, SecretKeyV1, SecretKeyV2, Wallet},
+};
+
+#[test]
+fn test_partial_file_keeps_defaults() {
+    let config: NodeConfig = toml::from_str(
+        r#"
+        [server]
+        port = 9000
+
+        [server.rate_limits.per_key]
+        requests_per_second = 2.0
+        "#,
+    )
+    .unwrap();
+
+    assert_eq!(config.server.port, 9000);
+    assert_eq!(config.server.addr, "0.0.0.0");
+    assert_eq!(config.server.rate_limits.per_key.requests_per_second, 2.0);
+    assert_eq!(config.server.timeouts.request, 30);
+    assert_eq!(config.router.message_buffer, 128);
+    assert!(config.validate().is_ok());
+}
+
+#[test]
+fn test_unknown_fields_rejected() {
+    assert!(toml::from_str::<NodeConfig>("bogus = 1").is_err());
+    assert!(toml::from_str::<NodeConfig>("[server.timeouts]\nfirst_bite = 1").is_err());
+}
+
+#[test]
+fn test_printed_config_round_trips() {
+    let mut config = NodeConfig::default();
+    config.server.keys.accepted_tags = vec!["dev".to_string(), "prod".to_string()];
+
+    let printed = config.to_toml().unwrap();
+    let parsed: NodeConfig = toml::from_str(&printed).unwrap();
+
+    assert_eq!(parsed.to_toml().unwrap(), printed);
+    assert!(parsed.server.keys.accepts_tag("prod"));
+}
+
+#[test]
+fn test_invalid_values_rejected() {
+    let mut config = NodeConfig::default();
+    config.router.message_buffer = 0;
+    assert!(config.validate().is_err());
+
+    let mut config = NodeConfig::default();
+    config.server.keys.accepted_tags = vec![];
+    assert!(config.validate().is_err());
+
+    let mut config = NodeConfig::default();
+    config.server.keys.revocation_prune_interval = 0;
+    assert!(config.validate().is_err());
+}
+
+#[test]
+fn test_signed_tag_required() {
+    let mut config = NodeConfig::default();
+    let keys = &mut config.server.keys;
+
+    assert!(keys.check_tag("dev", None).is_ok());
+    assert!(keys.check_tag("prod", Some("prod")).is_err());
+
+    keys.require_signed_tag = true;
+    assert!(keys.check_tag("dev", Some("dev")).is_ok());
+    let err = keys.check_tag("dev", None).unwrap_err();
+    assert!(err.to_string().contains("isn't signed"));
+}
+
+#[test]
+fn test_relabelled_v1_key() {
+    let mut config = NodeConfig::default();
+    let keys = &mut config.server.keys;
+    keys.accepted_tags = vec!["dev".to_string(), "prod".to_string()];
+
+    let keypair = Keypair::new();
+    let metadata = MetadataV1 {
+        created_at: Utc::now().timestamp_millis(),
+        valid_for: 60_000,
+        usage_limit: 0,
+        scopes: vec![Scope::CompletionModel],
+    };
+    let bytes = MetadataRawV1::try_from(metadata.clone())
+        .unwrap()
+        .into_bytes();
+    let sk = SecretKeyV1 {
+        version: 1,
+        wallet: Wallet::Solana,
+        signer: keypair.pubkey().to_string(),
+        signature: keypair.sign_message(&bytes).to_string(),
+        metadata,
+    }
+    .into_string("dev")
+    .unwrap();
+
+    // V1 keys only pass with the default tag, even without `require_signed_tag`
+    let (tag, key) = SecretKeyV2::decode(&sk).unwrap();
+    assert!(keys.check_tag(&tag, key.metadata.tag.as_deref()).is_ok());
+
+    let relabelled = sk.replacen("aimo-sk-dev-", "aimo-sk-prod-", 1);
+    let (tag, key) = SecretKeyV2::decode(&relabelled).unwrap();
+    assert_eq!(tag, "prod");
+    let err = keys
+        .check_tag(&tag, key.metadata.tag.as_deref())
+        .unwrap_err();
+    assert!(err.to_string().contains("isn't signed"));
+}
@@ -0,0 +1,130 @@
+use std::sync::{Arc, Mutex};
+
+use serde::Serialize;
+use tokio::sync::watch;
+
+use crate::config::{ConfigSource, NodeConfig, ServerOptions};
+
+/// Outcome of a configuration reload
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct ReloadReport {
+    /// Changed settings which took effect
+    pub applied: Vec<String>,
+
+    /// Changed settings which were not applied and only take effect after a restart
+    pub requires_restart: Vec<String>,
+}
+
+/// Holds the running node configuration and reloads it from its source.
+///
+/// Policies read for every request (keys, rate limits, timeouts, CORS and the admin
+/// token) are applied in place. Settings the running node was built with, such as the
+/// listen address, keep their current values and are reported instead.
+pub struct ConfigReloader {
+    source: ConfigSource,
+    current: Mutex<NodeConfig>,
+    options: watch::Sender<Arc<ServerOptions>>,
+}
+
+impl ConfigReloader {
+    pub fn new(source: ConfigSource, config: NodeConfig) -> Self {
+        let (options, _) = watch::channel(Arc::new(config.server.clone()));
+
+        Self {
+            source,
+            current: Mutex::new(config),
+            options,
+        }
+    }
+
+    /// The running configuration
+    pub fn current(&self) -> NodeConfig {
+        self.current.lock().unwrap().clone()
+    }
+
+    /// The running server options
+    pub fn options(&self) -> Arc<ServerOptions> {
+        self.options.borrow().clone()
+    }
+
+    /// Get notified of server options changes
+    pub fn subscribe(&self) -> watch::Receiver<Arc<ServerOptions>> {
+        self.options.subscribe()
+    }
+
+    /// Load the configuration from its source again and apply the live settings.
+    ///
+    /// Nothing is applied if the new configuration fails to load or validate.
+    pub fn reload(&self) -> anyhow::Result<ReloadReport> {
+        let new = self.source.load()?;
+        let mut current = self.current.lock().unwrap();
+        let mut report = ReloadReport::default();
+
+        let mut live = |name: &str, changed: bool| {
+            if changed {
+                report.applied.push(name.to_string());
+            }
+            changed
+        };
+        if live("server.keys", current.server.keys != new.server.keys) {
+            current.server.keys = new.server.keys.clone();
+        }
+        if live(
+            "server.rate_limits",
+            current.server.rate_limits != new.server.rate_limits,
+        ) {
+            current.server.rate_limits = new.server.rate_limits.clone();
+        }
+        if live(
+            "server.timeouts",
+            current.server.timeouts != new.server.timeouts,
+        ) {
+            current.server.timeouts = new.server.timeouts;
+        }
+        if live("server.cors", current.server.cors != new.server.cors) {
+            current.server.cors = new.server.cors.clone();
+        }
+        if live(
+            "server.admin_token",
+            current.server.admin_token != new.server.admin_token,
+        ) {
+            current.server.admin_token = new.server.admin_token.clone();
+        }
+
+        for (name, changed) in [
+            ("id", current.id != new.id),
+            ("state_db_dir", current.state_db_dir != new.state_db_dir),
+            ("server.addr", current.server.addr != new.server.addr),
+            ("server.port", current.server.port != new.server.port),
+            (
+                "server.stream_resume_window",
+                current.server.stream_resume_window != new.server.stream_resume_window,
+            ),
+            (
+                "server.max_buffered_streams",
+                current.server.max_buffered_streams != new.server.max_buffered_streams,
+            ),
+            (
+                "server.max_buffered_streams_per_signer",
+                current.server.max_buffered_streams_per_signer
+                    != new.server.max_buffered_streams_per_signer,
+            ),
+            (
+                "server.max_buffered_stream_bytes",
+                current.server.max_buffered_stream_bytes != new.server.max_buffered_stream_bytes,
+            ),
+            ("router", current.router != new.router),
+            ("telemetry", current.telemetry != new.telemetry),
+        ] {
+            if changed {
+                report.requires_restart.push(name.to_string());
+            }
+        }
+
+        if !report.applied.is_empty() {
+            self.options.send_replace(Arc::new(current.server.clone()));
+        }
+
+        Ok(report)
+    }
+}
@@ -0,0 +1,50 @@
+use std::{env, fs, path::PathBuf};
+
+use crate::config::{ConfigReloader, ConfigSource};
+
+fn write_config(path: &PathBuf, content: &str) {
+    fs::write(path, content).unwrap();
+}
+
+#[test]
+fn test_reload() {
+    let path = env::temp_dir().join(format!("aimo-{}.toml", uuid::Uuid::new_v4()));
+    write_config(&path, "[server]\nport = 9000\n");
+
+    let source = ConfigSource {
+        path: Some(path.clone()),
+        ..Default::default()
+    };
+    let reloader = ConfigReloader::new(source.clone(), source.load().unwrap());
+    let options_rx = reloader.subscribe();
+
+    write_config(
+        &path,
+        r#"
+        [server]
+        port = 9001
+
+        [server.rate_limits.per_key]
+        requests_per_second = 5.0
+        "#,
+    );
+    let report = reloader.reload().unwrap();
+
+    assert_eq!(report.applied, vec!["server.rate_limits"]);
+    assert_eq!(report.requires_restart, vec!["server.port"]);
+    assert!(options_rx.has_changed().unwrap());
+
+    let options = reloader.options();
+    assert_eq!(options.port, 9000);
+    assert_eq!(options.rate_limits.per_key.requests_per_second, 5.0);
+
+    // Invalid configs are rejected as a whole
+    write_config(&path, "[server.keys]\naccepted_tags = []\n");
+    assert!(reloader.reload().is_err());
+    assert_eq!(
+        reloader.options().rate_limits.per_key.requests_per_second,
+        5.0
+    );
+
+    fs::remove_file(path).unwrap();
+}
@@ -0,0 +1,37 @@
+use anyhow::bail;
+use serde::{Deserialize, Serialize};
+
+/// Channel sizes of the local router
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RouterOptions {
+    /// Messages queued for the router's dispatch loop
+    pub message_buffer: usize,
+
+    /// Requests and responses queued per connected provider
+    pub service_buffer: usize,
+
+    /// Responses queued per client request
+    pub response_buffer: usize,
+}
+
+impl Default for RouterOptions {
+    fn default() -> Self {
+        Self {
+            message_buffer: 128,
+            service_buffer: 16,
+            response_buffer: 1,
+        }
+    }
+}
+
+impl RouterOptions {
+    pub fn validate(&self) -> anyhow::Result<()> {
+        // Tokio channels panic with a zero capacity
+        if self.message_buffer == 0 || self.service_buffer == 0 || self.response_buffer == 0 {
+            bail!("Router buffer sizes must be greater than 0");
+        }
+
+        Ok(())
+    }
+}
@@ -1,11 +1,40 @@
 use std::env;
 
-use serde::{Deserialize, Serialize};
+use anyhow::{anyhow, bail};
+use serde::{Deserialize, Serialize, de::DeserializeOwned};
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+use crate::config::{CorsPolicies, RateLimits, TimeoutOptions};
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
 pub struct ServerOptions {
     pub addr: String,
     pub port: u16,
+
+    /// Seconds a finished completion stream stays resumable with `Last-Event-ID`
+    pub stream_resume_window: u64,
+
+    /// Completion streams buffered for resuming at once, over all signers
+    pub max_buffered_streams: usize,
+
+    /// Completion streams buffered for resuming at once per signer
+    pub max_buffered_streams_per_signer: usize,
+
+    /// Bytes buffered per completion stream. Older chunks are dropped past it and can't
+    /// be resumed from anymore.
+    pub max_buffered_stream_bytes: usize,
+
+    pub keys: KeyOptions,
+
+    pub rate_limits: RateLimits,
+
+    pub timeouts: TimeoutOptions,
+
+    pub cors: CorsPolicies,
+
+    /// Token of the `X-Admin-Token` header of the `/admin/*` routes, which are disabled if
+    /// not set. Admin requests also need a secret key with the `admin` scope.
+    pub admin_token: Option<String>,
 }
 
 impl Default for ServerOptions {
@@ -14,25 +43,208 @@ impl Default for ServerOptions {
         Self {
             addr: String::from("0.0.0.0"),
             port: 8000,
+            stream_resume_window: 300,
+            max_buffered_streams: 10_000,
+            max_buffered_streams_per_signer: 100,
+            max_buffered_stream_bytes: 4 * 1024 * 1024,
+            keys: KeyOptions::default(),
+            rate_limits: RateLimits::default(),
+            timeouts: TimeoutOptions::default(),
+            cors: CorsPolicies::default(),
+            admin_token: None,
         }
     }
 }
 
 impl ServerOptions {
-    #[allow(dead_code)]
-    pub fn from_env() -> Self {
-        let mut default_opts = Self::default();
-
+    /// Override options with `AIMO_*` environment variables.
+    ///
+    /// `AIMO_RATE_LIMITS`, `AIMO_TIMEOUTS` and `AIMO_CORS` take the JSON representations
+    /// of `RateLimits`, `TimeoutOptions` and `CorsPolicies`. `AIMO_ACCEPTED_KEY_TAGS` takes
+    /// comma-separated tags.
+    pub fn apply_env(&mut self) -> anyhow::Result<()> {
         if let Ok(addr) = env::var("AIMO_LISTEN_ADDRESS") {
-            default_opts.addr = addr;
+            self.addr = addr;
+        }
+
+        if let Some(port) = parse_env("AIMO_LISTEN_PORT")? {
+            self.port = port;
+        }
+
+        if let Some(window) = parse_env("AIMO_STREAM_RESUME_WINDOW")? {
+            self.stream_resume_window = window;
+        }
+
+        if let Ok(tags) = env::var("AIMO_ACCEPTED_KEY_TAGS") {
+            self.keys.accepted_tags = tags.split(',').map(|tag| tag.trim().to_string()).collect();
+        }
+
+        if let Some(required) = parse_env("AIMO_REQUIRE_SIGNED_KEY_TAG")? {
+            self.keys.require_signed_tag = required;
+        }
+
+        if let Some(size) = parse_env("AIMO_KEY_CACHE_SIZE")? {
+            self.keys.cache_size = size;
+        }
+
+        if let Ok(network_id) = env::var("AIMO_NETWORK_ID") {
+            self.keys.network_id = network_id;
+        }
+
+        if let Some(limits) = json_env("AIMO_RATE_LIMITS")? {
+            self.rate_limits = limits;
+        }
+
+        if let Some(timeouts) = json_env("AIMO_TIMEOUTS")? {
+            self.timeouts = timeouts;
+        }
+
+        if let Some(cors) = json_env("AIMO_CORS")? {
+            self.cors = cors;
+        }
+
+        if let Ok(token) = env::var("AIMO_ADMIN_TOKEN") {
+            self.admin_token = Some(token);
+        }
+
+        Ok(())
+    }
+
+    /// Check options which can't be represented by their types alone
+    pub fn validate(&self) -> anyhow::Result<()> {
+        if self
+            .admin_token
+            .as_ref()
+            .is_some_and(|token| token.is_empty())
+        {
+            bail!("`admin_token` can't be empty");
+        }
+
+        if self.max_buffered_streams == 0 || self.max_buffered_streams_per_signer == 0 {
+            bail!("Buffered stream limits must be positive");
+        }
+
+        self.keys.validate()?;
+        self.cors.validate()
+    }
+}
+
+/// Secret keys accepted by the node
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct KeyOptions {
+    /// Tags accepted in `aimo-sk-{tag}-...` keys. Keys generated by the node get the first one.
+    pub accepted_tags: Vec<String>,
+
+    /// Reject keys whose tag isn't signed into their metadata, like V1 keys, which could
+    /// have been relabelled. Unsigned tags are only ever accepted if they're the default
+    /// tag.
+    pub require_signed_tag: bool,
+
+    /// Verified keys cached to skip verifying them on every request, 0 to disable
+    pub cache_size: usize,
+
+    /// Id of the network the node belongs to. Revocations signed for other networks are
+    /// rejected.
+    pub network_id: String,
+
+    /// Seconds a signed revocation is accepted before or after its timestamp
+    pub revocation_window: u64,
+
+    /// Seconds revocations are kept after their key expired, as a margin for clock skew
+    pub revocation_retention: u64,
+
+    /// Seconds between prunes of the revocations of expired keys
+    pub revocation_prune_interval: u64,
+}
+
+impl Default for KeyOptions {
+    fn default() -> Self {
+        Self {
+            accepted_tags: vec!["dev".to_string()],
+            require_signed_tag: false,
+            cache_size: 10_000,
+            network_id: "aimo".to_string(),
+            revocation_window: 300,
+            revocation_retention: 3600,
+            revocation_prune_interval: 3600,
+        }
+    }
+}
+
+impl KeyOptions {
+    pub fn accepts_tag(&self, tag: &str) -> bool {
+        self.accepted_tags.iter().any(|accepted| accepted == tag)
+    }
+
+    /// Check the `tag` of a key, with `signed_tag` from its metadata
+    pub fn check_tag(&self, tag: &str, signed_tag: Option<&str>) -> anyhow::Result<()> {
+        if !self.accepts_tag(tag) {
+            bail!("Key tag `{tag}` isn't accepted by this node");
+        }
+
+        // Any key with an unsigned tag could be relabelled to another accepted tag
+        if signed_tag.is_none() && (self.require_signed_tag || tag != self.default_tag()) {
+            bail!("Key tag `{tag}` isn't signed into the key");
+        }
+
+        Ok(())
+    }
+
+    /// The tag of keys generated by the node
+    pub fn default_tag(&self) -> &str {
+        self.accepted_tags.first().map_or("dev", String::as_str)
+    }
+
+    pub fn validate(&self) -> anyhow::Result<()> {
+        if self.accepted_tags.is_empty() {
+            bail!("At least one accepted key tag is required");
         }
 
-        if let Ok(port_str) = env::var("AIMO_LISTEN_PORT") {
-            if let Ok(port) = port_str.parse::<u16>() {
-                default_opts.port = port;
+        for tag in &self.accepted_tags {
+            if tag.is_empty() || tag.contains('-') {
+                bail!("Invalid key tag `{tag}`: must be non-empty and contain no `-`");
             }
         }
 
-        default_opts
+        if self.network_id.is_empty() || self.network_id.contains('\n') {
+            bail!("Network id must be a non-empty single line");
+        }
+
+        if self.revocation_window == 0 {
+            bail!("Revocation window must be positive");
+        }
+
+        if self.revocation_prune_interval == 0 {
+            bail!("Revocation prune interval must be positive");
+        }
+
+        Ok(())
     }
 }
+
+/// Parse an environment variable, if set
+pub(super) fn parse_env<T>(name: &str) -> anyhow::Result<Option<T>>
+where
+    T: std::str::FromStr,
+    T::Err: std::fmt::Display,
+{
+    env::var(name)
+        .ok()
+        .map(|value| {
+            value
+                .parse::<T>()
+                .map_err(|err| anyhow!("Invalid {name}: {err}"))
+        })
+        .transpose()
+}
+
+/// Parse an environment variable holding JSON, if set
+fn json_env<T: DeserializeOwned>(name: &str) -> anyhow::Result<Option<T>> {
+    env::var(name)
+        .ok()
+        .map(|value| {
+            serde_json::from_str::<T>(&value).map_err(|err| anyhow!("Invalid {name}: {err}"))
+        })
+        .transpose()
+}
@@ -0,0 +1,56 @@
+use std::env;
+
+use anyhow::bail;
+use serde::{Deserialize, Serialize};
+
+use crate::config::server::parse_env;
+
+/// OpenTelemetry trace export
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct TelemetryOptions {
+    /// OTLP/HTTP collector base url, e.g. `http://localhost:4318`. Traces aren't exported if not set.
+    pub otlp_endpoint: Option<String>,
+
+    pub service_name: String,
+
+    /// Share of new traces recorded, from 0 to 1. Traces started upstream keep their decision.
+    pub sample_ratio: f64,
+}
+
+impl Default for TelemetryOptions {
+    fn default() -> Self {
+        Self {
+            otlp_endpoint: None,
+            service_name: "aimo-node".to_string(),
+            sample_ratio: 1.0,
+        }
+    }
+}
+
+impl TelemetryOptions {
+    /// Override options with the standard `OTEL_*` environment variables
+    pub fn apply_env(&mut self) -> anyhow::Result<()> {
+        if let Ok(endpoint) = env::var("OTEL_EXPORTER_OTLP_ENDPOINT") {
+            self.otlp_endpoint = Some(endpoint);
+        }
+
+        if let Ok(name) = env::var("OTEL_SERVICE_NAME") {
+            self.service_name = name;
+        }
+
+        if let Some(ratio) = parse_env("OTEL_TRACES_SAMPLER_ARG")? {
+            self.sample_ratio = ratio;
+        }
+
+        Ok(())
+    }
+
+    pub fn validate(&self) -> anyhow::Result<()> {
+        if !(0.0..=1.0).contains(&self.sample_ratio) {
+            bail!("`sample_ratio` must be between 0 and 1");
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,63 @@
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+/// Timeouts of the API server, in seconds. Each timeout is disabled when set to 0.
+///
+/// The provider subscription socket is never timed out.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct TimeoutOptions {
+    /// Time to serve a request on routes that don't stream
+    pub request: u64,
+
+    /// Time to wait for the first response of a provider
+    pub first_byte: u64,
+
+    /// Time allowed between two chunks of a stream
+    pub idle: u64,
+
+    /// Time a stream may last in total
+    pub stream_total: u64,
+
+    /// Time in-flight requests get to finish on shutdown
+    pub shutdown_grace: u64,
+}
+
+impl Default for TimeoutOptions {
+    fn default() -> Self {
+        Self {
+            request: 30,
+            first_byte: 60,
+            idle: 60,
+            stream_total: 600,
+            shutdown_grace: 30,
+        }
+    }
+}
+
+impl TimeoutOptions {
+    pub fn request(&self) -> Option<Duration> {
+        secs(self.request)
+    }
+
+    pub fn first_byte(&self) -> Option<Duration> {
+        secs(self.first_byte)
+    }
+
+    pub fn idle(&self) -> Option<Duration> {
+        secs(self.idle)
+    }
+
+    pub fn stream_total(&self) -> Option<Duration> {
+        secs(self.stream_total)
+    }
+
+    pub fn shutdown_grace(&self) -> Option<Duration> {
+        secs(self.shutdown_grace)
+    }
+}
+
+fn secs(secs: u64) -> Option<Duration> {
+    (secs > 0).then(|| Duration::from_secs(secs))
+}
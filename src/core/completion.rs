@@ -0,0 +1,379 @@
+//! OpenAI chat completion helpers
+//!
+//! Clients choose between a single `chat.completion` object and a stream of
+//! `chat.completion.chunk` events with the `stream` field, but providers don't always
+//! honour it. These helpers convert between the two shapes.
+
+use std::collections::BTreeMap;
+
+use anyhow::{anyhow, bail};
+use serde_json::{Map, Value, json};
+
+/// The data payload terminating an OpenAI event stream
+pub const DONE_MARKER: &str = "[DONE]";
+
+/// Token counts of an OpenAI `usage` block
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct TokenUsage {
+    pub prompt_tokens: u64,
+    pub completion_tokens: u64,
+    pub total_tokens: u64,
+}
+
+impl TokenUsage {
+    /// Read the `usage` block of a `chat.completion` object or a `chat.completion.chunk`.
+    ///
+    /// Returns `None` if there is no usage block, which is the case for every chunk but
+    /// the last one of a stream.
+    pub fn from_completion(completion: &Value) -> Option<Self> {
+        let usage = completion.get("usage").filter(|v| v.is_object())?;
+        let count = |field: &str| usage.get(field).and_then(Value::as_u64);
+
+        let prompt_tokens = count("prompt_tokens").unwrap_or(0);
+        let completion_tokens = count("completion_tokens").unwrap_or(0);
+
+        Some(Self {
+            prompt_tokens,
+            completion_tokens,
+            total_tokens: count("total_tokens").unwrap_or(prompt_tokens + completion_tokens),
+        })
+    }
+
+    /// Estimate the usage of a completion whose provider reported none, from the size of
+    /// the request's `messages` and the completion's `choices`.
+    ///
+    /// Counts a token per four bytes of JSON, which overestimates most text.
+    pub fn estimate(messages: &Value, completion: &Value) -> Self {
+        let count = |value: &Value| match value {
+            Value::Null => 0,
+            value => value.to_string().len().div_ceil(4) as u64,
+        };
+        let prompt_tokens = count(messages);
+        let completion_tokens = completion.get("choices").map_or(0, count);
+
+        Self {
+            prompt_tokens,
+            completion_tokens,
+            total_tokens: prompt_tokens + completion_tokens,
+        }
+    }
+}
+
+/// Incremental `text/event-stream` decoder.
+///
+/// Provider chunks don't necessarily align with event boundaries, so incomplete
+/// lines are buffered until the rest of them arrives.
+#[derive(Debug, Default)]
+pub struct SseDecoder {
+    buffer: String,
+    data: Vec<String>,
+}
+
+impl SseDecoder {
+    /// Feed a raw chunk and return the data of every event it completes
+    pub fn feed(&mut self, chunk: &str) -> Vec<String> {
+        self.buffer.push_str(chunk);
+
+        let mut events = Vec::new();
+        while let Some(pos) = self.buffer.find('\n') {
+            let line: String = self.buffer.drain(..=pos).collect();
+            let line = line.trim_end_matches(['\n', '\r']);
+
+            if line.is_empty() {
+                // A blank line dispatches the event
+                if !self.data.is_empty() {
+                    events.push(self.data.join("\n"));
+                    self.data.clear();
+                }
+            } else if let Some(value) = line.strip_prefix("data:") {
+                self.data
+                    .push(value.strip_prefix(' ').unwrap_or(value).to_string());
+            }
+            // Comments and other fields (`event`, `id`, `retry`) are not forwarded
+        }
+
+        events
+    }
+
+    /// Flush the pending event when the stream ends without a trailing blank line
+    pub fn finish(&mut self) -> Option<String> {
+        let rest = std::mem::take(&mut self.buffer);
+        if let Some(value) = rest.trim_end_matches('\r').strip_prefix("data:") {
+            self.data
+                .push(value.strip_prefix(' ').unwrap_or(value).to_string());
+        }
+
+        if self.data.is_empty() {
+            None
+        } else {
+            let data = self.data.join("\n");
+            self.data.clear();
+            Some(data)
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct ChoiceState {
+    role: Option<Value>,
+    content: Option<String>,
+    refusal: Option<String>,
+    tool_calls: BTreeMap<u64, ToolCallState>,
+    finish_reason: Option<Value>,
+}
+
+#[derive(Debug, Default)]
+struct ToolCallState {
+    id: Option<Value>,
+    kind: Option<Value>,
+    name: String,
+    arguments: String,
+}
+
+/// Assemble streamed `chat.completion.chunk` events into one `chat.completion` object
+#[derive(Debug, Default)]
+pub struct ChatCompletionAggregator {
+    id: Option<Value>,
+    created: Option<Value>,
+    model: Option<Value>,
+    system_fingerprint: Option<Value>,
+    choices: BTreeMap<u64, ChoiceState>,
+    usage: Option<Value>,
+}
+
+impl ChatCompletionAggregator {
+    /// Merge the data of one event into the completion.
+    ///
+    /// Returns error if the provider streamed an error object or a malformed chunk.
+    pub fn push(&mut self, data: &str) -> anyhow::Result<()> {
+        if data.trim() == DONE_MARKER {
+            return Ok(());
+        }
+
+        let chunk = serde_json::from_str::<Value>(data)
+            .map_err(|err| anyhow!("Invalid stream chunk: {err}"))?;
+
+        if let Some(error) = chunk.get("error") {
+            bail!("Provider streamed an error: {error}");
+        }
+
+        for (field, slot) in [
+            ("id", &mut self.id),
+            ("created", &mut self.created),
+            ("model", &mut self.model),
+            ("system_fingerprint", &mut self.system_fingerprint),
+        ] {
+            if slot.is_none() {
+                *slot = chunk.get(field).filter(|v| !v.is_null()).cloned();
+            }
+        }
+
+        if let Some(usage) = chunk.get("usage").filter(|v| !v.is_null()) {
+            self.usage = Some(usage.clone());
+        }
+
+        for choice in chunk
+            .get("choices")
+            .and_then(Value::as_array)
+            .into_iter()
+            .flatten()
+        {
+            let index = choice.get("index").and_then(Value::as_u64).unwrap_or(0);
+            let state = self.choices.entry(index).or_default();
+
+            if let Some(reason) = choice.get("finish_reason").filter(|v| !v.is_null()) {
+                state.finish_reason = Some(reason.clone());
+            }
+
+            let Some(delta) = choice.get("delta") else {
+                continue;
+            };
+
+            if let Some(role) = delta.get("role").filter(|v| !v.is_null()) {
+                state.role = Some(role.clone());
+            }
+            if let Some(content) = delta.get("content").and_then(Value::as_str) {
+                state.content.get_or_insert_default().push_str(content);
+            }
+            if let Some(refusal) = delta.get("refusal").and_then(Value::as_str) {
+                state.refusal.get_or_insert_default().push_str(refusal);
+            }
+
+            for call in delta
+                .get("tool_calls")
+                .and_then(Value::as_array)
+                .into_iter()
+                .flatten()
+            {
+                let call_index = call.get("index").and_then(Value::as_u64).unwrap_or(0);
+                let call_state = state.tool_calls.entry(call_index).or_default();
+
+                if let Some(id) = call.get("id").filter(|v| !v.is_null()) {
+                    call_state.id = Some(id.clone());
+                }
+                if let Some(kind) = call.get("type").filter(|v| !v.is_null()) {
+                    call_state.kind = Some(kind.clone());
+                }
+                if let Some(function) = call.get("function") {
+                    if let Some(name) = function.get("name").and_then(Value::as_str) {
+                        call_state.name.push_str(name);
+                    }
+                    if let Some(arguments) = function.get("arguments").and_then(Value::as_str) {
+                        call_state.arguments.push_str(arguments);
+                    }
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Build the final `chat.completion` object
+    pub fn finish(self) -> Value {
+        let choices = self
+            .choices
+            .into_iter()
+            .map(|(index, state)| {
+                let mut message = Map::new();
+                message.insert(
+                    "role".to_string(),
+                    state.role.unwrap_or(Value::String("assistant".to_string())),
+                );
+                message.insert(
+                    "content".to_string(),
+                    state.content.map_or(Value::Null, Value::String),
+                );
+                if let Some(refusal) = state.refusal {
+                    message.insert("refusal".to_string(), Value::String(refusal));
+                }
+                if !state.tool_calls.is_empty() {
+                    let calls = state
+                        .tool_calls
+                        .into_values()
+                        .map(|call| {
+                            json!({
+                                "id": call.id.unwrap_or(Value::Null),
+                                "type": call.kind.unwrap_or(Value::String("function".to_string())),
+                                "function": {
+                                    "name": call.name,
+                                    "arguments": call.arguments,
+                                },
+                            })
+                        })
+                        .collect();
+                    message.insert("tool_calls".to_string(), Value::Array(calls));
+                }
+
+                json!({
+                    "index": index,
+                    "message": message,
+                    "finish_reason": state.finish_reason.unwrap_or(Value::Null),
+                    "logprobs": null,
+                })
+            })
+            .collect::<Vec<_>>();
+
+        let mut completion = json!({
+            "id": self.id.unwrap_or(Value::Null),
+            "object": "chat.completion",
+            "created": self.created.unwrap_or(Value::Null),
+            "model": self.model.unwrap_or(Value::Null),
+            "choices": choices,
+        });
+        if let Some(fingerprint) = self.system_fingerprint {
+            completion["system_fingerprint"] = fingerprint;
+        }
+        if let Some(usage) = self.usage {
+            completion["usage"] = usage;
+        }
+
+        completion
+    }
+}
+
+/// Split a `chat.completion` object into the `chat.completion.chunk` events a streaming
+/// provider would have sent, not including the final `[DONE]` marker.
+pub fn completion_into_chunks(completion: &Value) -> Vec<Value> {
+    let chunk = |choices: Value| {
+        let mut chunk = json!({
+            "id": completion.get("id").cloned().unwrap_or(Value::Null),
+            "object": "chat.completion.chunk",
+            "created": completion.get("created").cloned().unwrap_or(Value::Null),
+            "model": completion.get("model").cloned().unwrap_or(Value::Null),
+            "choices": choices,
+        });
+        if let Some(fingerprint) = completion.get("system_fingerprint") {
+            chunk["system_fingerprint"] = fingerprint.clone();
+        }
+        chunk
+    };
+
+    let choices = completion
+        .get("choices")
+        .and_then(Value::as_array)
+        .cloned()
+        .unwrap_or_default();
+
+    let mut chunks = Vec::with_capacity(choices.len() * 2 + 1);
+    for (position, choice) in choices.iter().enumerate() {
+        let index = choice
+            .get("index")
+            .cloned()
+            .unwrap_or(Value::from(position));
+        let message = choice.get("message").cloned().unwrap_or(json!({}));
+
+        let mut delta = Map::new();
+        for field in ["role", "content", "refusal"] {
+            if let Some(value) = message.get(field).filter(|v| !v.is_null()) {
+                delta.insert(field.to_string(), value.clone());
+            }
+        }
+        if let Some(calls) = message.get("tool_calls").and_then(Value::as_array) {
+            let calls = calls
+                .iter()
+                .enumerate()
+                .map(|(call_index, call)| {
+                    let mut call = call.clone();
+                    call["index"] = Value::from(call_index);
+                    call
+                })
+                .collect();
+            delta.insert("tool_calls".to_string(), Value::Array(calls));
+        }
+
+        chunks.push(chunk(json!([{
+            "index": index,
+            "delta": delta,
+            "finish_reason": null,
+            "logprobs": choice.get("logprobs").cloned().unwrap_or(Value::Null),
+        }])));
+        chunks.push(chunk(json!([{
+            "index": index,
+            "delta": {},
+            "finish_reason": choice.get("finish_reason").cloned().unwrap_or(Value::Null),
+        }])));
+    }
+
+    // Usage goes last with no choices, like `stream_options.include_usage` does
+    if let Some(usage) = completion.get("usage").filter(|v| !v.is_null()) {
+        let mut usage_chunk = chunk(json!([]));
+        usage_chunk["usage"] = usage.clone();
+        chunks.push(usage_chunk);
+    }
+
+    chunks
+}
+
+/// A chunk without its `usage` block, for clients which didn't ask for usage with
+/// `stream_options.include_usage`. Usage-only chunks, which have no choices, are dropped.
+pub fn chunk_without_usage(mut chunk: Value) -> Option<Value> {
+    let choices = chunk.get("choices").and_then(Value::as_array);
+    if choices.is_some_and(Vec::is_empty) {
+        return None;
+    }
+
+    if let Some(chunk) = chunk.as_object_mut() {
+        chunk.remove("usage");
+    }
+    Some(chunk)
+}
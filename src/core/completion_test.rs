@@ -0,0 +1,181 @@
+use serde_json::{Value, json};
+
+use super::completion::*;
+
+fn chunk(delta: serde_json::Value, finish_reason: Option<&str>) -> String {
+    json!({
+        "id": "chatcmpl-1",
+        "object": "chat.completion.chunk",
+        "created": 1754401735,
+        "model": "gpt-4o",
+        "choices": [{ "index": 0, "delta": delta, "finish_reason": finish_reason }]
+    })
+    .to_string()
+}
+
+#[test]
+fn test_sse_decoder_split_chunks() {
+    let mut decoder = SseDecoder::default();
+
+    assert!(decoder.feed("data: {\"a\"").is_empty());
+    assert_eq!(
+        decoder.feed(":1}\n\n: keep-alive\n\ndata: [DONE]\r\n\r\n"),
+        vec!["{\"a\":1}", "[DONE]"]
+    );
+    assert_eq!(decoder.feed("data: tail"), Vec::<String>::new());
+    assert_eq!(decoder.finish().as_deref(), Some("tail"));
+}
+
+#[test]
+fn test_aggregate_chunks() {
+    let mut aggregator = ChatCompletionAggregator::default();
+    aggregator
+        .push(&chunk(json!({ "role": "assistant", "content": "" }), None))
+        .unwrap();
+    aggregator
+        .push(&chunk(json!({ "content": "Hello" }), None))
+        .unwrap();
+    aggregator
+        .push(&chunk(json!({ "content": " world" }), None))
+        .unwrap();
+    aggregator.push(&chunk(json!({}), Some("stop"))).unwrap();
+    aggregator
+        .push(
+            &json!({
+                "id": "chatcmpl-1",
+                "choices": [],
+                "usage": { "prompt_tokens": 3, "completion_tokens": 2, "total_tokens": 5 }
+            })
+            .to_string(),
+        )
+        .unwrap();
+    aggregator.push(DONE_MARKER).unwrap();
+
+    let completion = aggregator.finish();
+    assert_eq!(completion["object"], "chat.completion");
+    assert_eq!(completion["id"], "chatcmpl-1");
+    assert_eq!(completion["model"], "gpt-4o");
+    assert_eq!(completion["choices"][0]["message"]["role"], "assistant");
+    assert_eq!(
+        completion["choices"][0]["message"]["content"],
+        "Hello world"
+    );
+    assert_eq!(completion["choices"][0]["finish_reason"], "stop");
+    assert_eq!(completion["usage"]["total_tokens"], 5);
+}
+
+#[test]
+fn test_aggregate_tool_calls() {
+    let mut aggregator = ChatCompletionAggregator::default();
+    aggregator
+        .push(&chunk(
+            json!({ "role": "assistant", "tool_calls": [
+                { "index": 0, "id": "call_1", "type": "function", "function": { "name": "get_weather", "arguments": "" } }
+            ] }),
+            None,
+        ))
+        .unwrap();
+    aggregator
+        .push(&chunk(
+            json!({ "tool_calls": [{ "index": 0, "function": { "arguments": "{\"city\":" } }] }),
+            None,
+        ))
+        .unwrap();
+    aggregator
+        .push(&chunk(
+            json!({ "tool_calls": [{ "index": 0, "function": { "arguments": "\"Paris\"}" } }] }),
+            Some("tool_calls"),
+        ))
+        .unwrap();
+
+    let completion = aggregator.finish();
+    let call = &completion["choices"][0]["message"]["tool_calls"][0];
+    assert_eq!(call["id"], "call_1");
+    assert_eq!(call["function"]["name"], "get_weather");
+    assert_eq!(call["function"]["arguments"], "{\"city\":\"Paris\"}");
+    assert!(completion["choices"][0]["message"]["content"].is_null());
+}
+
+#[test]
+fn test_aggregate_error() {
+    let mut aggregator = ChatCompletionAggregator::default();
+    assert!(
+        aggregator
+            .push(&json!({ "error": { "message": "overloaded" } }).to_string())
+            .is_err()
+    );
+    assert!(aggregator.push("not json").is_err());
+}
+
+#[test]
+fn test_completion_round_trip() {
+    let completion = json!({
+        "id": "chatcmpl-2",
+        "object": "chat.completion",
+        "created": 1754401735,
+        "model": "gpt-4o",
+        "choices": [{
+            "index": 0,
+            "message": { "role": "assistant", "content": "Hi there" },
+            "finish_reason": "stop"
+        }],
+        "usage": { "prompt_tokens": 1, "completion_tokens": 2, "total_tokens": 3 }
+    });
+
+    let chunks = completion_into_chunks(&completion);
+    assert_eq!(chunks.len(), 3);
+    assert!(
+        chunks
+            .iter()
+            .all(|chunk| chunk["object"] == "chat.completion.chunk")
+    );
+    assert_eq!(chunks[0]["choices"][0]["delta"]["content"], "Hi there");
+    assert_eq!(chunks[1]["choices"][0]["finish_reason"], "stop");
+
+    let mut aggregator = ChatCompletionAggregator::default();
+    for chunk in &chunks {
+        aggregator.push(&chunk.to_string()).unwrap();
+    }
+    let rebuilt = aggregator.finish();
+    assert_eq!(
+        rebuilt["choices"][0]["message"],
+        completion["choices"][0]["message"]
+    );
+    assert_eq!(rebuilt["usage"], completion["usage"]);
+}
+
+#[test]
+fn test_chunk_without_usage() {
+    let usage = json!({ "prompt_tokens": 1, "completion_tokens": 2, "total_tokens": 3 });
+
+    // Usage-only chunks are dropped
+    let usage_chunk = json!({ "object": "chat.completion.chunk", "choices": [], "usage": usage });
+    assert_eq!(chunk_without_usage(usage_chunk), None);
+
+    // Usage is removed from chunks with content, which are kept
+    let content_chunk = json!({
+        "object": "chat.completion.chunk",
+        "choices": [{ "index": 0, "delta": { "content": "\"usage\"" } }],
+        "usage": usage,
+    });
+    let stripped = chunk_without_usage(content_chunk).unwrap();
+    assert!(stripped.get("usage").is_none());
+    assert_eq!(stripped["choices"][0]["delta"]["content"], "\"usage\"");
+}
+
+#[test]
+fn test_estimate_usage() {
+    let messages = json!([{ "role": "user", "content": "Hi" }]);
+    let completion = json!({ "choices": [{ "index": 0, "message": { "content": "Hello" } }] });
+
+    let tokens = TokenUsage::estimate(&messages, &completion);
+    assert_eq!(tokens.prompt_tokens, 8);
+    assert_eq!(tokens.completion_tokens, 11);
+    assert_eq!(tokens.total_tokens, 19);
+
+    // Nothing to count, nothing charged
+    assert_eq!(
+        TokenUsage::estimate(&Value::Null, &json!({})),
+        TokenUsage::default()
+    );
+}
@@ -0,0 +1,124 @@
+//! Ethereum wallet signatures: EIP-191 `personal_sign` over secp256k1
+//!
+//! Signers are 20 bytes addresses, shown as EIP-55 checksummed hex. Signatures are 65 bytes
+//! `r || s || v`, with `v` of `27` or `28` and a low `s` (EIP-2), so each signature has a
+//! single encoding and keys holding one can't be re-encoded into other valid keys.
+
+use anyhow::{anyhow, bail};
+use libsecp256k1::{Message, PublicKey, RecoveryId, SecretKey, Signature};
+use sha3::{Digest, Keccak256};
+
+pub const ADDRESS_LENGTH: usize = 20;
+pub const SIGNATURE_LENGTH: usize = 65;
+
+pub fn keccak256(bytes: &[u8]) -> [u8; 32] {
+    Keccak256::digest(bytes).into()
+}
+
+/// Hash of `message` as signed by `personal_sign`
+pub fn personal_message_hash(message: &[u8]) -> [u8; 32] {
+    let prefix = format!("\x19Ethereum Signed Message:\n{}", message.len());
+    keccak256(&[prefix.as_bytes(), message].concat())
+}
+
+/// Address of a public key: the last 20 bytes of the hash of its uncompressed point
+pub fn address_of(public_key: &PublicKey) -> [u8; ADDRESS_LENGTH] {
+    let hash = keccak256(&public_key.serialize()[1..]);
+    hash[32 - ADDRESS_LENGTH..]
+        .try_into()
+        .expect("hash is longer than an address")
+}
+
+/// Sign `message` like `personal_sign` does, with `v` set to `27` or `28`
+pub fn sign_message(secret_key: &SecretKey, message: &[u8]) -> [u8; SIGNATURE_LENGTH] {
+    let hash = Message::parse(&personal_message_hash(message));
+    let (signature, recovery_id) = libsecp256k1::sign(&hash, secret_key);
+
+    let mut bytes = [0; SIGNATURE_LENGTH];
+    bytes[..64].copy_from_slice(&signature.serialize());
+    bytes[64] = recovery_id.serialize() + 27;
+    bytes
+}
+
+/// Parse a canonical signature: `v` of `27` or `28` and a low `s`
+fn parse_signature(signature: &[u8]) -> anyhow::Result<(Signature, RecoveryId)> {
+    let [rs @ .., v] = signature else {
+        bail!("Empty signature");
+    };
+    if signature.len() != SIGNATURE_LENGTH {
+        bail!("Signature must be {SIGNATURE_LENGTH} bytes");
+    }
+
+    let recovery_id = match v {
+        27 | 28 => v - 27,
+        _ => bail!("Invalid signature recovery id {v}"),
+    };
+    let recovery_id =
+        RecoveryId::parse(recovery_id).map_err(|err| anyhow!("Invalid signature: {err:?}"))?;
+    let signature =
+        Signature::parse_standard_slice(rs).map_err(|err| anyhow!("Invalid signature: {err:?}"))?;
+    if signature.s.is_high() {
+        bail!("Invalid signature: `s` must be low");
+    }
+
+    Ok((signature, recovery_id))
+}
+
+/// Check a signature is canonical, without recovering its signer
+pub fn check_signature(signature: &[u8]) -> anyhow::Result<()> {
+    parse_signature(signature).map(|_| ())
+}
+
+/// Recover the address that signed `message` with `personal_sign`. Only canonical
+/// signatures are accepted.
+pub fn recover_address(message: &[u8], signature: &[u8]) -> anyhow::Result<[u8; ADDRESS_LENGTH]> {
+    let (signature, recovery_id) = parse_signature(signature)?;
+    let hash = Message::parse(&personal_message_hash(message));
+
+    let public_key = libsecp256k1::recover(&hash, &signature, &recovery_id)
+        .map_err(|err| anyhow!("Failed to recover signer: {err:?}"))?;
+    Ok(address_of(&public_key))
+}
+
+/// Decode `0x`-prefixed or bare hex
+pub fn decode_hex(value: &str) -> anyhow::Result<Vec<u8>> {
+    let value = value.strip_prefix("0x").unwrap_or(value);
+    Ok(hex::decode(value)?)
+}
+
+/// Encode an address as EIP-55 mixed-case checksummed hex
+pub fn to_checksum_address(address: &[u8]) -> String {
+    let lower = hex::encode(address);
+    let hash = keccak256(lower.as_bytes());
+
+    let checksummed: String = lower
+        .chars()
+        .enumerate()
+        .map(|(i, c)| {
+            let nibble = (hash[i / 2] >> (if i % 2 == 0 { 4 } else { 0 })) & 0x0f;
+            if nibble >= 8 {
+                c.to_ascii_uppercase()
+            } else {
+                c
+            }
+        })
+        .collect();
+    format!("0x{checksummed}")
+}
+
+/// Parse a hex address. Mixed-case addresses must have a valid EIP-55 checksum.
+pub fn parse_address(value: &str) -> anyhow::Result<[u8; ADDRESS_LENGTH]> {
+    let bytes = decode_hex(value)?;
+    let address: [u8; ADDRESS_LENGTH] = bytes[..]
+        .try_into()
+        .map_err(|_| anyhow!("Address must be {ADDRESS_LENGTH} bytes"))?;
+
+    let digits = value.strip_prefix("0x").unwrap_or(value);
+    let is_mixed_case = digits.chars().any(|c| c.is_ascii_uppercase())
+        && digits.chars().any(|c| c.is_ascii_lowercase());
+    if is_mixed_case && to_checksum_address(&address)[2..] != *digits {
+        bail!("Invalid address checksum");
+    }
+
+    Ok(address)
+}
@@ -0,0 +1,81 @@
+use libsecp256k1::{PublicKey, SecretKey, Signature};
+
+use super::ethereum::*;
+
+// Test vector from the web3.js `accounts.sign` docs
+const PRIVATE_KEY: &str = "0x4c0883a69102937d6231471b5dbb6204fe5129617082792ae468d01a3f362318";
+const ADDRESS: &str = "0x2c7536E3605D9C16a7a3D7b1898e529396a65c23";
+const SIGNATURE: &str = "b91467e570a6466aa9e9876cbcd013baba02900b8979d43fe208a4a4f339f5fd6007e74cd82e037b800186422fc2da167c747ef045e5d18a5f5d4300f8e1a0291c";
+
+fn secret_key() -> SecretKey {
+    SecretKey::parse_slice(&decode_hex(PRIVATE_KEY).unwrap()).unwrap()
+}
+
+#[test]
+fn test_personal_sign() {
+    let message = b"Some data";
+    assert_eq!(
+        hex::encode(personal_message_hash(message)),
+        "1da44b586eb0729ff70a73c326926f6ed5a25f5b056e7f47fbc6e58d86871655"
+    );
+
+    let signature = sign_message(&secret_key(), message);
+    assert_eq!(hex::encode(signature), SIGNATURE);
+
+    let address = recover_address(message, &signature).unwrap();
+    assert_eq!(to_checksum_address(&address), ADDRESS);
+    assert_eq!(
+        address,
+        address_of(&PublicKey::from_secret_key(&secret_key()))
+    );
+
+    // Other messages recover other signers
+    assert_ne!(recover_address(b"Other data", &signature).unwrap(), address);
+
+    let mut signature = signature;
+    signature[64] = 5;
+    assert!(recover_address(message, &signature).is_err());
+    assert!(recover_address(message, &signature[..64]).is_err());
+}
+
+#[test]
+fn test_checksum_address() {
+    let address = parse_address(ADDRESS).unwrap();
+    assert_eq!(to_checksum_address(&address), ADDRESS);
+
+    // Single-case addresses have no checksum
+    assert_eq!(parse_address(&ADDRESS.to_lowercase()).unwrap(), address);
+    assert_eq!(
+        parse_address(&ADDRESS[2..].to_uppercase()).unwrap(),
+        address
+    );
+
+    let typo = ADDRESS.replace("2c7536E", "2c7536e");
+    assert!(parse_address(&typo).is_err());
+    assert!(parse_address("0x2c7536").is_err());
+}
+
+/// The same signature with `v` of `0`/`1`, or with `s` negated and `v` flipped, which both
+/// recover the same signer
+pub fn malleate(signature: &[u8; SIGNATURE_LENGTH]) -> [[u8; SIGNATURE_LENGTH]; 2] {
+    let mut bare_v = *signature;
+    bare_v[64] -= 27;
+
+    let mut high_s = *signature;
+    let mut parsed = Signature::parse_standard_slice(&signature[..64]).unwrap();
+    parsed.s = -parsed.s;
+    high_s[..64].copy_from_slice(&parsed.serialize());
+    high_s[64] = if signature[64] == 27 { 28 } else { 27 };
+
+    [bare_v, high_s]
+}
+
+#[test]
+fn test_canonical_signature() {
+    let message = b"Some data";
+    let signature = sign_message(&secret_key(), message);
+
+    for malleated in malleate(&signature) {
+        assert!(recover_address(message, &malleated).is_err());
+    }
+}
@@ -0,0 +1,77 @@
+use std::{
+    collections::BTreeMap,
+    sync::{Arc, Mutex},
+};
+
+use serde::Serialize;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SubsystemState {
+    Running,
+
+    /// Failed and waiting for its restart backoff
+    Restarting,
+
+    /// Failed and won't be restarted
+    Stopped,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SubsystemStatus {
+    pub state: SubsystemState,
+
+    /// Restarts since the node started
+    pub restarts: u32,
+
+    pub last_error: Option<String>,
+}
+
+/// Status of the node's supervised subsystems, by name
+#[derive(Debug, Clone, Default)]
+pub struct Health {
+    subsystems: Arc<Mutex<BTreeMap<&'static str, SubsystemStatus>>>,
+}
+
+impl Health {
+    pub fn set_running(&self, name: &'static str) {
+        self.update(name, |status| status.state = SubsystemState::Running);
+    }
+
+    pub fn set_restarting(&self, name: &'static str, error: String) {
+        self.update(name, |status| {
+            status.state = SubsystemState::Restarting;
+            status.restarts += 1;
+            status.last_error = Some(error);
+        });
+    }
+
+    pub fn set_stopped(&self, name: &'static str, error: String) {
+        self.update(name, |status| {
+            status.state = SubsystemState::Stopped;
+            status.last_error = Some(error);
+        });
+    }
+
+    fn update(&self, name: &'static str, f: impl FnOnce(&mut SubsystemStatus)) {
+        let mut subsystems = self.subsystems.lock().unwrap();
+        f(subsystems.entry(name).or_insert(SubsystemStatus {
+            state: SubsystemState::Running,
+            restarts: 0,
+            last_error: None,
+        }));
+    }
+
+    pub fn subsystems(&self) -> BTreeMap<&'static str, SubsystemStatus> {
+        self.subsystems.lock().unwrap().clone()
+    }
+
+    /// All subsystems are running
+    pub fn is_ready(&self) -> bool {
+        self.subsystems
+            .lock()
+            .unwrap()
+            .values()
+            .all(|status| status.state == SubsystemState::Running)
+    }
+}
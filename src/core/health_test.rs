@@ -0,0 +1,26 @@
+use crate::core::health::{Health, SubsystemState};
+
+#[test]
+fn test_health() {
+    let health = Health::default();
+    health.set_running("router");
+    health.set_running("api_server");
+    assert!(health.is_ready());
+
+    health.set_restarting("router", "panicked".to_string());
+    assert!(!health.is_ready());
+
+    health.set_running("router");
+    assert!(health.is_ready());
+
+    let router = &health.subsystems()["router"];
+    assert_eq!(router.state, SubsystemState::Running);
+    assert_eq!(router.restarts, 1);
+    assert_eq!(router.last_error.as_deref(), Some("panicked"));
+
+    health.set_stopped("api_server", "stopped unexpectedly".to_string());
+    assert_eq!(
+        health.subsystems()["api_server"].state,
+        SubsystemState::Stopped
+    );
+}
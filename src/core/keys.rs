@@ -1,14 +1,17 @@
 //! TODO: Compress secret keys with raw bytes
 
-use std::str::FromStr;
+use std::{fmt, net::IpAddr, str::FromStr};
 
 use anyhow::{Ok, anyhow, bail};
 use chrono::{DateTime, Utc};
+use ipnet::IpNet;
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 use sha2::{Digest, Sha256};
 use solana_sdk::{pubkey::Pubkey, signature::Signature};
 
+use crate::core::ethereum;
+
 /// Secret key format
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct SecretKeyV1 {
@@ -69,18 +72,7 @@ impl SecretKeyV1 {
             bail!("Wrong signature");
         }
 
-        // Check expiry
-        if let Some(dt) = DateTime::<Utc>::from_timestamp_millis(
-            self.metadata.created_at + self.metadata.valid_for,
-        ) {
-            if dt < Utc::now() {
-                bail!("Expired");
-            }
-        } else {
-            bail!("Invalid timestamp");
-        }
-
-        Ok(())
+        check_expiry(self.metadata.created_at, self.metadata.valid_for)
     }
 
     pub fn into_hash(self) -> anyhow::Result<[u8; 32]> {
@@ -91,34 +83,197 @@ impl SecretKeyV1 {
     }
 }
 
+fn check_expiry(created_at: i64, valid_for: i64) -> anyhow::Result<()> {
+    if let Some(dt) = DateTime::<Utc>::from_timestamp_millis(created_at + valid_for) {
+        if dt < Utc::now() {
+            bail!("Expired");
+        }
+    } else {
+        bail!("Invalid timestamp");
+    }
+
+    Ok(())
+}
+
+/// First 8 bytes of the SHA-256 hash of a signer's address, base58 encoded
+pub fn signer_fingerprint(signer: &str) -> String {
+    let hash = Sha256::digest(signer.as_bytes());
+    bs58::encode(&hash[..8]).into_string()
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct MetadataV1 {
     pub created_at: i64,
     pub valid_for: i64,
+    /// Total tokens the key may consume, 0 for unlimited
     pub usage_limit: u64,
     pub scopes: Vec<Scope>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema, PartialEq)]
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, JsonSchema, PartialEq, Eq)]
 pub enum Wallet {
     #[serde(rename = "solana")]
     Solana,
+
+    /// Keys signed with `personal_sign` (EIP-191), only supported by V2 keys
+    #[serde(rename = "ethereum")]
+    Ethereum,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema, PartialEq)]
+impl Wallet {
+    pub fn from_byte(wallet: WalletEnum) -> anyhow::Result<Self> {
+        match wallet {
+            wallets::SOLANA => Ok(Self::Solana),
+            wallets::ETHEREUM => Ok(Self::Ethereum),
+            wallet => bail!("Unsupported wallet type in secret key: {wallet}"),
+        }
+    }
+
+    pub fn as_byte(&self) -> WalletEnum {
+        match self {
+            Self::Solana => wallets::SOLANA,
+            Self::Ethereum => wallets::ETHEREUM,
+        }
+    }
+
+    /// The wallet of a signer: `0x` prefixed Ethereum addresses, or base58 Solana public keys
+    pub fn of_signer(signer: &str) -> Self {
+        if signer.starts_with("0x") {
+            Self::Ethereum
+        } else {
+            Self::Solana
+        }
+    }
+
+    /// Decode a signer of this wallet: base58 for Solana, hex addresses for Ethereum
+    pub fn decode_signer(&self, signer: &str) -> anyhow::Result<Vec<u8>> {
+        match self {
+            Self::Solana => Ok(bs58::decode(signer).into_vec()?),
+            Self::Ethereum => Ok(ethereum::parse_address(signer)?.to_vec()),
+        }
+    }
+
+    /// Decode a signature of this wallet: base58 for Solana, hex for Ethereum
+    pub fn decode_signature(&self, signature: &str) -> anyhow::Result<Vec<u8>> {
+        match self {
+            Self::Solana => Ok(bs58::decode(signature).into_vec()?),
+            Self::Ethereum => ethereum::decode_hex(signature),
+        }
+    }
+
+    pub fn encode_signer(&self, signer: &[u8]) -> String {
+        match self {
+            Self::Solana => bs58::encode(signer).into_string(),
+            Self::Ethereum => ethereum::to_checksum_address(signer),
+        }
+    }
+
+    pub fn encode_signature(&self, signature: &[u8]) -> String {
+        match self {
+            Self::Solana => bs58::encode(signature).into_string(),
+            Self::Ethereum => format!("0x{}", hex::encode(signature)),
+        }
+    }
+
+    /// Whether `signature` is `signer`'s signature of `message`
+    ///
+    /// Errors if the signer or signature is malformed.
+    pub fn verify(&self, signer: &str, signature: &str, message: &[u8]) -> anyhow::Result<bool> {
+        match self {
+            Self::Solana => {
+                let public_key = Pubkey::from_str(signer)?;
+                let signature = Signature::from_str(signature)?;
+                Ok(signature.verify(public_key.as_ref(), message))
+            }
+            Self::Ethereum => {
+                let address = ethereum::parse_address(signer)?;
+                let signature = ethereum::decode_hex(signature)?;
+                ethereum::check_signature(&signature)?;
+                // Signatures that recover to no key don't belong to any signer
+                Ok(ethereum::recover_address(message, &signature).is_ok_and(|a| a == address))
+            }
+        }
+    }
+
+    /// Whether two signers of this wallet are the same, ignoring the case of Ethereum
+    /// addresses
+    pub fn is_same_signer(&self, a: &str, b: &str) -> bool {
+        match self {
+            Self::Solana => a == b,
+            Self::Ethereum => a.eq_ignore_ascii_case(b),
+        }
+    }
+}
+
+/// What a secret key may be used for
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, JsonSchema, PartialEq, Eq, Hash)]
 pub enum Scope {
+    /// Request chat completions
     #[serde(rename = "completion_model")]
     CompletionModel,
+
+    /// Request embeddings
+    #[serde(rename = "embedding_model")]
+    EmbeddingModel,
+
+    /// Subscribe to a node as a service provider
+    #[serde(rename = "provider")]
+    Provider,
+
+    /// Revoke other keys of the same signer
+    #[serde(rename = "revoke")]
+    Revoke,
+
+    /// Manage the node
+    #[serde(rename = "admin")]
+    Admin,
+}
+
+impl Scope {
+    pub const ALL: [Scope; 5] = [
+        Scope::CompletionModel,
+        Scope::EmbeddingModel,
+        Scope::Provider,
+        Scope::Revoke,
+        Scope::Admin,
+    ];
+
+    /// Position of the scope in a metadata scope bitmap
+    pub fn bit(self) -> ScopeBitMap {
+        match self {
+            Scope::CompletionModel => scopes::COMPLETION_MODEL,
+            Scope::EmbeddingModel => scopes::EMBEDDING_MODEL,
+            Scope::Provider => scopes::PROVIDER,
+            Scope::Revoke => scopes::REVOKE,
+            Scope::Admin => scopes::ADMIN,
+        }
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Scope::CompletionModel => "completion_model",
+            Scope::EmbeddingModel => "embedding_model",
+            Scope::Provider => "provider",
+            Scope::Revoke => "revoke",
+            Scope::Admin => "admin",
+        }
+    }
+}
+
+impl fmt::Display for Scope {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
 }
 
 impl FromStr for Scope {
     type Err = anyhow::Error;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        match s {
-            "completion_model" => Ok(Self::CompletionModel),
-            _ => Err(anyhow!("Scope {s} not supported")),
-        }
+        Self::ALL
+            .into_iter()
+            .find(|scope| scope.as_str() == s)
+            .ok_or(anyhow!("Scope {s} not supported"))
     }
 }
 
@@ -201,15 +356,10 @@ impl TryFrom<SecretKeyRawV1> for SecretKeyV1 {
     type Error = anyhow::Error;
 
     fn try_from(value: SecretKeyRawV1) -> Result<Self, Self::Error> {
-        if value.wallet >= wallets::TOTAL_WALLETS_SUPPORTED {
-            bail!("Unsupported wallet type in secret key: {}", value.wallet);
-        }
-
+        // V1 keys have fixed Solana signer and signature lengths
         let wallet = match value.wallet {
             wallets::SOLANA => Wallet::Solana,
-
-            // This will never happen
-            _ => Wallet::Solana,
+            wallet => bail!("Unsupported wallet type in V1 secret key: {wallet}"),
         };
 
         let signer = bs58::encode(&value.signer).into_string();
@@ -278,9 +428,10 @@ impl TryFrom<MetadataV1> for MetadataRawV1 {
     /// Keep `TryFrom` here even though this doesn't produce errors now
     fn try_from(value: MetadataV1) -> Result<Self, Self::Error> {
         // Convert options list into a bitmap
-        let bitmap: ScopeBitMap = value.scopes.iter().fold(0, |bm, scope| match scope {
-            Scope::CompletionModel => bm | 1 << scopes::COMPLETION_MODEL,
-        });
+        let bitmap: ScopeBitMap = value
+            .scopes
+            .iter()
+            .fold(0, |bm, scope| bm | 1 << scope.bit());
 
         Ok(Self {
             created_at: value.created_at,
@@ -307,29 +458,1057 @@ impl TryFrom<MetadataRawV1> for MetadataV1 {
             bail!("Secret key contains currently unsupported scope type");
         }
 
-        let scopes = if value.scopes | scopes::COMPLETION_MODEL > 0 {
-            vec![Scope::CompletionModel]
+        let scopes = Scope::ALL
+            .into_iter()
+            .filter(|scope| value.scopes & (1 << scope.bit()) != 0)
+            .collect();
+
+        Ok(Self {
+            created_at: value.created_at,
+            valid_for: value.valid_for,
+            usage_limit: value.usage_limit,
+            scopes,
+        })
+    }
+}
+
+/// Secret key format V2, which secret keys of every version decode into
+///
+/// Version 1 keys keep their V1 encoding and signature, and have no extension fields.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct SecretKeyV2 {
+    pub version: u8,
+    pub wallet: Wallet,
+    pub signer: String,
+    pub signature: String,
+    pub metadata: MetadataV2,
+}
+
+impl SecretKeyV2 {
+    /// Encode the secret key into a string in the form of:
+    ///
+    /// `aimo-sk-{scope}-{base58check_encoded_secret_key_bytes}`
+    ///
+    /// Version 1 keys are encoded without a checksum. Keys with a signed tag must be
+    /// encoded with that tag.
+    pub fn into_string(self, scope: &str) -> anyhow::Result<String> {
+        if scope.is_empty() || scope.contains('-') {
+            bail!("Invalid secret key tag `{scope}`: must be non-empty and contain no `-`");
+        }
+        if let Some(tag) = &self.metadata.tag
+            && tag != scope
+        {
+            bail!("Secret key is signed for tag `{tag}`, not `{scope}`");
+        }
+
+        match self.version {
+            1 => SecretKeyV1::try_from(self)?.into_string(scope),
+            2 => {
+                let raw = SecretKeyRawV2::try_from(self)?;
+                let base58_encoded = bs58::encode(raw.into_bytes()).with_check().into_string();
+                Ok(format!("aimo-sk-{scope}-{base58_encoded}"))
+            }
+            version => bail!("Unsupported secret key version {version}"),
+        }
+    }
+
+    /// Decode a secret key of any version, dispatching on its version byte
+    pub fn decode(sk: &str) -> anyhow::Result<(String, Self)> {
+        let (scope, key) = SecretKeyV1::split_sk_string(sk).ok_or(anyhow!(
+            "Invalid secret key: Failed to split secret key into valid parts"
+        ))?;
+        let decoded_bytes = bs58::decode(key).into_vec()?;
+
+        let secret_key: Self = match decoded_bytes.first() {
+            Some(1) => SecretKeyV1::decode(sk)?.1.into(),
+            Some(2) => {
+                let checked_bytes = bs58::decode(key)
+                    .with_check(Some(2))
+                    .into_vec()
+                    .map_err(|_| anyhow!("Invalid secret key: Checksum mismatch"))?;
+                Self::from_bytes(&checked_bytes[..])?
+            }
+            Some(version) => bail!("Unsupported secret key version {version}"),
+            None => bail!("Invalid secret key: Empty payload"),
+        };
+
+        // The prefix isn't signed, so it must not be relabelled
+        if let Some(tag) = &secret_key.metadata.tag
+            && tag != scope
+        {
+            bail!("Invalid secret key: Tag `{scope}` doesn't match its signed tag `{tag}`");
+        }
+
+        Ok((scope.to_string(), secret_key))
+    }
+
+    /// Tag and hash of the bytes of an encoded key, without parsing or verifying it
+    ///
+    /// Unlike `into_hash`, the hash covers the signature of V2 keys, so a copy of a key
+    /// with another signature doesn't share it.
+    pub fn hash_encoded(sk: &str) -> anyhow::Result<(String, [u8; 32])> {
+        let (scope, key) = SecretKeyV1::split_sk_string(sk).ok_or(anyhow!(
+            "Invalid secret key: Failed to split secret key into valid parts"
+        ))?;
+        let mut bytes = bs58::decode(key).into_vec()?;
+        if bytes.first() == Some(&SecretKeyRawV2::VERSION) {
+            bytes = bs58::decode(key)
+                .with_check(Some(SecretKeyRawV2::VERSION))
+                .into_vec()
+                .map_err(|_| anyhow!("Invalid secret key: Checksum mismatch"))?;
+        }
+
+        Ok((scope.to_string(), Sha256::digest(&bytes).into()))
+    }
+
+    /// Unix timestamp in milliseconds the key, or the first of its ancestors, expires at
+    pub fn expires_at(&self) -> i64 {
+        self.chain()
+            .map(|key| key.metadata.created_at + key.metadata.valid_for)
+            .min()
+            .unwrap_or_default()
+    }
+
+    /// Decode raw bytes of a key of any version, dispatching on its version byte
+    pub fn from_bytes(bytes: &[u8]) -> anyhow::Result<Self> {
+        match bytes.first() {
+            Some(1) => Ok(SecretKeyV1::try_from(SecretKeyRawV1::from_bytes(bytes)?)?.into()),
+            Some(2) => SecretKeyRawV2::from_bytes(bytes)?.try_into(),
+            Some(version) => bail!("Unsupported secret key version {version}"),
+            None => bail!("Invalid secret key: Empty payload"),
+        }
+    }
+
+    /// Verify the signature and expiry of the key and, for delegated keys, of every
+    /// ancestor
+    pub fn verify_signature(&self) -> anyhow::Result<()> {
+        if self.version == 1 {
+            return SecretKeyV1::try_from(self.clone())?.verify_signature();
+        }
+
+        let bytes = self.metadata.signing_bytes(self.version)?;
+        let is_valid = self.wallet.verify(&self.signer, &self.signature, &bytes)?;
+
+        if !is_valid {
+            bail!("Wrong signature");
+        }
+
+        check_expiry(self.metadata.created_at, self.metadata.valid_for)?;
+        self.verify_parent()
+    }
+
+    /// Verify the keys the key is delegated from
+    ///
+    /// Parents are embedded without their signature, which the key's own signature by the
+    /// parent's wallet stands for. Only delegation grants, which their delegate signs for,
+    /// carry theirs.
+    fn verify_parent(&self) -> anyhow::Result<()> {
+        let Some(parent) = &self.metadata.parent else {
+            return Ok(());
+        };
+
+        let verified = if parent.is_delegation_grant() {
+            parent.verify_signature()
         } else {
-            vec![]
+            check_expiry(parent.metadata.created_at, parent.metadata.valid_for)
+                .and_then(|_| parent.verify_parent())
         };
+        verified.map_err(|err| anyhow!("Invalid parent key: {err}"))?;
+
+        self.check_delegation(parent)
+    }
+
+    /// Whether the key names a delegate. Keys its delegate signs embed its signature, so
+    /// it can only sign delegated keys and not be used itself.
+    pub fn is_delegation_grant(&self) -> bool {
+        self.metadata.delegate.is_some()
+    }
+
+    /// The key followed by its ancestors, nearest first
+    pub fn chain(&self) -> impl Iterator<Item = &SecretKeyV2> {
+        std::iter::successors(Some(self), |key| key.metadata.parent.as_deref())
+    }
+
+    /// Check that a delegated key is signed by its parent's wallet or delegate, and only
+    /// narrows what its parent allows
+    fn check_delegation(&self, parent: &SecretKeyV2) -> anyhow::Result<()> {
+        let is_signer = |wallet: Wallet, signer: &str| {
+            self.wallet == wallet && wallet.is_same_signer(&self.signer, signer)
+        };
+        let delegate = parent.metadata.delegate.as_ref();
+        if !is_signer(parent.wallet, &parent.signer)
+            && !delegate.is_some_and(|delegate| is_signer(delegate.wallet, &delegate.signer))
+        {
+            bail!("Delegated key isn't signed by its parent's wallet or delegate");
+        }
+
+        let (metadata, parent) = (&self.metadata, &parent.metadata);
+        if let Some(scope) = metadata
+            .scopes
+            .iter()
+            .find(|scope| !parent.has_scope(**scope))
+        {
+            bail!("Delegated key can't have the `{scope}` scope its parent lacks");
+        }
+
+        if metadata.created_at + metadata.valid_for > parent.created_at + parent.valid_for {
+            bail!("Delegated key can't outlive its parent");
+        }
+
+        if parent.usage_limit > 0
+            && (metadata.usage_limit == 0 || metadata.usage_limit > parent.usage_limit)
+        {
+            bail!(
+                "Delegated key can't exceed its parent's usage limit of {} tokens",
+                parent.usage_limit
+            );
+        }
+
+        if parent.tag.is_some() && metadata.tag != parent.tag {
+            bail!("Delegated key must have its parent's tag");
+        }
+
+        metadata.restrictions.check_narrows(&parent.restrictions)
+    }
+
+    /// Short, stable identifier of the key's signer for logs
+    pub fn signer_fingerprint(&self) -> String {
+        signer_fingerprint(&self.signer)
+    }
+
+    /// Hash identifying the key, which revocations and usage are recorded by
+    ///
+    /// V2 keys hash their bytes without the signature, so they hash the same as their
+    /// copies embedded in delegated keys. Version 1 keys hash the same as
+    /// `SecretKeyV1::into_hash`.
+    pub fn into_hash(self) -> anyhow::Result<[u8; 32]> {
+        if self.version == 1 {
+            return SecretKeyV1::try_from(self)?.into_hash();
+        }
+
+        let bytes = self.unsigned_bytes()?;
+        let mut hasher = Sha256::new();
+        hasher.update(&bytes[..]);
+        Ok(hasher.finalize().into())
+    }
+
+    /// Bytes of a V2 key without its signature, as its delegated keys embed it
+    fn unsigned_bytes(&self) -> anyhow::Result<Vec<u8>> {
+        if self.version != SecretKeyRawV2::VERSION {
+            bail!("Secret key version {} isn't a V2 key", self.version);
+        }
+
+        let wallet = self.wallet.as_byte();
+        let signer = self.wallet.decode_signer(&self.signer)?;
+        if wallets::key_lengths(wallet).map(|(len, _)| len) != Some(signer.len()) {
+            bail!("Invalid signer length for the wallet");
+        }
+
+        Ok([
+            vec![SecretKeyRawV2::VERSION, wallet],
+            signer,
+            MetadataRawV2::try_from(self.metadata.clone())?.into_bytes(),
+        ]
+        .concat())
+    }
+
+    /// Decode a V2 key embedded without its signature, with up to `ancestors` keys it's
+    /// delegated from
+    fn from_unsigned_bytes(bytes: &[u8], ancestors: usize) -> anyhow::Result<Self> {
+        let [version, wallet, rest @ ..] = bytes else {
+            bail!("Bytes too short for a parent key");
+        };
+        if *version != SecretKeyRawV2::VERSION {
+            bail!("Only V2 keys can be delegated from");
+        }
+
+        let (signer_len, _) = wallets::key_lengths(*wallet)
+            .ok_or(anyhow!("Unsupported wallet type in parent key: {wallet}"))?;
+        if rest.len() < signer_len {
+            bail!("Bytes too short for a parent key");
+        }
+        let (signer, metadata) = rest.split_at(signer_len);
+        let wallet = Wallet::from_byte(*wallet)?;
 
         Ok(Self {
+            version: SecretKeyRawV2::VERSION,
+            wallet,
+            signer: wallet.encode_signer(signer),
+            signature: String::new(),
+            metadata: MetadataV2::from_raw(MetadataRawV2::from_bytes(metadata)?, ancestors)?,
+        })
+    }
+}
+
+impl From<SecretKeyV1> for SecretKeyV2 {
+    fn from(value: SecretKeyV1) -> Self {
+        Self {
+            version: value.version,
+            wallet: value.wallet,
+            signer: value.signer,
+            signature: value.signature,
+            metadata: value.metadata.into(),
+        }
+    }
+}
+
+impl TryFrom<SecretKeyV2> for SecretKeyV1 {
+    type Error = anyhow::Error;
+
+    fn try_from(value: SecretKeyV2) -> Result<Self, Self::Error> {
+        if value.version != 1 {
+            bail!("Secret key version {} isn't a V1 key", value.version);
+        }
+
+        Ok(Self {
+            version: value.version,
+            wallet: value.wallet,
+            signer: value.signer,
+            signature: value.signature,
+            metadata: value.metadata.try_into()?,
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct MetadataV2 {
+    pub created_at: i64,
+    pub valid_for: i64,
+    /// Total tokens the key may consume, 0 for unlimited
+    pub usage_limit: u64,
+    pub scopes: Vec<Scope>,
+
+    /// Human-readable name of the key
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub label: Option<String>,
+
+    /// Tag the key is issued for, which its `aimo-sk-{tag}-` prefix must match
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tag: Option<String>,
+
+    #[serde(default, skip_serializing_if = "KeyRestrictions::is_empty")]
+    pub restrictions: KeyRestrictions,
+
+    /// Signer allowed to sign delegated keys of this key, besides its own wallet
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub delegate: Option<KeyDelegate>,
+
+    /// Key this key is delegated from, which it can only narrow
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub parent: Option<Box<SecretKeyV2>>,
+
+    /// Non-critical extension fields unknown to this node, kept to rebuild the signed bytes
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub extensions: Vec<ExtensionField>,
+}
+
+impl MetadataV2 {
+    pub fn has_scope(&self, scope: Scope) -> bool {
+        self.scopes.contains(&scope)
+    }
+
+    /// The bytes a key of `version` with this metadata is signed over
+    pub fn signing_bytes(&self, version: u8) -> anyhow::Result<Vec<u8>> {
+        match version {
+            1 => Ok(MetadataRawV1::try_from(MetadataV1::try_from(self.clone())?)?.into_bytes()),
+            2 => Ok([
+                fields::SIGNING_DOMAIN.to_vec(),
+                MetadataRawV2::try_from(self.clone())?.into_bytes(),
+            ]
+            .concat()),
+            version => bail!("Unsupported secret key version {version}"),
+        }
+    }
+}
+
+impl From<MetadataV1> for MetadataV2 {
+    fn from(value: MetadataV1) -> Self {
+        Self {
             created_at: value.created_at,
             valid_for: value.valid_for,
             usage_limit: value.usage_limit,
-            scopes,
+            scopes: value.scopes,
+            label: None,
+            tag: None,
+            restrictions: KeyRestrictions::default(),
+            delegate: None,
+            parent: None,
+            extensions: vec![],
+        }
+    }
+}
+
+impl TryFrom<MetadataV2> for MetadataV1 {
+    type Error = anyhow::Error;
+
+    fn try_from(value: MetadataV2) -> Result<Self, Self::Error> {
+        if value.label.is_some()
+            || value.tag.is_some()
+            || !value.restrictions.is_empty()
+            || value.delegate.is_some()
+            || value.parent.is_some()
+            || !value.extensions.is_empty()
+        {
+            bail!("V1 secret keys can't have extension fields");
+        }
+
+        Ok(Self {
+            created_at: value.created_at,
+            valid_for: value.valid_for,
+            usage_limit: value.usage_limit,
+            scopes: value.scopes,
         })
     }
 }
 
+/// Limits on what a key can be used for, from where and when. Unset restrictions allow
+/// everything.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize, JsonSchema)]
+#[serde(default)]
+pub struct KeyRestrictions {
+    /// Service ids the key may request, or register as a provider
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub services: Option<Vec<String>>,
+
+    /// Model names the key may request, without the `<target>:` prefix
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub models: Option<Vec<String>>,
+
+    /// Networks the key may be used from
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[schemars(with = "Option<Vec<String>>")]
+    pub addresses: Option<Vec<IpNet>>,
+
+    /// Unix timestamp in milliseconds before which the key is rejected
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub not_before: Option<i64>,
+
+    /// Completion requests the key may have in flight at the same time
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_streams: Option<u32>,
+}
+
+impl KeyRestrictions {
+    pub fn is_empty(&self) -> bool {
+        self == &Self::default()
+    }
+
+    pub fn check_time(&self, now: DateTime<Utc>) -> anyhow::Result<()> {
+        if let Some(not_before) = self.not_before
+            && now.timestamp_millis() < not_before
+        {
+            let not_before = DateTime::<Utc>::from_timestamp_millis(not_before)
+                .ok_or(anyhow!("Invalid timestamp"))?;
+            bail!("Secret key is not valid before {}", not_before.to_rfc3339());
+        }
+
+        Ok(())
+    }
+
+    pub fn check_address(&self, address: Option<IpAddr>) -> anyhow::Result<()> {
+        let Some(networks) = &self.addresses else {
+            return Ok(());
+        };
+
+        match address {
+            Some(address) if networks.iter().any(|net| net.contains(&address)) => Ok(()),
+            Some(address) => bail!("Secret key is not allowed from {address}"),
+            None => bail!("Secret key is restricted by address, but the client address is unknown"),
+        }
+    }
+
+    pub fn check_service(&self, service_id: &str) -> anyhow::Result<()> {
+        if let Some(services) = &self.services
+            && !services.iter().any(|service| service == service_id)
+        {
+            bail!("Secret key is not allowed to use service `{service_id}`");
+        }
+
+        Ok(())
+    }
+
+    pub fn check_model(&self, model: &str) -> anyhow::Result<()> {
+        if let Some(models) = &self.models
+            && !models.iter().any(|allowed| allowed == model)
+        {
+            bail!("Secret key is not allowed to use model `{model}`");
+        }
+
+        Ok(())
+    }
+
+    /// Check that these restrictions are at least as strict as `parent`'s
+    pub fn check_narrows(&self, parent: &KeyRestrictions) -> anyhow::Result<()> {
+        fn narrows<T>(own: &Option<T>, parent: &Option<T>, f: impl Fn(&T, &T) -> bool) -> bool {
+            match (own, parent) {
+                (_, None) => true,
+                (None, Some(_)) => false,
+                (Some(own), Some(parent)) => f(own, parent),
+            }
+        }
+        let subset =
+            |own: &Vec<String>, parent: &Vec<String>| own.iter().all(|v| parent.contains(v));
+
+        let loosened = [
+            (
+                "services",
+                narrows(&self.services, &parent.services, subset),
+            ),
+            ("models", narrows(&self.models, &parent.models, subset)),
+            (
+                "addresses",
+                narrows(&self.addresses, &parent.addresses, |own, parent| {
+                    own.iter()
+                        .all(|net| parent.iter().any(|allowed| allowed.contains(net)))
+                }),
+            ),
+            (
+                "not_before",
+                narrows(&self.not_before, &parent.not_before, |own, parent| {
+                    own >= parent
+                }),
+            ),
+            (
+                "max_streams",
+                narrows(&self.max_streams, &parent.max_streams, |own, parent| {
+                    own <= parent
+                }),
+            ),
+        ]
+        .into_iter()
+        .find(|(_, narrows)| !narrows);
+
+        if let Some((restriction, _)) = loosened {
+            bail!("Delegated key can't loosen its parent's `{restriction}` restriction");
+        }
+
+        Ok(())
+    }
+
+    fn into_fields(self) -> anyhow::Result<Vec<ExtensionField>> {
+        let strings = |kind: u8, values: Vec<String>| -> anyhow::Result<ExtensionField> {
+            Ok(ExtensionField {
+                kind,
+                value: encode_list(values.into_iter().map(String::into_bytes))?,
+            })
+        };
+
+        let mut fields = vec![];
+        if let Some(services) = self.services {
+            fields.push(strings(fields::SERVICES, services)?);
+        }
+        if let Some(models) = self.models {
+            fields.push(strings(fields::MODELS, models)?);
+        }
+        if let Some(addresses) = self.addresses {
+            fields.push(ExtensionField {
+                kind: fields::ADDRESSES,
+                value: encode_list(addresses.into_iter().map(|net| {
+                    let mut bytes = match net.addr() {
+                        IpAddr::V4(addr) => addr.octets().to_vec(),
+                        IpAddr::V6(addr) => addr.octets().to_vec(),
+                    };
+                    bytes.push(net.prefix_len());
+                    bytes
+                }))?,
+            });
+        }
+        if let Some(not_before) = self.not_before {
+            fields.push(ExtensionField {
+                kind: fields::NOT_BEFORE,
+                value: not_before.to_be_bytes().to_vec(),
+            });
+        }
+        if let Some(max_streams) = self.max_streams {
+            fields.push(ExtensionField {
+                kind: fields::MAX_STREAMS,
+                value: max_streams.to_be_bytes().to_vec(),
+            });
+        }
+
+        Ok(fields)
+    }
+
+    /// Apply a restriction field, returning fields of other types
+    fn apply_field(&mut self, field: ExtensionField) -> anyhow::Result<Option<ExtensionField>> {
+        let strings = |value: &[u8]| -> anyhow::Result<Vec<String>> {
+            decode_list(value)?
+                .into_iter()
+                .map(|bytes| Ok(String::from_utf8(bytes.to_vec())?))
+                .collect()
+        };
+
+        match field.kind {
+            fields::SERVICES => self.services = Some(strings(&field.value)?),
+            fields::MODELS => self.models = Some(strings(&field.value)?),
+            fields::ADDRESSES => {
+                let networks = decode_list(&field.value)?
+                    .into_iter()
+                    .map(|bytes| {
+                        let net = match *bytes {
+                            [a, b, c, d, prefix] => IpNet::new(IpAddr::from([a, b, c, d]), prefix)?,
+                            [ref addr @ .., prefix] if addr.len() == 16 => {
+                                let addr: [u8; 16] = addr.try_into()?;
+                                IpNet::new(IpAddr::from(addr), prefix)?
+                            }
+                            _ => bail!("Invalid network in secret key restrictions"),
+                        };
+                        Ok(net)
+                    })
+                    .collect::<anyhow::Result<_>>()?;
+                self.addresses = Some(networks);
+            }
+            fields::NOT_BEFORE => {
+                self.not_before = Some(i64::from_be_bytes(field.value[..].try_into()?))
+            }
+            fields::MAX_STREAMS => {
+                self.max_streams = Some(u32::from_be_bytes(field.value[..].try_into()?))
+            }
+            _ => return Ok(Some(field)),
+        }
+
+        Ok(None)
+    }
+}
+
+/// Encode values as a list of 1 byte lengths followed by the value
+fn encode_list(values: impl Iterator<Item = Vec<u8>>) -> anyhow::Result<Vec<u8>> {
+    let mut bytes = vec![];
+    for value in values {
+        let len = u8::try_from(value.len())
+            .map_err(|_| anyhow!("Secret key restriction values are limited to 255 bytes"))?;
+        bytes.push(len);
+        bytes.extend(value);
+    }
+
+    Ok(bytes)
+}
+
+fn decode_list(mut bytes: &[u8]) -> anyhow::Result<Vec<&[u8]>> {
+    let mut values = vec![];
+    while let [len, rest @ ..] = bytes {
+        let len = *len as usize;
+        if rest.len() < len {
+            bail!("Truncated list in metadata extension field");
+        }
+        let (value, rest) = rest.split_at(len);
+        values.push(value);
+        bytes = rest;
+    }
+
+    Ok(values)
+}
+
+/// Signer a key allows to sign its delegated keys
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct KeyDelegate {
+    pub wallet: Wallet,
+    pub signer: String,
+}
+
+impl KeyDelegate {
+    fn into_bytes(self) -> anyhow::Result<Vec<u8>> {
+        let signer = self.wallet.decode_signer(&self.signer)?;
+        Ok([vec![self.wallet.as_byte()], signer].concat())
+    }
+
+    fn from_bytes(bytes: &[u8]) -> anyhow::Result<Self> {
+        let [wallet, signer @ ..] = bytes else {
+            bail!("Empty delegate field");
+        };
+        let wallet = Wallet::from_byte(*wallet)?;
+        if wallets::key_lengths(wallet.as_byte()).map(|(len, _)| len) != Some(signer.len()) {
+            bail!("Invalid delegate signer length for the wallet");
+        }
+
+        Ok(Self {
+            wallet,
+            signer: wallet.encode_signer(signer),
+        })
+    }
+}
+
+impl FromStr for KeyDelegate {
+    type Err = anyhow::Error;
+
+    /// Parse a signer: `0x` prefixed Ethereum addresses, or base58 Solana public keys
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let wallet = Wallet::of_signer(s);
+        let signer = wallet.decode_signer(s)?;
+        Self::from_bytes(&[vec![wallet.as_byte()], signer].concat())
+    }
+}
+
+/// A type-length-value extension field of V2 metadata
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct ExtensionField {
+    /// Field type. Types with the `fields::CRITICAL` bit set must be understood to accept
+    /// the key.
+    pub kind: u8,
+    pub value: Vec<u8>,
+}
+
+impl ExtensionField {
+    pub fn is_critical(&self) -> bool {
+        self.kind & fields::CRITICAL != 0
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct SecretKeyRawV2 {
+    pub wallet: WalletEnum,      // 1 byte
+    pub signer: Vec<u8>,         // Wallet dependent, 32 bytes for Solana, 20 for Ethereum
+    pub signature: Vec<u8>,      // Wallet dependent, 64 bytes for Solana, 65 for Ethereum
+    pub metadata: MetadataRawV2, // 32 bytes and extension fields
+}
+
+impl SecretKeyRawV2 {
+    pub const VERSION: u8 = 2;
+
+    pub fn into_bytes(self) -> Vec<u8> {
+        [
+            vec![Self::VERSION, self.wallet],
+            self.signer,
+            self.signature,
+            self.metadata.into_bytes(),
+        ]
+        .concat()
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> anyhow::Result<Self> {
+        let [version, wallet, rest @ ..] = bytes else {
+            bail!("Bytes too short for a secret key");
+        };
+        if *version != Self::VERSION {
+            bail!("Unexpected secret key version {version}");
+        }
+
+        let (signer_len, signature_len) = wallets::key_lengths(*wallet)
+            .ok_or(anyhow!("Unsupported wallet type in secret key: {wallet}"))?;
+        if rest.len() < signer_len + signature_len {
+            bail!("Bytes too short for a secret key");
+        }
+        let (signer, rest) = rest.split_at(signer_len);
+        let (signature, metadata) = rest.split_at(signature_len);
+
+        Ok(Self {
+            wallet: *wallet,
+            signer: signer.to_vec(),
+            signature: signature.to_vec(),
+            metadata: MetadataRawV2::from_bytes(metadata)?,
+        })
+    }
+}
+
+impl TryFrom<SecretKeyV2> for SecretKeyRawV2 {
+    type Error = anyhow::Error;
+
+    fn try_from(value: SecretKeyV2) -> Result<Self, Self::Error> {
+        if value.version != Self::VERSION {
+            bail!("Secret key version {} isn't a V2 key", value.version);
+        }
+
+        let wallet = value.wallet.as_byte();
+        let signer = value.wallet.decode_signer(&value.signer)?;
+        let signature = value.wallet.decode_signature(&value.signature)?;
+        if wallets::key_lengths(wallet) != Some((signer.len(), signature.len())) {
+            bail!("Invalid signer or signature length for the wallet");
+        }
+
+        Ok(Self {
+            wallet,
+            signer,
+            signature,
+            metadata: value.metadata.try_into()?,
+        })
+    }
+}
+
+impl TryFrom<SecretKeyRawV2> for SecretKeyV2 {
+    type Error = anyhow::Error;
+
+    fn try_from(value: SecretKeyRawV2) -> Result<Self, Self::Error> {
+        Self::from_raw(value, fields::MAX_CHAIN_LENGTH - 1)
+    }
+}
+
+impl SecretKeyV2 {
+    /// Decode a raw key with up to `ancestors` keys it's delegated from
+    fn from_raw(value: SecretKeyRawV2, ancestors: usize) -> anyhow::Result<Self> {
+        let wallet = Wallet::from_byte(value.wallet)?;
+        // Other encodings of a signature would make other keys of the same one
+        if wallet == Wallet::Ethereum {
+            ethereum::check_signature(&value.signature)?;
+        }
+
+        Ok(Self {
+            version: SecretKeyRawV2::VERSION,
+            signer: wallet.encode_signer(&value.signer),
+            signature: wallet.encode_signature(&value.signature),
+            wallet,
+            metadata: MetadataV2::from_raw(value.metadata, ancestors)?,
+        })
+    }
+}
+
+/// V1 metadata followed by extension fields in ascending type order, each encoded as a
+/// 1 byte type, a 2 bytes big-endian length and the value
+#[derive(Debug, Clone)]
+pub struct MetadataRawV2 {
+    pub base: MetadataRawV1,
+    pub fields: Vec<ExtensionField>,
+}
+
+impl MetadataRawV2 {
+    pub fn into_bytes(self) -> Vec<u8> {
+        let mut bytes = self.base.into_bytes();
+        for field in self.fields {
+            bytes.push(field.kind);
+            bytes.extend((field.value.len() as u16).to_be_bytes());
+            bytes.extend(field.value);
+        }
+
+        bytes
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> anyhow::Result<Self> {
+        if bytes.len() < MetadataRawV1::BYTES {
+            bail!("Bytes too short for secret key metadata");
+        }
+        let (base, mut rest) = bytes.split_at(MetadataRawV1::BYTES);
+
+        let mut fields: Vec<ExtensionField> = vec![];
+        while !rest.is_empty() {
+            let [kind, len_high, len_low, tail @ ..] = rest else {
+                bail!("Truncated metadata extension field");
+            };
+            let len = u16::from_be_bytes([*len_high, *len_low]) as usize;
+            if tail.len() < len {
+                bail!("Truncated metadata extension field {kind:#04x}");
+            }
+
+            // One encoding per metadata, so re-encoding reproduces the signed bytes
+            if fields.last().is_some_and(|last| last.kind >= *kind) {
+                bail!("Metadata extension fields must be unique and in ascending order");
+            }
+
+            let (value, tail) = tail.split_at(len);
+            fields.push(ExtensionField {
+                kind: *kind,
+                value: value.to_vec(),
+            });
+            rest = tail;
+        }
+
+        Ok(Self {
+            base: MetadataRawV1::from_bytes(base)?,
+            fields,
+        })
+    }
+}
+
+impl TryFrom<MetadataV2> for MetadataRawV2 {
+    type Error = anyhow::Error;
+
+    fn try_from(value: MetadataV2) -> Result<Self, Self::Error> {
+        let mut fields = value.extensions;
+        if let Some(field) = fields.iter().find(|field| field.is_critical()) {
+            bail!("Unsupported critical metadata field {:#04x}", field.kind);
+        }
+
+        if let Some(label) = value.label {
+            fields.push(ExtensionField {
+                kind: fields::LABEL,
+                value: label.into_bytes(),
+            });
+        }
+        if let Some(tag) = value.tag {
+            fields.push(ExtensionField {
+                kind: fields::TAG,
+                value: tag.into_bytes(),
+            });
+        }
+        if let Some(delegate) = value.delegate {
+            fields.push(ExtensionField {
+                kind: fields::DELEGATE,
+                value: delegate.into_bytes()?,
+            });
+        }
+        if let Some(parent) = value.parent {
+            if parent.chain().count() >= fields::MAX_CHAIN_LENGTH {
+                bail!("Delegation chain too long");
+            }
+            // The signature would make the parent a bearer key anyone given the delegated
+            // key could use
+            if parent.is_delegation_grant() {
+                fields.push(ExtensionField {
+                    kind: fields::PARENT_SIGNATURE,
+                    value: parent.wallet.decode_signature(&parent.signature)?,
+                });
+            }
+            fields.push(ExtensionField {
+                kind: fields::PARENT,
+                value: parent.unsigned_bytes()?,
+            });
+        }
+        fields.extend(value.restrictions.into_fields()?);
+
+        fields.sort_by_key(|field| field.kind);
+        if fields.windows(2).any(|pair| pair[0].kind == pair[1].kind) {
+            bail!("Duplicate metadata extension fields");
+        }
+        if fields
+            .iter()
+            .any(|field| field.value.len() > u16::MAX as usize)
+        {
+            bail!("Metadata extension field too long");
+        }
+
+        let base = MetadataRawV1::try_from(MetadataV1 {
+            created_at: value.created_at,
+            valid_for: value.valid_for,
+            usage_limit: value.usage_limit,
+            scopes: value.scopes,
+        })?;
+
+        Ok(Self { base, fields })
+    }
+}
+
+impl TryFrom<MetadataRawV2> for MetadataV2 {
+    type Error = anyhow::Error;
+
+    fn try_from(value: MetadataRawV2) -> Result<Self, Self::Error> {
+        Self::from_raw(value, fields::MAX_CHAIN_LENGTH - 1)
+    }
+}
+
+impl MetadataV2 {
+    /// Decode raw metadata with up to `ancestors` keys it's delegated from
+    fn from_raw(value: MetadataRawV2, ancestors: usize) -> anyhow::Result<Self> {
+        let mut metadata = MetadataV2::from(MetadataV1::try_from(value.base)?);
+        let mut parent_signature = None;
+
+        for field in value.fields {
+            let Some(field) = metadata.restrictions.apply_field(field)? else {
+                continue;
+            };
+
+            match field.kind {
+                fields::LABEL => metadata.label = Some(String::from_utf8(field.value)?),
+                fields::TAG => metadata.tag = Some(String::from_utf8(field.value)?),
+                fields::DELEGATE => {
+                    metadata.delegate = Some(KeyDelegate::from_bytes(&field.value)?)
+                }
+                fields::PARENT => {
+                    // Keys come from unauthenticated requests, so nesting is bounded before
+                    // decoding the parent, let alone verifying it
+                    let Some(ancestors) = ancestors.checked_sub(1) else {
+                        bail!("Delegation chain too long");
+                    };
+                    let parent = SecretKeyV2::from_unsigned_bytes(&field.value, ancestors)?;
+                    metadata.parent = Some(Box::new(parent));
+                }
+                fields::PARENT_SIGNATURE => parent_signature = Some(field.value),
+                kind if field.is_critical() => {
+                    bail!("Secret key contains unsupported critical field {kind:#04x}")
+                }
+                _ => metadata.extensions.push(field),
+            }
+        }
+
+        // One encoding per metadata: the signature of delegation grants, and no other
+        match (metadata.parent.as_deref_mut(), parent_signature) {
+            (Some(parent), Some(signature)) if parent.is_delegation_grant() => {
+                if wallets::key_lengths(parent.wallet.as_byte()).map(|(_, len)| len)
+                    != Some(signature.len())
+                {
+                    bail!("Invalid parent signature length for the wallet");
+                }
+                if parent.wallet == Wallet::Ethereum {
+                    ethereum::check_signature(&signature)?;
+                }
+                parent.signature = parent.wallet.encode_signature(&signature);
+            }
+            (Some(parent), None) if !parent.is_delegation_grant() => {}
+            (None, None) => {}
+            (Some(parent), _) if parent.is_delegation_grant() => {
+                bail!("Parent key naming a delegate must carry its signature")
+            }
+            _ => bail!("Only parent keys naming a delegate carry their signature"),
+        }
+
+        Ok(metadata)
+    }
+}
+
 pub mod wallets {
     //! Signing wallets supported: `0x00` - `0xFF`
 
     use super::WalletEnum;
-    pub const TOTAL_WALLETS_SUPPORTED: WalletEnum = 1;
+    use crate::core::ethereum::{ADDRESS_LENGTH, SIGNATURE_LENGTH};
 
     /// The default option: Solana wallets
     pub const SOLANA: WalletEnum = 0x00;
+
+    /// Ethereum wallets signing with `personal_sign`, V2 keys only
+    pub const ETHEREUM: WalletEnum = 0x01;
+
+    /// Signer and signature lengths of a wallet's keys
+    pub fn key_lengths(wallet: WalletEnum) -> Option<(usize, usize)> {
+        match wallet {
+            SOLANA => Some((32, 64)),
+            ETHEREUM => Some((ADDRESS_LENGTH, SIGNATURE_LENGTH)),
+            _ => None,
+        }
+    }
+}
+
+pub mod fields {
+    //! Extension field types of V2 metadata: `0x00` - `0xFF`
+    //!
+    //! Nodes reject keys with critical fields they don't understand, and ignore unknown
+    //! non-critical fields.
+
+    /// Bit set on the types of critical fields
+    pub const CRITICAL: u8 = 0x80;
+
+    /// Prefix of the bytes V2 keys are signed over, so they can't pass as V1 signatures
+    pub const SIGNING_DOMAIN: &[u8] = b"aimo-sk-v2";
+
+    /// Field: human-readable label of the key, UTF-8
+    pub const LABEL: u8 = 0x01;
+
+    /// Field: service ids the key may use, a list of UTF-8 strings
+    pub const SERVICES: u8 = CRITICAL | 0x01;
+
+    /// Field: model names the key may use, a list of UTF-8 strings
+    pub const MODELS: u8 = CRITICAL | 0x02;
+
+    /// Field: networks the key may be used from, a list of addresses followed by their
+    /// prefix length
+    pub const ADDRESSES: u8 = CRITICAL | 0x03;
+
+    /// Field: time before which the key is rejected, 8 bytes big-endian unix milliseconds
+    pub const NOT_BEFORE: u8 = CRITICAL | 0x04;
+
+    /// Field: maximum concurrent completion requests, 4 bytes big-endian
+    pub const MAX_STREAMS: u8 = CRITICAL | 0x05;
+
+    /// Field: tag of the `aimo-sk-{tag}-` prefix the key is issued for, UTF-8
+    pub const TAG: u8 = CRITICAL | 0x06;
+
+    /// Field: signer allowed to sign delegated keys, a wallet type followed by the signer
+    pub const DELEGATE: u8 = 0x02;
+
+    /// Field: parent key a delegated key narrows, as its raw bytes without the signature
+    pub const PARENT: u8 = CRITICAL | 0x07;
+
+    /// Field: signature of the parent key, only for parents naming a delegate
+    pub const PARENT_SIGNATURE: u8 = CRITICAL | 0x08;
+
+    /// Most keys in a delegation chain, the root key included
+    pub const MAX_CHAIN_LENGTH: usize = 4;
 }
 
 pub mod scopes {
@@ -338,10 +1517,30 @@ pub mod scopes {
 
     use super::ScopeBitMap;
 
-    pub const SCOPES_SUPPORTED: ScopeBitMap = 0x01;
+    pub const SCOPES_SUPPORTED: ScopeBitMap = 0x1F;
 
-    /// Scope: `model:completion`
+    /// Scope: `completion_model`
     ///
     /// Position: `0x01` (1 << 0)
     pub const COMPLETION_MODEL: ScopeBitMap = 0;
+
+    /// Scope: `embedding_model`
+    ///
+    /// Position: `0x02` (1 << 1)
+    pub const EMBEDDING_MODEL: ScopeBitMap = 1;
+
+    /// Scope: `provider`
+    ///
+    /// Position: `0x04` (1 << 2)
+    pub const PROVIDER: ScopeBitMap = 2;
+
+    /// Scope: `revoke`
+    ///
+    /// Position: `0x08` (1 << 3)
+    pub const REVOKE: ScopeBitMap = 3;
+
+    /// Scope: `admin`
+    ///
+    /// Position: `0x10` (1 << 4)
+    pub const ADMIN: ScopeBitMap = 4;
 }
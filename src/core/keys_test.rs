@@ -1,10 +1,12 @@
+use chrono::{DateTime, Duration, Utc};
 use solana_sdk::{signature::Keypair, signer::Signer};
 
-use super::keys::*;
+use super::{ethereum, keys::*};
+use crate::test_utils::{metadata_v2, sign_v2};
 
 fn create_metadata() -> MetadataV1 {
     MetadataV1 {
-        created_at: 1754401735372,
+        created_at: Utc::now().timestamp_millis(),
         valid_for: 5_000_000_000,
         usage_limit: 1234,
         scopes: vec![Scope::CompletionModel],
@@ -25,7 +27,7 @@ fn create_sk() -> SecretKeyV1 {
         wallet: Wallet::Solana,
         signer: keypair.pubkey().to_string(),
         signature,
-        metadata: create_metadata(),
+        metadata,
     }
 }
 
@@ -87,6 +89,46 @@ fn test_raw_metadata() {
     assert_eq!(bytes.len(), MetadataRawV1::BYTES);
 }
 
+#[test]
+fn test_scope_bitmap() {
+    let mut metadata = create_metadata();
+    metadata.scopes = vec![Scope::Provider, Scope::Admin];
+    let raw = MetadataRawV1::try_from(metadata).unwrap();
+    assert_eq!(raw.scopes, 0b10100);
+
+    // Only the scopes set in the bitmap are decoded
+    let decoded = MetadataV1::try_from(raw).unwrap();
+    assert_eq!(decoded.scopes, vec![Scope::Provider, Scope::Admin]);
+    assert!(!MetadataV2::from(decoded).has_scope(Scope::CompletionModel));
+
+    let all = MetadataRawV1 {
+        scopes: scopes::SCOPES_SUPPORTED,
+        ..raw
+    };
+    assert_eq!(
+        MetadataV1::try_from(all).unwrap().scopes,
+        Scope::ALL.to_vec()
+    );
+
+    let unsupported = MetadataRawV1 {
+        scopes: 1 << 5,
+        ..raw
+    };
+    assert!(MetadataV1::try_from(unsupported).is_err());
+}
+
+#[test]
+fn test_scope_parse() {
+    for scope in Scope::ALL {
+        assert_eq!(scope.to_string().parse::<Scope>().unwrap(), scope);
+        assert_eq!(
+            serde_json::to_string(&scope).unwrap(),
+            format!("\"{scope}\"")
+        );
+    }
+    assert!("completion".parse::<Scope>().is_err());
+}
+
 #[test]
 fn test_verify() {
     let sk = create_sk();
@@ -104,3 +146,464 @@ fn test_hash() {
     let sk = create_sk();
     assert!(sk.into_hash().is_ok());
 }
+
+#[test]
+fn test_signer_fingerprint() {
+    let sk = SecretKeyV2::from(create_sk());
+    let fingerprint = sk.signer_fingerprint();
+
+    assert_eq!(fingerprint, signer_fingerprint(&sk.signer));
+    assert_ne!(fingerprint, signer_fingerprint(&create_sk().signer));
+    assert!(!sk.signer.contains(&fingerprint));
+}
+
+#[test]
+fn test_v2_encode_decode() {
+    let extension = ExtensionField {
+        kind: 0x42,
+        value: vec![1, 2, 3],
+    };
+    let sk = sign_v2(
+        &Keypair::new(),
+        MetadataV2 {
+            label: Some("ci".to_string()),
+            extensions: vec![extension.clone()],
+            ..metadata_v2()
+        },
+    );
+    let sk_string = sk.clone().into_string("test").unwrap();
+    let (scope, decoded) = SecretKeyV2::decode(&sk_string).unwrap();
+
+    assert_eq!(scope, "test");
+    assert_eq!(decoded.version, 2);
+    assert_eq!(decoded.metadata, sk.metadata);
+    assert_eq!(decoded.metadata.label.as_deref(), Some("ci"));
+    assert_eq!(decoded.metadata.extensions, vec![extension]);
+
+    // Unknown non-critical fields are still covered by the signature
+    assert!(decoded.verify_signature().is_ok());
+    assert_eq!(decoded.into_hash().unwrap(), sk.into_hash().unwrap());
+}
+
+#[test]
+fn test_v2_checksum() {
+    let sk_string = sign_v2(&Keypair::new(), metadata_v2())
+        .into_string("test")
+        .unwrap();
+
+    // Swap the last two characters of the key
+    let mut chars: Vec<char> = sk_string.chars().collect();
+    let len = chars.len();
+    chars.swap(len - 1, len - 2);
+    let typo: String = chars.into_iter().collect();
+
+    if typo != sk_string {
+        let err = SecretKeyV2::decode(&typo).unwrap_err();
+        assert!(err.to_string().contains("Checksum"));
+    }
+}
+
+#[test]
+fn test_v2_decodes_v1() {
+    let sk = create_sk();
+    let sk_string = sk.clone().into_string("test").unwrap();
+    let (_, decoded) = SecretKeyV2::decode(&sk_string).unwrap();
+
+    assert_eq!(decoded.version, 1);
+    assert!(decoded.verify_signature().is_ok());
+    assert_eq!(
+        decoded.clone().into_hash().unwrap(),
+        sk.into_hash().unwrap()
+    );
+    assert_eq!(decoded.into_string("test").unwrap(), sk_string);
+}
+
+#[test]
+fn test_v2_signature_domain() {
+    // A V1 signature doesn't verify the same metadata as a V2 key
+    let sk = SecretKeyV2 {
+        version: 2,
+        ..create_sk().into()
+    };
+    assert!(sk.verify_signature().is_err());
+}
+
+#[test]
+fn test_v2_critical_fields() {
+    let sk = sign_v2(&Keypair::new(), metadata_v2());
+    let mut raw = SecretKeyRawV2::try_from(sk).unwrap();
+    raw.metadata.fields.push(ExtensionField {
+        kind: fields::CRITICAL | 0x42,
+        value: vec![],
+    });
+
+    let bytes = raw.into_bytes();
+    let err = SecretKeyV2::try_from(SecretKeyRawV2::from_bytes(&bytes).unwrap()).unwrap_err();
+    assert!(err.to_string().contains("critical"));
+}
+
+#[test]
+fn test_v2_field_order() {
+    let sk = sign_v2(&Keypair::new(), metadata_v2());
+    let mut raw = SecretKeyRawV2::try_from(sk).unwrap();
+    for kind in [0x02, 0x01] {
+        raw.metadata.fields.push(ExtensionField {
+            kind,
+            value: vec![],
+        });
+    }
+
+    assert!(SecretKeyRawV2::from_bytes(&raw.into_bytes()).is_err());
+}
+
+#[test]
+fn test_unknown_version() {
+    let bytes = [vec![3u8], vec![0; 129]].concat();
+    let sk_string = format!("aimo-sk-test-{}", bs58::encode(bytes).into_string());
+
+    assert!(SecretKeyV2::decode(&sk_string).is_err());
+}
+
+fn restrictions() -> KeyRestrictions {
+    KeyRestrictions {
+        services: Some(vec!["provider".to_string()]),
+        models: Some(vec!["gpt-4o".to_string(), "gpt-4o-mini".to_string()]),
+        addresses: Some(vec![
+            "10.0.0.0/8".parse().unwrap(),
+            "2001:db8::/32".parse().unwrap(),
+        ]),
+        not_before: Some(1_700_000_000_000),
+        max_streams: Some(2),
+    }
+}
+
+#[test]
+fn test_restrictions_encode_decode() {
+    let mut sk = sign_v2(&Keypair::new(), metadata_v2());
+    sk.metadata.restrictions = restrictions();
+
+    let raw = MetadataRawV2::try_from(sk.metadata.clone()).unwrap();
+    assert!(raw.fields.iter().all(ExtensionField::is_critical));
+    assert_eq!(raw.fields.len(), 5);
+
+    let decoded = MetadataV2::try_from(MetadataRawV2::from_bytes(&raw.into_bytes()).unwrap());
+    assert_eq!(decoded.unwrap(), sk.metadata);
+
+    // Restrictions can't be carried by V1 keys
+    assert!(sk.metadata.signing_bytes(1).is_err());
+}
+
+#[test]
+fn test_restrictions_check() {
+    let restrictions = restrictions();
+
+    assert!(restrictions.check_service("provider").is_ok());
+    let err = restrictions.check_service("other").unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "Secret key is not allowed to use service `other`"
+    );
+
+    assert!(restrictions.check_model("gpt-4o-mini").is_ok());
+    assert!(restrictions.check_model("gpt-4").is_err());
+
+    assert!(
+        restrictions
+            .check_address(Some([10, 1, 2, 3].into()))
+            .is_ok()
+    );
+    assert!(
+        restrictions
+            .check_address(Some("2001:db8::1".parse().unwrap()))
+            .is_ok()
+    );
+    assert!(
+        restrictions
+            .check_address(Some([192, 168, 0, 1].into()))
+            .is_err()
+    );
+    assert!(restrictions.check_address(None).is_err());
+
+    let not_before = DateTime::<Utc>::from_timestamp_millis(1_700_000_000_000).unwrap();
+    assert!(restrictions.check_time(not_before).is_ok());
+    let err = restrictions
+        .check_time(not_before - Duration::seconds(1))
+        .unwrap_err();
+    assert!(err.to_string().contains("not valid before 2023-11-14"));
+
+    // Unset restrictions allow everything
+    let unrestricted = KeyRestrictions::default();
+    assert!(unrestricted.check_service("other").is_ok());
+    assert!(unrestricted.check_address(None).is_ok());
+}
+
+#[test]
+fn test_restrictions_too_long() {
+    let mut metadata: MetadataV2 = create_metadata().into();
+    metadata.restrictions.models = Some(vec!["m".repeat(256)]);
+
+    assert!(MetadataRawV2::try_from(metadata).is_err());
+}
+
+#[test]
+fn test_v2_signed_tag() {
+    let sk = sign_v2(
+        &Keypair::new(),
+        MetadataV2 {
+            tag: Some("prod".to_string()),
+            ..metadata_v2()
+        },
+    );
+
+    // The tag round-trips and is covered by the signature
+    let sk_string = sk.clone().into_string("prod").unwrap();
+    let (scope, decoded) = SecretKeyV2::decode(&sk_string).unwrap();
+    assert_eq!(scope, "prod");
+    assert_eq!(decoded.metadata.tag.as_deref(), Some("prod"));
+    assert!(decoded.verify_signature().is_ok());
+
+    // It can't be encoded or relabelled under another tag
+    assert!(sk.clone().into_string("dev").is_err());
+    let relabelled = sk_string.replacen("aimo-sk-prod-", "aimo-sk-dev-", 1);
+    let err = SecretKeyV2::decode(&relabelled).unwrap_err();
+    assert!(err.to_string().contains("signed tag `prod`"));
+
+    // Empty tags are rejected
+    assert!(sk.into_string("").is_err());
+}
+
+fn create_sk_eth() -> SecretKeyV2 {
+    let secret_key = libsecp256k1::SecretKey::parse(&[7; 32]).unwrap();
+    let public_key = libsecp256k1::PublicKey::from_secret_key(&secret_key);
+    let metadata: MetadataV2 = create_metadata().into();
+    let signature = ethereum::sign_message(&secret_key, &metadata.signing_bytes(2).unwrap());
+
+    SecretKeyV2 {
+        version: 2,
+        wallet: Wallet::Ethereum,
+        signer: Wallet::Ethereum.encode_signer(&ethereum::address_of(&public_key)),
+        signature: Wallet::Ethereum.encode_signature(&signature),
+        metadata,
+    }
+}
+
+#[test]
+fn test_ethereum_key() {
+    let sk = create_sk_eth();
+    assert!(sk.signer.starts_with("0x") && sk.signer.len() == 42);
+    assert!(sk.verify_signature().is_ok());
+
+    let sk_string = sk.clone().into_string("test").unwrap();
+    let (_, decoded) = SecretKeyV2::decode(&sk_string).unwrap();
+    assert_eq!(decoded.wallet, Wallet::Ethereum);
+    assert_eq!(decoded.signer, sk.signer);
+    assert_eq!(decoded.signature, sk.signature);
+    assert!(decoded.verify_signature().is_ok());
+
+    // Addresses of any case verify, but signatures of other signers don't
+    let lowercase = SecretKeyV2 {
+        signer: sk.signer.to_lowercase(),
+        ..sk.clone()
+    };
+    assert!(lowercase.verify_signature().is_ok());
+    let other = SecretKeyV2 {
+        signer: format!("0x{}", "11".repeat(20)),
+        ..sk.clone()
+    };
+    let err = other.verify_signature().unwrap_err();
+    assert_eq!(err.to_string(), "Wrong signature");
+
+    // Re-encoding the signature with another `v` or a high `s` doesn't make another key
+    let signature: [u8; ethereum::SIGNATURE_LENGTH] = Wallet::Ethereum
+        .decode_signature(&sk.signature)
+        .unwrap()
+        .try_into()
+        .unwrap();
+    for malleated in super::ethereum_test::malleate(&signature) {
+        let malleated = SecretKeyV2 {
+            signature: Wallet::Ethereum.encode_signature(&malleated),
+            ..sk.clone()
+        };
+        assert!(malleated.verify_signature().is_err());
+        let encoded = malleated.into_string("test").unwrap();
+        assert!(SecretKeyV2::decode(&encoded).is_err());
+    }
+
+    // V1 keys only hold Solana signers
+    let v1 = SecretKeyV2 { version: 1, ..sk };
+    assert!(v1.into_string("test").is_err());
+}
+
+fn child_metadata(parent: &SecretKeyV2) -> MetadataV2 {
+    MetadataV2 {
+        valid_for: parent.metadata.valid_for - 1000,
+        usage_limit: 100,
+        parent: Some(Box::new(parent.clone())),
+        ..parent.metadata.clone()
+    }
+}
+
+#[test]
+fn test_delegated_key() {
+    let keypair = Keypair::new();
+    let delegate = Keypair::new();
+    let parent = sign_v2(
+        &keypair,
+        MetadataV2 {
+            delegate: Some(delegate.pubkey().to_string().parse().unwrap()),
+            ..create_metadata().into()
+        },
+    );
+
+    // Signed by the parent's wallet, or its delegate
+    for signer in [&keypair, &delegate] {
+        let child = sign_v2(signer, child_metadata(&parent));
+        let sk_string = child.clone().into_string("test").unwrap();
+        let (_, decoded) = SecretKeyV2::decode(&sk_string).unwrap();
+
+        assert_eq!(decoded, child);
+        assert_eq!(decoded.chain().count(), 2);
+        assert!(decoded.verify_signature().is_ok());
+    }
+
+    let err = sign_v2(&Keypair::new(), child_metadata(&parent))
+        .verify_signature()
+        .unwrap_err();
+    assert!(err.to_string().contains("parent's wallet or delegate"));
+
+    // Grandchildren are checked against the whole chain
+    let child = sign_v2(&delegate, child_metadata(&parent));
+    let grandchild = sign_v2(&delegate, child_metadata(&child));
+    assert_eq!(grandchild.chain().count(), 3);
+    assert!(grandchild.verify_signature().is_ok());
+
+    // A tampered parent fails the chain
+    let mut tampered = child_metadata(&parent);
+    tampered.parent.as_mut().unwrap().metadata.usage_limit = 0;
+    let err = sign_v2(&keypair, tampered).verify_signature().unwrap_err();
+    assert!(err.to_string().contains("Invalid parent key"));
+
+    // Delegates only sign for parents with their signature
+    let mut unsigned = child_metadata(&parent);
+    unsigned.parent.as_mut().unwrap().signature = String::new();
+    let err = sign_v2(&delegate, unsigned).verify_signature().unwrap_err();
+    assert!(err.to_string().contains("Invalid parent key"));
+}
+
+#[test]
+fn test_parent_not_recoverable() {
+    let keypair = Keypair::new();
+    let parent = sign_v2(&keypair, create_metadata().into());
+    let sk_string = sign_v2(&keypair, child_metadata(&parent))
+        .into_string("test")
+        .unwrap();
+    let (_, child) = SecretKeyV2::decode(&sk_string).unwrap();
+    assert!(child.verify_signature().is_ok());
+
+    // The parent is embedded without its signature, so it can't be used as a key
+    let embedded = child.metadata.parent.as_deref().unwrap();
+    assert!(embedded.signature.is_empty());
+    assert!(embedded.clone().into_string("test").is_err());
+
+    let (_, encoded) = sk_string.rsplit_once('-').unwrap();
+    let bytes = bs58::decode(encoded).into_vec().unwrap();
+    let signature = bs58::decode(&parent.signature).into_vec().unwrap();
+    assert!(
+        !bytes
+            .windows(signature.len())
+            .any(|window| window == signature)
+    );
+
+    // It still hashes the same, so revoking the parent revokes the child
+    assert_eq!(
+        embedded.clone().into_hash().unwrap(),
+        parent.into_hash().unwrap()
+    );
+}
+
+type Narrowing = fn(&mut MetadataV2);
+
+#[test]
+fn test_delegated_key_narrows() {
+    let keypair = Keypair::new();
+    let parent = sign_v2(
+        &keypair,
+        MetadataV2 {
+            restrictions: KeyRestrictions {
+                models: Some(vec!["gpt-4o".to_string()]),
+                max_streams: Some(2),
+                ..Default::default()
+            },
+            ..create_metadata().into()
+        },
+    );
+    let delegated = |f: Narrowing| {
+        let mut metadata = child_metadata(&parent);
+        f(&mut metadata);
+        sign_v2(&keypair, metadata).verify_signature()
+    };
+
+    assert!(delegated(|_| {}).is_ok());
+    assert!(
+        delegated(|m| m.restrictions.max_streams = Some(1)).is_ok(),
+        "Stricter restrictions are allowed"
+    );
+
+    let cases: [(Narrowing, &str); 6] = [
+        (|m| m.scopes.push(Scope::Admin), "`admin` scope"),
+        (|m| m.valid_for += 2000, "outlive"),
+        (|m| m.usage_limit = 0, "usage limit"),
+        (|m| m.usage_limit = 5000, "usage limit"),
+        (|m| m.restrictions.models = None, "`models` restriction"),
+        (
+            |m| m.restrictions.max_streams = Some(3),
+            "`max_streams` restriction",
+        ),
+    ];
+    for (f, message) in cases {
+        let err = delegated(f).unwrap_err();
+        assert!(err.to_string().contains(message), "{err}");
+    }
+}
+
+#[test]
+fn test_delegation_chain_length() {
+    let keypair = Keypair::new();
+    let mut key = sign_v2(&keypair, create_metadata().into());
+    for _ in 1..fields::MAX_CHAIN_LENGTH {
+        key = sign_v2(&keypair, child_metadata(&key));
+    }
+    assert!(key.clone().into_string("test").is_ok());
+
+    let err = child_metadata(&key).signing_bytes(2).unwrap_err();
+    assert!(err.to_string().contains("chain too long"));
+}
+
+#[test]
+fn test_nested_parents_bounded() {
+    // Keys nested deeper than any chain, each the parent of the next
+    let base = MetadataRawV1::try_from(create_metadata()).unwrap();
+    let mut fields = vec![];
+    for _ in 0..100 {
+        let parent = [
+            vec![SecretKeyRawV2::VERSION, wallets::SOLANA],
+            vec![0; 32],
+            MetadataRawV2 { base, fields }.into_bytes(),
+        ]
+        .concat();
+        fields = vec![ExtensionField {
+            kind: fields::PARENT,
+            value: parent,
+        }];
+    }
+    let bytes = SecretKeyRawV2 {
+        wallet: wallets::SOLANA,
+        signer: vec![0; 32],
+        signature: vec![0; 64],
+        metadata: MetadataRawV2 { base, fields },
+    }
+    .into_bytes();
+
+    let err = SecretKeyV2::from_bytes(&bytes).unwrap_err();
+    assert!(err.to_string().contains("chain too long"));
+}
@@ -1,7 +1,19 @@
+pub mod completion;
+pub mod ethereum;
+pub mod health;
 pub mod keys;
+pub mod revocation;
 pub mod router;
 pub mod state;
 pub mod transport;
 
+#[cfg(test)]
+mod completion_test;
+#[cfg(test)]
+mod ethereum_test;
+#[cfg(test)]
+mod health_test;
 #[cfg(test)]
 mod keys_test;
+#[cfg(test)]
+mod revocation_test;
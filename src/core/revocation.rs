@@ -0,0 +1,110 @@
+//! Signed key revocation requests
+//!
+//! A revocation is signed by the wallet of the keys it revokes, over a text message with a
+//! domain tag, the network it's meant for, a timestamp and a nonce. Nodes only accept
+//! fresh messages for their own network, and each nonce once.
+//!
+//! A revocation targets either one secret key, or every key the signing wallet issued
+//! before a time, e.g. when the wallet is compromised.
+
+use anyhow::bail;
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::core::keys::Wallet;
+
+/// First line of revocation messages, so their signatures can't pass for anything else
+pub const REVOCATION_DOMAIN: &str = "aimo-revoke-v1";
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RevocationMessage {
+    /// Network id of the nodes the revocation is meant for
+    pub network: String,
+    /// Unix timestamp in milliseconds the revocation was signed at
+    pub timestamp: i64,
+    /// Unique per revocation, so it can't be replayed
+    pub nonce: String,
+    #[serde(flatten)]
+    pub target: RevocationTarget,
+}
+
+/// What a revocation revokes
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum RevocationTarget {
+    /// One secret key
+    Key { secret_key: String },
+    /// Every key of the signer created before a unix timestamp in milliseconds
+    Signer { issued_before: i64 },
+}
+
+impl RevocationMessage {
+    /// A message revoking `target` on `network`, timestamped now with a random nonce
+    pub fn new(network: &str, target: RevocationTarget) -> Self {
+        Self {
+            network: network.to_string(),
+            timestamp: Utc::now().timestamp_millis(),
+            nonce: Uuid::new_v4().simple().to_string(),
+            target,
+        }
+    }
+
+    /// The text the wallet signs, one field per line
+    pub fn signing_bytes(&self) -> anyhow::Result<Vec<u8>> {
+        let (target, value) = match &self.target {
+            RevocationTarget::Key { secret_key } => ("key", secret_key.clone()),
+            RevocationTarget::Signer { issued_before } => {
+                ("issued-before", issued_before.to_string())
+            }
+        };
+        let fields = [&self.network, &self.nonce, &value];
+        if fields
+            .iter()
+            .any(|field| field.is_empty() || field.contains('\n'))
+        {
+            bail!("Revocation fields must be non-empty single lines");
+        }
+
+        Ok(format!(
+            "{REVOCATION_DOMAIN}\nnetwork: {}\ntimestamp: {}\nnonce: {}\n{target}: {value}",
+            self.network, self.timestamp, self.nonce
+        )
+        .into_bytes())
+    }
+
+    /// Check the message is meant for `network` and was signed within `window_ms` of `now`
+    pub fn check(&self, network: &str, now: i64, window_ms: i64) -> anyhow::Result<()> {
+        if self.network != network {
+            bail!(
+                "Revocation is for network `{}`, not `{network}`",
+                self.network
+            );
+        }
+
+        if now.abs_diff(self.timestamp) > window_ms.max(0) as u64 {
+            bail!("Revocation timestamp is outside the accepted window");
+        }
+
+        Ok(())
+    }
+}
+
+/// A revocation message with its signature
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SignedRevocation {
+    pub signer: String,
+    pub signature: String,
+    #[serde(flatten)]
+    pub message: RevocationMessage,
+}
+
+impl SignedRevocation {
+    /// Whether `signature` is valid for `signer` on a `wallet`
+    ///
+    /// Errors if the message, signer or signature is malformed.
+    pub fn verify(&self, wallet: Wallet) -> anyhow::Result<bool> {
+        let bytes = self.message.signing_bytes()?;
+        wallet.verify(&self.signer, &self.signature, &bytes)
+    }
+}
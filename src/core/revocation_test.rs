@@ -0,0 +1,152 @@
+use solana_sdk::{signature::Keypair, signer::Signer};
+
+use super::revocation::*;
+use crate::test_utils::{metadata_v2, sign_v2};
+
+fn key_target(secret_key: &str) -> RevocationTarget {
+    RevocationTarget::Key {
+        secret_key: secret_key.to_string(),
+    }
+}
+
+fn sign(keypair: &Keypair, message: RevocationMessage) -> SignedRevocation {
+    let signature = keypair.sign_message(&message.signing_bytes().unwrap());
+
+    SignedRevocation {
+        signer: keypair.pubkey().to_string(),
+        signature: signature.to_string(),
+        message,
+    }
+}
+
+#[test]
+fn test_signing_bytes() {
+    let message = RevocationMessage {
+        network: "aimo".to_string(),
+        timestamp: 1700000000000,
+        nonce: "abc".to_string(),
+        target: RevocationTarget::Key {
+            secret_key: "aimo-sk-dev-xyz".to_string(),
+        },
+    };
+
+    assert_eq!(
+        String::from_utf8(message.signing_bytes().unwrap()).unwrap(),
+        "aimo-revoke-v1\nnetwork: aimo\ntimestamp: 1700000000000\nnonce: abc\nkey: aimo-sk-dev-xyz"
+    );
+
+    // Fields can't inject lines
+    let injected = RevocationMessage {
+        nonce: "abc\nkey: other".to_string(),
+        ..message.clone()
+    };
+    assert!(injected.signing_bytes().is_err());
+
+    let signer = RevocationMessage {
+        target: RevocationTarget::Signer {
+            issued_before: 1690000000000,
+        },
+        ..message
+    };
+    assert_eq!(
+        String::from_utf8(signer.signing_bytes().unwrap()).unwrap(),
+        "aimo-revoke-v1\nnetwork: aimo\ntimestamp: 1700000000000\nnonce: abc\nissued-before: 1690000000000"
+    );
+}
+
+#[test]
+fn test_target_json() {
+    let key: RevocationMessage = serde_json::from_str(
+        r#"{"network":"aimo","timestamp":1,"nonce":"abc","secret_key":"aimo-sk-dev-xyz"}"#,
+    )
+    .unwrap();
+    assert_eq!(
+        key.target,
+        RevocationTarget::Key {
+            secret_key: "aimo-sk-dev-xyz".to_string()
+        }
+    );
+
+    let signer: RevocationMessage = serde_json::from_str(
+        r#"{"network":"aimo","timestamp":1,"nonce":"abc","issued_before":1000}"#,
+    )
+    .unwrap();
+    assert_eq!(
+        signer.target,
+        RevocationTarget::Signer {
+            issued_before: 1000
+        }
+    );
+}
+
+#[test]
+fn test_check() {
+    let message = RevocationMessage::new("aimo", key_target("aimo-sk-dev-xyz"));
+    let now = message.timestamp;
+
+    assert!(message.check("aimo", now + 1000, 5000).is_ok());
+    assert!(message.check("aimo", now - 1000, 5000).is_ok());
+
+    let err = message.check("testnet", now, 5000).unwrap_err();
+    assert!(err.to_string().contains("not `testnet`"));
+    let err = message.check("aimo", now + 6000, 5000).unwrap_err();
+    assert!(err.to_string().contains("window"));
+
+    // Timestamps far from `now` are rejected rather than overflowing
+    for timestamp in [i64::MIN, i64::MAX] {
+        let message = RevocationMessage {
+            timestamp,
+            ..message.clone()
+        };
+        let err = message.check("aimo", now, 5000).unwrap_err();
+        assert!(err.to_string().contains("window"));
+    }
+
+    // Every message gets its own nonce
+    assert_ne!(
+        message.nonce,
+        RevocationMessage::new("aimo", key_target("aimo-sk-dev-xyz")).nonce
+    );
+}
+
+#[test]
+fn test_verify() {
+    let keypair = Keypair::new();
+    let key = sign_v2(&keypair, metadata_v2());
+    let message =
+        RevocationMessage::new("aimo", key_target(&key.clone().into_string("dev").unwrap()));
+    let revocation = sign(&keypair, message.clone());
+
+    assert!(revocation.verify(key.wallet).unwrap());
+
+    // The signature covers every field
+    for message in [
+        RevocationMessage {
+            network: "testnet".to_string(),
+            ..message.clone()
+        },
+        RevocationMessage {
+            timestamp: message.timestamp + 1,
+            ..message.clone()
+        },
+        RevocationMessage {
+            nonce: "replayed".to_string(),
+            ..message.clone()
+        },
+    ] {
+        let tampered = SignedRevocation {
+            message,
+            ..revocation.clone()
+        };
+        assert!(!tampered.verify(key.wallet).unwrap());
+    }
+
+    // Signatures over the bare key, as earlier clients sent, are rejected
+    let bare = SignedRevocation {
+        signature: keypair
+            .sign_message(key.clone().into_string("dev").unwrap().as_bytes())
+            .to_string(),
+        ..revocation
+    };
+    assert!(!bare.verify(key.wallet).unwrap());
+}
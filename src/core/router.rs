@@ -66,4 +66,10 @@ pub trait Router {
     async fn register_service(&self, service_id: String) -> anyhow::Result<ResponseHandler>;
 
     async fn drop_service(&self, service_id: String) -> anyhow::Result<()>;
+
+    /// Number of service providers currently connected
+    async fn service_count(&self) -> usize;
+
+    /// Number of messages waiting to be dispatched
+    fn queue_depth(&self) -> usize;
 }
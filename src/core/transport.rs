@@ -21,6 +21,10 @@ pub struct Request {
     pub headers: HashMap<String, String>, // Only essential headers
     pub payload_encrypted: bool,
     pub signature: Option<String>,
+
+    /// W3C trace context (`traceparent`, `tracestate`) of the request's span
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub trace_context: HashMap<String, String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -34,17 +38,14 @@ pub struct Response {
     pub stream_done: bool,
 }
 
-/// Headers that should be included in requests to reduce message size
-pub const ESSENTIAL_REQUEST_HEADERS: &[&str] = &[
-    "content-type",
-    "content-length",
-    "authorization",
-    "accept",
-    "accept-encoding",
-    "user-agent",
-    "x-forwarded-for",
-    "x-real-ip",
-];
+/// Notices sent to providers over the subscription socket, alongside requests
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ProviderNotice {
+    /// The node stopped accepting requests. The socket is closed once in-flight requests
+    /// are finished, or after `grace_period` seconds (0 for no limit).
+    Draining { grace_period: u64 },
+}
 
 /// Headers that should be included in responses to reduce message size
 pub const ESSENTIAL_RESPONSE_HEADERS: &[&str] = &[
@@ -1,19 +1,65 @@
-use anyhow::Ok;
+use anyhow::{Ok, bail};
 use chrono::Utc;
+use serde::Serialize;
 
-use crate::core::{keys::SecretKeyV1, state::events};
+use crate::core::{keys::SecretKeyV2, revocation::RevocationMessage, state::events};
+
+pub const NONCE_TREE_NAME: &str = "revocation_nonces";
+
+/// A revoked key, stored by its hash
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RevocationRecord {
+    pub revoked_at: i64, // 8 bytes
+    /// When the key expires, after which it doesn't need to be revoked anymore. `None` for
+    /// records written before expiries were stored, which are kept.
+    pub expires_at: Option<i64>, // 8 bytes
+}
+
+impl RevocationRecord {
+    pub fn into_bytes(self) -> Vec<u8> {
+        match self.expires_at {
+            Some(expires_at) => [self.revoked_at.to_be_bytes(), expires_at.to_be_bytes()].concat(),
+            None => self.revoked_at.to_be_bytes().to_vec(),
+        }
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> anyhow::Result<Self> {
+        match bytes.len() {
+            8 => Ok(Self {
+                revoked_at: i64::from_be_bytes(bytes.try_into()?),
+                expires_at: None,
+            }),
+            16 => Ok(Self {
+                revoked_at: i64::from_be_bytes(bytes[0..8].try_into()?),
+                expires_at: Some(i64::from_be_bytes(bytes[8..16].try_into()?)),
+            }),
+            len => bail!("Invalid revocation record length {len}"),
+        }
+    }
+}
+
+/// Counts of the revocation records
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub struct RevocationStats {
+    pub revoked_keys: usize,
+    /// Records without an expiry, which are never pruned
+    pub without_expiry: usize,
+}
 
 pub struct RevocationDb(pub sled::Db);
 
 impl RevocationDb {
     pub fn revoke_key(&self, event: events::KeyRevocation) -> anyhow::Result<()> {
         let events::KeyRevocation { key } = event;
-        let (_, secret_key) = SecretKeyV1::decode(&key)?;
+        let (_, secret_key) = SecretKeyV2::decode(&key)?;
+        let record = RevocationRecord {
+            revoked_at: Utc::now().timestamp_millis(),
+            expires_at: Some(secret_key.expires_at()),
+        };
         let hash = secret_key.into_hash()?;
-        let now = Utc::now().timestamp_millis();
 
         if self.0.get(hash)?.is_none() {
-            self.0.insert(hash, &now.to_be_bytes())?;
+            self.0.insert(hash, record.into_bytes())?;
         }
 
         tracing::debug!("Revoked key {key}");
@@ -21,7 +67,82 @@ impl RevocationDb {
         Ok(())
     }
 
-    pub fn is_key_revoked(&self, key: &SecretKeyV1) -> anyhow::Result<bool> {
-        Ok(self.0.get(key.clone().into_hash()?)?.is_some())
+    /// Record the nonce of a signed revocation, returning `false` if `signer` already used
+    /// it. Nonces older than `window_ms` are forgotten, as their messages are rejected as
+    /// stale anyway.
+    pub fn use_nonce(
+        &self,
+        signer: &str,
+        message: &RevocationMessage,
+        window_ms: i64,
+    ) -> anyhow::Result<bool> {
+        let tree = self.0.open_tree(NONCE_TREE_NAME)?;
+
+        // Keys start with the timestamp, so stale nonces are a prefix range
+        let cutoff = Utc::now().timestamp_millis() - window_ms;
+        for entry in tree.range(..cutoff.max(0).to_be_bytes()) {
+            tree.remove(entry?.0)?;
+        }
+
+        let key = [
+            message.timestamp.to_be_bytes().as_slice(),
+            signer.as_bytes(),
+            b"\n",
+            message.nonce.as_bytes(),
+        ]
+        .concat();
+        let used = tree
+            .compare_and_swap(key, None::<&[u8]>, Some(&[]))?
+            .is_err();
+
+        Ok(!used)
+    }
+
+    /// Whether the key, or any key it's delegated from, is revoked
+    pub fn is_key_revoked(&self, key: &SecretKeyV2) -> anyhow::Result<bool> {
+        for key in key.chain() {
+            if self.0.get(key.clone().into_hash()?)?.is_some() {
+                return Ok(true);
+            }
+        }
+
+        Ok(false)
+    }
+
+    /// Remove the records of keys which expired more than `retention_ms` before `now`, as
+    /// expired keys are rejected anyway. Returns the number of records removed.
+    pub fn prune_expired(&self, now: i64, retention_ms: i64) -> anyhow::Result<usize> {
+        let mut pruned = 0;
+        for entry in self.0.iter() {
+            let (hash, bytes) = entry?;
+            let record = RevocationRecord::from_bytes(&bytes)?;
+            if let Some(expires_at) = record.expires_at
+                && expires_at.saturating_add(retention_ms) < now
+            {
+                // Skip records rewritten meanwhile
+                if self
+                    .0
+                    .compare_and_swap(&hash, Some(bytes), None::<&[u8]>)?
+                    .is_ok()
+                {
+                    pruned += 1;
+                }
+            }
+        }
+
+        Ok(pruned)
+    }
+
+    pub fn stats(&self) -> anyhow::Result<RevocationStats> {
+        let mut stats = RevocationStats::default();
+        for entry in self.0.iter() {
+            let record = RevocationRecord::from_bytes(&entry?.1)?;
+            stats.revoked_keys += 1;
+            if record.expires_at.is_none() {
+                stats.without_expiry += 1;
+            }
+        }
+
+        Ok(stats)
     }
 }
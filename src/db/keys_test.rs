@@ -0,0 +1,88 @@
+use chrono::Utc;
+
+use super::keys::{RevocationDb, RevocationRecord, RevocationStats};
+use crate::core::revocation::{RevocationMessage, RevocationTarget};
+
+fn target(secret_key: &str) -> RevocationTarget {
+    RevocationTarget::Key {
+        secret_key: secret_key.to_string(),
+    }
+}
+
+#[test]
+fn test_use_nonce() {
+    let db = RevocationDb(sled::Config::new().temporary(true).open().unwrap());
+    let message = RevocationMessage::new("aimo", target("aimo-sk-dev-xyz"));
+
+    assert!(db.use_nonce("signer", &message, 60_000).unwrap());
+    assert!(!db.use_nonce("signer", &message, 60_000).unwrap());
+
+    // Nonces are per signer and message
+    assert!(db.use_nonce("other", &message, 60_000).unwrap());
+    let next = RevocationMessage::new("aimo", target("aimo-sk-dev-xyz"));
+    assert!(db.use_nonce("signer", &next, 60_000).unwrap());
+
+    // Stale nonces are forgotten
+    let stale = RevocationMessage {
+        timestamp: Utc::now().timestamp_millis() - 120_000,
+        ..message
+    };
+    assert!(db.use_nonce("signer", &stale, 60_000).unwrap());
+    let tree = db.0.open_tree(super::keys::NONCE_TREE_NAME).unwrap();
+    assert_eq!(tree.len(), 4);
+    db.use_nonce(
+        "signer",
+        &RevocationMessage::new("aimo", target("k")),
+        60_000,
+    )
+    .unwrap();
+    assert_eq!(tree.len(), 4);
+}
+
+#[test]
+fn test_record_bytes() {
+    let record = RevocationRecord {
+        revoked_at: 1000,
+        expires_at: Some(2000),
+    };
+    assert_eq!(
+        RevocationRecord::from_bytes(&record.into_bytes()).unwrap(),
+        record
+    );
+
+    // Records of earlier nodes only hold the revocation time
+    let legacy = RevocationRecord::from_bytes(&1000i64.to_be_bytes()).unwrap();
+    assert_eq!(legacy.expires_at, None);
+    assert!(RevocationRecord::from_bytes(&[0; 4]).is_err());
+}
+
+#[test]
+fn test_prune_expired() {
+    let db = RevocationDb(sled::Config::new().temporary(true).open().unwrap());
+    let record = |expires_at| RevocationRecord {
+        revoked_at: 0,
+        expires_at,
+    };
+    db.0.insert("expired", record(Some(1000)).into_bytes())
+        .unwrap();
+    db.0.insert("skewed", record(Some(8000)).into_bytes())
+        .unwrap();
+    db.0.insert("valid", record(Some(20_000)).into_bytes())
+        .unwrap();
+    db.0.insert("legacy", record(None).into_bytes()).unwrap();
+
+    // Keys expired within the retention margin are kept
+    assert_eq!(db.prune_expired(10_000, 3000).unwrap(), 1);
+    assert!(db.0.get("expired").unwrap().is_none());
+    assert_eq!(
+        db.stats().unwrap(),
+        RevocationStats {
+            revoked_keys: 3,
+            without_expiry: 1,
+        }
+    );
+
+    assert_eq!(db.prune_expired(30_000, 3000).unwrap(), 2);
+    assert_eq!(db.0.len(), 1);
+    assert!(db.0.get("legacy").unwrap().is_some());
+}
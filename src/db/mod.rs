@@ -1,4 +1,17 @@
 mod keys;
 mod state;
+mod usage;
+mod watermarks;
 
 pub use state::*;
+pub use usage::KeyUsage;
+pub use watermarks::{WatermarkDb, Watermarks};
+
+#[cfg(test)]
+mod keys_test;
+#[cfg(test)]
+mod state_test;
+#[cfg(test)]
+mod usage_test;
+#[cfg(test)]
+mod watermarks_test;
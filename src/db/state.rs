@@ -1,21 +1,96 @@
-use std::path::{Path, PathBuf};
+use std::{
+    path::{Path, PathBuf},
+    sync::Mutex,
+    time::{Duration, Instant},
+};
 
-use anyhow::Ok;
+use anyhow::{Ok, anyhow};
+use serde::Serialize;
 
-use crate::db::keys::RevocationDb;
+use crate::db::{
+    keys::{RevocationDb, RevocationStats},
+    usage::UsageDb,
+    watermarks::{WATERMARK_TREE_NAME, WatermarkDb},
+};
 
 pub struct StateDb {
     pub revocation: RevocationDb,
+    pub usage: UsageDb,
+    pub watermarks: WatermarkDb,
+    /// Last result of `check_writable`, with when it was checked
+    writable: Mutex<Option<(Instant, Result<(), String>)>>,
 }
 
-pub const KEYS_DB_NAME: &'static str = "keys.db";
+/// Size of the state db, reported to admins
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct StateStats {
+    /// Bytes used by the db files
+    pub size_on_disk: u64,
+    pub revocations: RevocationStats,
+    /// Signers with a revocation watermark, and the global watermark if set
+    pub watermarks: usize,
+    /// Keys with usage counters
+    pub usage_keys: usize,
+}
+
+pub const KEYS_DB_NAME: &str = "keys.db";
+pub const USAGE_TREE_NAME: &str = "usage";
+pub const HEALTH_TREE_NAME: &str = "health";
 
 impl StateDb {
     pub fn load_or_create(directory: &Path) -> anyhow::Result<Self> {
+        let keys_db = sled::open(directory.join(KEYS_DB_NAME))?;
+
         Ok(Self {
-            revocation: RevocationDb(sled::open(directory.join(KEYS_DB_NAME))?),
+            usage: UsageDb(keys_db.open_tree(USAGE_TREE_NAME)?),
+            watermarks: WatermarkDb(keys_db.open_tree(WATERMARK_TREE_NAME)?),
+            revocation: RevocationDb(keys_db),
+            writable: Mutex::new(None),
         })
     }
+
+    /// Check the db still accepts writes, e.g. that the disk isn't full
+    pub fn check_writable(&self) -> anyhow::Result<()> {
+        let tree = self.revocation.0.open_tree(HEALTH_TREE_NAME)?;
+        tree.insert(
+            "probe",
+            &chrono::Utc::now().timestamp_millis().to_be_bytes(),
+        )?;
+        tree.flush()?;
+        Ok(())
+    }
+
+    /// `check_writable`, reusing its last result if checked within `max_age`, so frequent
+    /// probes don't write to the disk each time
+    pub fn check_writable_within(&self, max_age: Duration) -> anyhow::Result<()> {
+        let mut writable = self.writable.lock().unwrap();
+        let result = match &*writable {
+            Some((checked_at, result)) if checked_at.elapsed() < max_age => result.clone(),
+            _ => {
+                let result = self.check_writable().map_err(|err| err.to_string());
+                *writable = Some((Instant::now(), result.clone()));
+                result
+            }
+        };
+
+        result.map_err(|err| anyhow!(err))
+    }
+
+    pub fn stats(&self) -> anyhow::Result<StateStats> {
+        Ok(StateStats {
+            size_on_disk: self.revocation.0.size_on_disk()?,
+            revocations: self.revocation.stats()?,
+            watermarks: self.watermarks.0.len(),
+            usage_keys: self.usage.0.len(),
+        })
+    }
+
+    /// Write all pending changes to disk
+    pub fn flush(&self) -> anyhow::Result<()> {
+        // Flushing the db covers all of its trees
+        self.revocation.0.flush()?;
+        Ok(())
+    }
 }
 
 pub fn default_directory() -> PathBuf {
@@ -0,0 +1,34 @@
+use std::{env, time::Duration};
+
+use crate::db::{HEALTH_TREE_NAME, StateDb};
+
+fn probe(state_db: &StateDb) -> Option<sled::IVec> {
+    state_db
+        .revocation
+        .0
+        .open_tree(HEALTH_TREE_NAME)
+        .unwrap()
+        .get("probe")
+        .unwrap()
+}
+
+#[test]
+fn test_check_writable_within() {
+    let directory = env::temp_dir().join(format!("aimo-{}", uuid::Uuid::new_v4()));
+    let state_db = StateDb::load_or_create(&directory).unwrap();
+
+    state_db
+        .check_writable_within(Duration::from_secs(60))
+        .unwrap();
+    let written = probe(&state_db).unwrap();
+
+    // Checks within the max age reuse the last result without writing
+    std::thread::sleep(Duration::from_millis(5));
+    state_db
+        .check_writable_within(Duration::from_secs(60))
+        .unwrap();
+    assert_eq!(probe(&state_db), Some(written.clone()));
+
+    state_db.check_writable_within(Duration::ZERO).unwrap();
+    assert_ne!(probe(&state_db), Some(written));
+}
@@ -0,0 +1,98 @@
+use anyhow::{Ok, bail};
+use serde::Serialize;
+
+use crate::core::{completion::TokenUsage, keys::SecretKeyV2};
+
+/// Usage counters of a secret key
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub struct KeyUsage {
+    pub requests: u64,          // 8 bytes
+    pub prompt_tokens: u64,     // 8 bytes
+    pub completion_tokens: u64, // 8 bytes
+    pub total_tokens: u64,      // 8 bytes
+}
+
+impl KeyUsage {
+    pub const BYTES: usize = 32; // 8 + 8 + 8 + 8
+
+    pub fn into_bytes(self) -> Vec<u8> {
+        [
+            self.requests.to_be_bytes(),
+            self.prompt_tokens.to_be_bytes(),
+            self.completion_tokens.to_be_bytes(),
+            self.total_tokens.to_be_bytes(),
+        ]
+        .concat()
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> anyhow::Result<Self> {
+        if bytes.len() != Self::BYTES {
+            bail!(
+                "Bytes length doesn't match: expect {}, got {}",
+                Self::BYTES,
+                bytes.len()
+            );
+        }
+
+        Ok(Self {
+            requests: u64::from_be_bytes(bytes[0..8].try_into()?),
+            prompt_tokens: u64::from_be_bytes(bytes[8..16].try_into()?),
+            completion_tokens: u64::from_be_bytes(bytes[16..24].try_into()?),
+            total_tokens: u64::from_be_bytes(bytes[24..32].try_into()?),
+        })
+    }
+}
+
+/// Per-key usage counters, indexed by the key hash
+///
+/// Usage of a delegated key is also counted against every key it's delegated from.
+pub struct UsageDb(pub sled::Tree);
+
+impl UsageDb {
+    pub fn get(&self, key: &SecretKeyV2) -> anyhow::Result<KeyUsage> {
+        match self.0.get(key.clone().into_hash()?)? {
+            Some(bytes) => KeyUsage::from_bytes(&bytes),
+            None => Ok(KeyUsage::default()),
+        }
+    }
+
+    pub fn record_request(&self, key: &SecretKeyV2) -> anyhow::Result<KeyUsage> {
+        self.update(key, |usage| usage.requests += 1)
+    }
+
+    pub fn record_tokens(&self, key: &SecretKeyV2, tokens: TokenUsage) -> anyhow::Result<KeyUsage> {
+        self.update(key, |usage| {
+            usage.prompt_tokens += tokens.prompt_tokens;
+            usage.completion_tokens += tokens.completion_tokens;
+            usage.total_tokens += tokens.total_tokens;
+        })
+    }
+
+    /// Atomically apply `f` to the counters of a key and its ancestors, returning the
+    /// key's own counters
+    fn update(&self, key: &SecretKeyV2, f: impl Fn(&mut KeyUsage)) -> anyhow::Result<KeyUsage> {
+        for ancestor in key.chain().skip(1) {
+            self.update_one(ancestor, &f)?;
+        }
+
+        self.update_one(key, &f)
+    }
+
+    fn update_one(
+        &self,
+        key: &SecretKeyV2,
+        f: &impl Fn(&mut KeyUsage),
+    ) -> anyhow::Result<KeyUsage> {
+        let updated = self.0.update_and_fetch(key.clone().into_hash()?, |bytes| {
+            let mut usage = bytes
+                .and_then(|bytes| KeyUsage::from_bytes(bytes).ok())
+                .unwrap_or_default();
+            f(&mut usage);
+            Some(usage.into_bytes())
+        })?;
+
+        updated
+            .map(|bytes| KeyUsage::from_bytes(&bytes))
+            .unwrap_or(Ok(KeyUsage::default()))
+    }
+}
@@ -0,0 +1,70 @@
+use solana_sdk::signature::Keypair;
+
+use super::usage::*;
+use crate::{
+    core::{
+        completion::TokenUsage,
+        keys::{MetadataV2, SecretKeyV2},
+    },
+    test_utils::{metadata_v2, sign_v2},
+};
+
+fn create_sk() -> SecretKeyV2 {
+    sign_v2(
+        &Keypair::new(),
+        MetadataV2 {
+            usage_limit: 100,
+            ..metadata_v2()
+        },
+    )
+}
+
+#[test]
+fn test_usage_counters() {
+    let db = sled::Config::new().temporary(true).open().unwrap();
+    let usage_db = UsageDb(db.open_tree("usage").unwrap());
+    let sk = create_sk();
+
+    assert_eq!(usage_db.get(&sk).unwrap(), KeyUsage::default());
+
+    usage_db.record_request(&sk).unwrap();
+    usage_db.record_request(&sk).unwrap();
+    let usage = usage_db
+        .record_tokens(
+            &sk,
+            TokenUsage {
+                prompt_tokens: 10,
+                completion_tokens: 5,
+                total_tokens: 15,
+            },
+        )
+        .unwrap();
+
+    assert_eq!(usage.requests, 2);
+    assert_eq!(usage.total_tokens, 15);
+    assert_eq!(usage_db.get(&sk).unwrap(), usage);
+
+    // Counters are kept per key
+    assert_eq!(usage_db.get(&create_sk()).unwrap(), KeyUsage::default());
+}
+
+#[test]
+fn test_delegated_usage() {
+    let db = sled::Config::new().temporary(true).open().unwrap();
+    let usage_db = UsageDb(db.open_tree("usage").unwrap());
+    let parent = create_sk();
+    let child = SecretKeyV2 {
+        metadata: MetadataV2 {
+            parent: Some(Box::new(parent.clone())),
+            ..parent.metadata.clone()
+        },
+        ..parent.clone()
+    };
+
+    usage_db.record_request(&child).unwrap();
+    usage_db.record_request(&parent).unwrap();
+
+    // Usage of the child is also counted against its parent
+    assert_eq!(usage_db.get(&child).unwrap().requests, 1);
+    assert_eq!(usage_db.get(&parent).unwrap().requests, 2);
+}
@@ -0,0 +1,107 @@
+use std::collections::BTreeMap;
+
+use anyhow::Ok;
+use serde::Serialize;
+
+use crate::core::keys::{SecretKeyV2, Wallet};
+
+pub const WATERMARK_TREE_NAME: &str = "revocation_watermarks";
+
+/// Entry of the watermark applying to every signer. Signer entries start with their
+/// wallet byte, so they're never empty.
+const GLOBAL_KEY: &[u8] = b"";
+
+/// Revocation watermarks: keys created before the watermark of their signer, or before
+/// the global one, are revoked
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct Watermarks {
+    pub global: Option<i64>,
+    pub signers: BTreeMap<String, i64>,
+}
+
+/// Watermarks as unix timestamps in milliseconds, indexed by the wallet byte and the
+/// decoded signer, so differently encoded signers share their watermark
+pub struct WatermarkDb(pub sled::Tree);
+
+impl WatermarkDb {
+    fn signer_key(wallet: Wallet, signer: &str) -> anyhow::Result<Vec<u8>> {
+        Ok([vec![wallet.as_byte()], wallet.decode_signer(signer)?].concat())
+    }
+
+    fn read(bytes: &[u8]) -> anyhow::Result<i64> {
+        Ok(i64::from_be_bytes(bytes.try_into()?))
+    }
+
+    fn get(&self, key: &[u8]) -> anyhow::Result<Option<i64>> {
+        self.0.get(key)?.map(|bytes| Self::read(&bytes)).transpose()
+    }
+
+    fn put(&self, key: &[u8], issued_before: Option<i64>) -> anyhow::Result<()> {
+        match issued_before {
+            Some(issued_before) => self.0.insert(key, &issued_before.to_be_bytes())?,
+            None => self.0.remove(key)?,
+        };
+        Ok(())
+    }
+
+    /// Revoke the keys of `signer` created before `issued_before`, returning the new
+    /// watermark. Watermarks only move forward, so earlier revocations still hold.
+    pub fn raise(&self, wallet: Wallet, signer: &str, issued_before: i64) -> anyhow::Result<i64> {
+        let key = Self::signer_key(wallet, signer)?;
+        let mut watermark = issued_before;
+        self.0.fetch_and_update(&key, |current| {
+            let current = current.and_then(|bytes| Self::read(bytes).ok());
+            watermark = current.map_or(issued_before, |current| current.max(issued_before));
+            Some(watermark.to_be_bytes().to_vec())
+        })?;
+
+        Ok(watermark)
+    }
+
+    /// Set or clear the watermark of `signer`, which may lower it
+    pub fn set_signer(
+        &self,
+        wallet: Wallet,
+        signer: &str,
+        issued_before: Option<i64>,
+    ) -> anyhow::Result<()> {
+        self.put(&Self::signer_key(wallet, signer)?, issued_before)
+    }
+
+    /// Set or clear the watermark of every signer
+    pub fn set_global(&self, issued_before: Option<i64>) -> anyhow::Result<()> {
+        self.put(GLOBAL_KEY, issued_before)
+    }
+
+    pub fn list(&self) -> anyhow::Result<Watermarks> {
+        let mut watermarks = Watermarks::default();
+        for entry in self.0.iter() {
+            let (key, value) = entry?;
+            let issued_before = Self::read(&value)?;
+            match &key[..] {
+                [] => watermarks.global = Some(issued_before),
+                [wallet, signer @ ..] => {
+                    let signer = Wallet::from_byte(*wallet)?.encode_signer(signer);
+                    watermarks.signers.insert(signer, issued_before);
+                }
+            }
+        }
+
+        Ok(watermarks)
+    }
+
+    /// The watermark revoking the key, or any key it's delegated from, if any
+    pub fn revoked_before(&self, key: &SecretKeyV2) -> anyhow::Result<Option<i64>> {
+        let global = self.get(GLOBAL_KEY)?;
+        for key in key.chain() {
+            let signer = self.get(&Self::signer_key(key.wallet, &key.signer)?)?;
+            if let Some(watermark) = global.max(signer)
+                && key.metadata.created_at < watermark
+            {
+                return Ok(Some(watermark));
+            }
+        }
+
+        Ok(None)
+    }
+}
@@ -0,0 +1,102 @@
+use solana_sdk::{signature::Keypair, signer::Signer};
+
+use super::watermarks::*;
+use crate::{
+    core::keys::{MetadataV2, SecretKeyV2, Wallet},
+    test_utils::{metadata_v2, sign_v2},
+};
+
+const ADDRESS: &str = "0x5aAeb6053F3E94C9b9A09f33669435E7Ef1BeAed";
+
+fn create_sk(wallet: Wallet, signer: &str, created_at: i64) -> SecretKeyV2 {
+    SecretKeyV2 {
+        wallet,
+        signer: signer.to_string(),
+        ..sign_v2(
+            &Keypair::new(),
+            MetadataV2 {
+                created_at,
+                ..metadata_v2()
+            },
+        )
+    }
+}
+
+fn create_db() -> WatermarkDb {
+    let db = sled::Config::new().temporary(true).open().unwrap();
+    WatermarkDb(db.open_tree(WATERMARK_TREE_NAME).unwrap())
+}
+
+#[test]
+fn test_signer_watermark() {
+    let db = create_db();
+    let signer = Keypair::new().pubkey().to_string();
+    let old = create_sk(Wallet::Solana, &signer, 1000);
+    let new = create_sk(Wallet::Solana, &signer, 3000);
+
+    assert_eq!(db.revoked_before(&old).unwrap(), None);
+
+    assert_eq!(db.raise(Wallet::Solana, &signer, 2000).unwrap(), 2000);
+    assert_eq!(db.revoked_before(&old).unwrap(), Some(2000));
+    assert_eq!(db.revoked_before(&new).unwrap(), None);
+
+    // Other signers are unaffected
+    let other = create_sk(Wallet::Solana, &Keypair::new().pubkey().to_string(), 1000);
+    assert_eq!(db.revoked_before(&other).unwrap(), None);
+
+    // Signed revocations can't lower the watermark, admins can
+    assert_eq!(db.raise(Wallet::Solana, &signer, 1500).unwrap(), 2000);
+    db.set_signer(Wallet::Solana, &signer, Some(500)).unwrap();
+    assert_eq!(db.revoked_before(&old).unwrap(), None);
+    db.set_signer(Wallet::Solana, &signer, None).unwrap();
+    assert_eq!(db.list().unwrap(), Watermarks::default());
+}
+
+#[test]
+fn test_global_watermark() {
+    let db = create_db();
+    let signer = Keypair::new().pubkey().to_string();
+    let key = create_sk(Wallet::Solana, &signer, 1000);
+
+    db.set_global(Some(2000)).unwrap();
+    assert_eq!(db.revoked_before(&key).unwrap(), Some(2000));
+
+    // The later of the global and signer watermarks applies
+    db.raise(Wallet::Solana, &signer, 3000).unwrap();
+    let key = create_sk(Wallet::Solana, &signer, 2500);
+    assert_eq!(db.revoked_before(&key).unwrap(), Some(3000));
+
+    let watermarks = db.list().unwrap();
+    assert_eq!(watermarks.global, Some(2000));
+    assert_eq!(watermarks.signers.get(&signer), Some(&3000));
+
+    db.set_global(None).unwrap();
+    assert_eq!(db.list().unwrap().global, None);
+}
+
+#[test]
+fn test_ethereum_signer() {
+    let db = create_db();
+
+    // Addresses are the same signer in any case
+    db.raise(Wallet::Ethereum, &ADDRESS.to_lowercase(), 2000)
+        .unwrap();
+    let key = create_sk(Wallet::Ethereum, ADDRESS, 1000);
+    assert_eq!(db.revoked_before(&key).unwrap(), Some(2000));
+    assert_eq!(
+        db.list().unwrap().signers.keys().collect::<Vec<_>>(),
+        [ADDRESS]
+    );
+}
+
+#[test]
+fn test_delegated_key() {
+    let db = create_db();
+    let parent_signer = Keypair::new().pubkey().to_string();
+    let mut key = create_sk(Wallet::Solana, &Keypair::new().pubkey().to_string(), 3000);
+    key.metadata.parent = Some(Box::new(create_sk(Wallet::Solana, &parent_signer, 1000)));
+
+    // Keys delegated from a revoked key are revoked too
+    db.raise(Wallet::Solana, &parent_signer, 2000).unwrap();
+    assert_eq!(db.revoked_before(&key).unwrap(), Some(2000));
+}
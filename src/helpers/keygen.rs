@@ -4,10 +4,55 @@ use chrono::{Duration, Utc};
 use solana_sdk::signer::Signer;
 
 use crate::{
-    core::keys::{MetadataRawV1, MetadataV1, Scope, SecretKeyV1, Wallet},
-    utils::id::create_keypair_from_file,
+    core::{
+        ethereum,
+        keys::{KeyDelegate, KeyRestrictions, MetadataV2, Scope, SecretKeyV2, Wallet},
+    },
+    utils::id::{create_keypair_from_file, read_ethereum_key_file},
 };
 
+/// Wallet file to sign a secret key with
+pub enum KeySigner {
+    /// Solana wallet id file, `~/.config/solana/id.json` if not set
+    Solana(Option<PathBuf>),
+    /// Ethereum private key file
+    Ethereum(PathBuf),
+}
+
+impl KeySigner {
+    /// Sign `bytes` with the wallet, returning its type, signer and signature
+    pub fn sign(self, bytes: &[u8]) -> anyhow::Result<(Wallet, String, String)> {
+        match self {
+            KeySigner::Solana(id) => {
+                let keypair = create_keypair_from_file(id)?;
+                let signature = keypair.sign_message(bytes).to_string();
+                Ok((Wallet::Solana, keypair.pubkey().to_string(), signature))
+            }
+            KeySigner::Ethereum(path) => {
+                let secret_key = read_ethereum_key_file(path)?;
+                let public_key = libsecp256k1::PublicKey::from_secret_key(&secret_key);
+                let signature = ethereum::sign_message(&secret_key, bytes);
+                Ok((
+                    Wallet::Ethereum,
+                    Wallet::Ethereum.encode_signer(&ethereum::address_of(&public_key)),
+                    Wallet::Ethereum.encode_signature(&signature),
+                ))
+            }
+        }
+    }
+}
+
+/// Optional metadata of a generated secret key
+#[derive(Default)]
+pub struct KeygenOptions {
+    pub label: Option<String>,
+    pub restrictions: KeyRestrictions,
+    /// Signer allowed to sign delegated keys of the key
+    pub delegate: Option<KeyDelegate>,
+    /// Secret key to delegate the key from
+    pub parent: Option<String>,
+}
+
 /// Generate a secret key with given metadata and keypair.
 ///
 /// NOTE: `valid_for` is how many **DAYS** this key should be valid for.
@@ -16,30 +61,45 @@ pub fn generate_secret_key(
     valid_for: u32,
     scopes: Vec<Scope>,
     usage_limit: u64,
-    id: Option<PathBuf>,
+    options: KeygenOptions,
+    signer: KeySigner,
 ) -> anyhow::Result<String> {
-    let keypair = create_keypair_from_file(id)?;
     let valid_for = Duration::days(valid_for.into()).num_milliseconds();
     let created_at = Utc::now().timestamp_millis();
+    let parent = options
+        .parent
+        .map(|parent| SecretKeyV2::decode(&parent).map(|(_, parent)| Box::new(parent)))
+        .transpose()?;
 
-    let metadata = MetadataV1 {
+    let metadata = MetadataV2 {
         created_at,
         usage_limit,
         valid_for,
         scopes,
+        label: options.label,
+        tag: Some(tag.to_string()),
+        restrictions: options.restrictions,
+        delegate: options.delegate,
+        parent,
+        extensions: vec![],
     };
 
-    let bytes = MetadataRawV1::try_from(metadata.clone())?.into_bytes();
-    let signature = keypair.sign_message(&bytes[..]).to_string();
-    let signer = keypair.pubkey().to_string();
+    let version = 2;
+    let bytes = metadata.signing_bytes(version)?;
+    let (wallet, signer, signature) = signer.sign(&bytes)?;
 
-    let payload = SecretKeyV1 {
-        version: 1,
-        wallet: Wallet::Solana,
+    let payload = SecretKeyV2 {
+        version,
+        wallet,
         signer,
         signature,
         metadata,
     };
 
-    Ok(payload.into_string(tag)?)
+    // Catch keys their parent doesn't allow before handing them out
+    if payload.metadata.parent.is_some() {
+        payload.verify_signature()?;
+    }
+
+    payload.into_string(tag)
 }
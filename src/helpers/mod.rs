@@ -1,2 +1,3 @@
 pub mod keygen;
 pub mod proxy;
+pub mod revoke;
@@ -9,24 +9,28 @@ use tokio_tungstenite::{
     connect_async,
     tungstenite::{self, Message},
 };
-use tracing::{debug, error, info, warn};
+use tracing::{Instrument, debug, error, info, warn};
 use url::Url;
 
-use crate::core::transport::{
-    ESSENTIAL_RESPONSE_HEADERS, Request, Response, filter_essential_headers,
+use crate::{
+    core::{
+        keys::signer_fingerprint,
+        transport::{ESSENTIAL_RESPONSE_HEADERS, Request, Response, filter_essential_headers},
+    },
+    telemetry,
 };
 
 /// Proxy aimo node requests to standard http endpoints
 ///
 /// 1. Connect to aimo node's websocket endpoint
 /// 2. On receiving serialized `Request` messages, spawn a tokio thread to do the following:
-///     a. Deserialize the `Request`, extract its payload;
-///     b. Forward the request payload to the http endpoint;
-///     c. Wait for response.
-///     d. If the response is a normal http response, wrap the response in `Response` message,
-///         send it back through websocket, and quit the thread.
-///     e. If the response is a SSE stream, wrap each chunk inside the `Response` message, and
-///         send back through websocket in receiving sequence.
+///    a. Deserialize the `Request`, extract its payload;
+///    b. Forward the request payload to the http endpoint;
+///    c. Wait for response.
+///    d. If the response is a normal http response, wrap the response in `Response` message,
+///    send it back through websocket, and quit the thread.
+///    e. If the response is a SSE stream, wrap each chunk inside the `Response` message, and
+///    send back through websocket in receiving sequence.
 pub async fn serve_websocket(
     node_url: String,
     secret_key: String,
@@ -83,7 +87,7 @@ pub async fn serve_websocket(
     while let Some(message) = ws_receiver.next().await {
         match message {
             Ok(Message::Text(text)) => {
-                debug!("Received message: {}", text);
+                debug!("Received message of {} bytes", text.len());
 
                 // Parse the request directly (no MessageFrame wrapper)
                 let request: Request = match serde_json::from_str::<Request>(&text) {
@@ -96,7 +100,6 @@ pub async fn serve_websocket(
                     }
                     Err(e) => {
                         warn!("Failed to parse request: {}", e);
-                        warn!("Raw message was: {}", text);
                         continue;
                     }
                 }; // Clone necessary data for the spawned task
@@ -105,15 +108,28 @@ pub async fn serve_websocket(
                 let client = http_client.clone();
                 let response_sender = response_tx.clone();
 
+                // Continue the node's trace in this request's span
+                let span = tracing::info_span!(
+                    "proxy_request",
+                    request_id = %request.request_id,
+                    service_id = %request.service_id,
+                    signer = %signer_fingerprint(&request.sender_id),
+                    otel.kind = "client",
+                );
+                telemetry::set_parent(&span, &request.trace_context);
+
                 // Spawn a task to handle this request
-                tokio::spawn(async move {
-                    if let Err(e) =
-                        handle_request(client, request, endpoint_url, api_key, response_sender)
-                            .await
-                    {
-                        error!("Error handling request: {}", e);
+                tokio::spawn(
+                    async move {
+                        if let Err(e) =
+                            handle_request(client, request, endpoint_url, api_key, response_sender)
+                                .await
+                        {
+                            error!("Error handling request: {}", e);
+                        }
                     }
-                });
+                    .instrument(span),
+                );
             }
             Ok(Message::Close(_)) => {
                 info!("WebSocket connection closed by server");
@@ -201,25 +217,22 @@ async fn handle_request(
     };
 
     debug!("Forwarding {} request to: {}", method, target_url);
-    debug!("Request headers: {:?}", request.headers);
+    debug!(
+        "Request headers: {:?}",
+        telemetry::redact_headers(&request.headers)
+    );
     debug!("Request payload length: {} bytes", request.payload.len());
-    if !request.payload.is_empty() {
-        debug!(
-            "Request payload preview: {}",
-            if request.payload.len() > 200 {
-                format!("{}...", &request.payload[..200])
-            } else {
-                request.payload.clone()
-            }
-        );
-    }
 
     // Build the HTTP request
     let mut http_request = client.request(method, &target_url);
 
     // Add headers from the original request
     for (key, value) in &request.headers {
-        debug!("Adding header: {}: {}", key, value);
+        http_request = http_request.header(key, value);
+    }
+
+    // Forward the trace context to the upstream endpoint
+    for (key, value) in telemetry::current_context() {
         http_request = http_request.header(key, value);
     }
 
@@ -336,14 +349,6 @@ async fn handle_regular_response(
         status_code, content_type
     );
     info!("Response body length: {} bytes", body.len());
-    debug!(
-        "Response body: {}",
-        if body.len() > 1000 {
-            format!("{}...", &body[..1000])
-        } else {
-            body.clone()
-        }
-    );
 
     let response = Response {
         request_id: request_id.to_string(),
@@ -381,15 +386,7 @@ async fn handle_sse_stream(
         match chunk_result {
             Ok(chunk) => {
                 let chunk_data = String::from_utf8_lossy(&chunk).to_string();
-                debug!(
-                    "Received stream chunk ({} bytes): {}",
-                    chunk.len(),
-                    if chunk_data.len() > 200 {
-                        format!("{}...", &chunk_data[..200])
-                    } else {
-                        chunk_data.clone()
-                    }
-                );
+                debug!("Received stream chunk ({} bytes)", chunk.len());
 
                 // Send this chunk as a streaming response
                 let response = Response {
@@ -0,0 +1,56 @@
+use anyhow::bail;
+use reqwest::{Client, header::CONTENT_TYPE};
+use url::Url;
+
+use crate::{
+    core::{
+        keys::SecretKeyV2,
+        revocation::{RevocationMessage, RevocationTarget, SignedRevocation},
+    },
+    helpers::keygen::KeySigner,
+};
+
+/// Sign a revocation of `target` for `network` with the wallet and submit it to the node
+/// at `node_url`. Signer revocations are authenticated with `revoke_key`, a secret key
+/// of the wallet with the `revoke` scope.
+pub async fn submit_revocation(
+    node_url: &str,
+    network: &str,
+    target: RevocationTarget,
+    signer: KeySigner,
+    revoke_key: Option<String>,
+) -> anyhow::Result<()> {
+    let path = match &target {
+        RevocationTarget::Key { secret_key } => {
+            // Catch typos before signing anything
+            SecretKeyV2::decode(secret_key)?;
+            "/api/v1/keys/revoke"
+        }
+        RevocationTarget::Signer { .. } => "/api/v1/keys/revoke_signer",
+    };
+
+    let message = RevocationMessage::new(network, target);
+    let (_, signer, signature) = signer.sign(&message.signing_bytes()?)?;
+    let body = SignedRevocation {
+        signer,
+        signature,
+        message,
+    };
+
+    let url = Url::parse(node_url)?.join(path)?;
+    let mut request = Client::new()
+        .post(url)
+        .header(CONTENT_TYPE, "application/json");
+    if let Some(revoke_key) = revoke_key {
+        request = request.bearer_auth(revoke_key);
+    }
+    let response = request.body(serde_json::to_string(&body)?).send().await?;
+
+    let status = response.status();
+    if !status.is_success() {
+        let reason = response.text().await.unwrap_or_default();
+        bail!("Node rejected the revocation ({status}): {reason}");
+    }
+
+    Ok(())
+}
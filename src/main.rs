@@ -1,11 +1,18 @@
-use std::process;
+use std::{process, sync::Arc};
 
 use clap::Parser;
 
 use crate::{
     cli::{CliArgs, CommandArgs},
-    helpers::{keygen::generate_secret_key, proxy},
+    config::{ConfigOverrides, ConfigReloader, ConfigSource, TelemetryOptions},
+    core::{keys::KeyRestrictions, revocation::RevocationTarget},
+    helpers::{
+        keygen::{KeySigner, KeygenOptions, generate_secret_key},
+        proxy,
+        revoke::submit_revocation,
+    },
     node::run_serve,
+    telemetry::LogOptions,
 };
 
 mod cli;
@@ -16,23 +23,60 @@ mod helpers;
 mod node;
 mod router;
 mod server;
+mod telemetry;
 mod utils;
 
+#[cfg(test)]
+mod node_test;
+#[cfg(test)]
+mod telemetry_test;
+#[cfg(test)]
+mod test_utils;
+
 #[tokio::main]
 async fn main() {
-    tracing_subscriber::fmt::init();
-
-    let args = CliArgs::parse();
+    let CliArgs { command, log } = CliArgs::parse();
 
-    match args.command {
+    match command {
         // aimo serve
         CommandArgs::Serve {
+            config,
+            print_config,
             port,
             addr,
             id,
             state_db_dir,
+            stream_resume_window,
         } => {
-            run_serve(addr, port, id, state_db_dir).await;
+            let source = ConfigSource {
+                path: config,
+                overrides: ConfigOverrides {
+                    addr,
+                    port,
+                    stream_resume_window,
+                    id,
+                    state_db_dir,
+                },
+            };
+            let config = source.load().unwrap_or_else(|err| {
+                println!("Error: {err}");
+                process::exit(1);
+            });
+
+            if print_config {
+                match config.to_toml() {
+                    Ok(toml) => print!("{toml}"),
+                    Err(err) => {
+                        println!("Error: {err}");
+                        process::exit(1);
+                    }
+                }
+                return;
+            }
+
+            let telemetry = init_telemetry(&log, &config.telemetry);
+            run_serve(Arc::new(ConfigReloader::new(source, config))).await;
+            telemetry.shutdown();
         }
 
         // aimo keygen
@@ -41,29 +85,109 @@ async fn main() {
             valid_for,
             scopes,
             usage_limit,
+            label,
+            services,
+            models,
+            allow_from,
+            not_before,
+            max_streams,
             id,
+            eth_key,
+            delegate,
+            parent,
         } => {
-            if let Err(err) = generate_secret_key(&tag, valid_for, scopes, usage_limit, id)
-                .map(|sk| println!("{sk}"))
+            init_telemetry(&log, &TelemetryOptions::default());
+            let options = KeygenOptions {
+                label,
+                restrictions: KeyRestrictions {
+                    services,
+                    models,
+                    addresses: allow_from,
+                    not_before: not_before.map(|time| time.timestamp_millis()),
+                    max_streams,
+                },
+                delegate,
+                parent,
+            };
+            if let Err(err) = generate_secret_key(
+                &tag,
+                valid_for,
+                scopes,
+                usage_limit,
+                options,
+                eth_key.map_or(KeySigner::Solana(id), KeySigner::Ethereum),
+            )
+            .map(|sk| println!("{sk}"))
             {
                 println!("Error: {err}");
                 process::exit(1);
             }
         }
 
+        // aimo revoke
+        CommandArgs::Revoke {
+            node_url,
+            secret_key,
+            issued_before,
+            all_keys: _,
+            revoke_key,
+            network,
+            id,
+            eth_key,
+        } => {
+            init_telemetry(&log, &TelemetryOptions::default());
+            let signer = eth_key.map_or(KeySigner::Solana(id), KeySigner::Ethereum);
+            let target = match (secret_key, issued_before) {
+                (Some(secret_key), _) => RevocationTarget::Key { secret_key },
+                (None, Some(time)) => RevocationTarget::Signer {
+                    issued_before: time.timestamp_millis(),
+                },
+                // Clap requires `--all-keys` otherwise
+                (None, None) => RevocationTarget::Signer {
+                    issued_before: i64::MAX,
+                },
+            };
+            match submit_revocation(&node_url, &network, target, signer, revoke_key).await {
+                Ok(()) => println!("Revoked"),
+                Err(err) => {
+                    println!("Error: {err}");
+                    process::exit(1);
+                }
+            }
+        }
+
         // aimo proxy
         CommandArgs::Proxy {
             node_url,
             secret_key,
             endpoint_url,
             api_key,
+            otlp_endpoint,
         } => {
-            if let Err(err) =
-                proxy::serve_websocket(node_url, secret_key, endpoint_url, api_key).await
-            {
+            let mut options = TelemetryOptions {
+                service_name: "aimo-proxy".to_string(),
+                ..Default::default()
+            };
+            if let Err(err) = options.apply_env() {
+                println!("Error: {err}");
+                process::exit(1);
+            }
+            options.otlp_endpoint = otlp_endpoint.or(options.otlp_endpoint);
+
+            let telemetry = init_telemetry(&log, &options);
+            let result = proxy::serve_websocket(node_url, secret_key, endpoint_url, api_key).await;
+            telemetry.shutdown();
+            if let Err(err) = result {
                 println!("Error: {err}");
                 process::exit(1);
             }
         }
     }
 }
+
+fn init_telemetry(log: &LogOptions, options: &TelemetryOptions) -> telemetry::Telemetry {
+    telemetry::init(log, options).unwrap_or_else(|err| {
+        println!("Error: failed to set up telemetry: {err}");
+        process::exit(1);
+    })
+}
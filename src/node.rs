@@ -1,67 +1,270 @@
-use std::{path::PathBuf, process, sync::Arc};
+use std::{
+    process,
+    sync::Arc,
+    time::{Duration, Instant},
+};
 
+use chrono::Utc;
+#[cfg(unix)]
+use tokio::signal::unix::{SignalKind, signal};
 use tokio::task::JoinSet;
 
 use crate::{
-    config::ServerOptions,
+    config::ConfigReloader,
+    core::health::Health,
     db::{self, StateDb},
     router::local::LocalRouter,
-    server::{self, ServiceContext},
+    server::{self, ApiState, ServiceContext, Shutdown},
 };
 
-enum TaskFinishBehaviour {
+/// Consecutive failures of a subsystem are forgotten once it ran for this long
+const STABLE_RUN: Duration = Duration::from_secs(60);
+const INITIAL_BACKOFF: Duration = Duration::from_secs(1);
+const MAX_BACKOFF: Duration = Duration::from_secs(30);
+const MAX_CONSECUTIVE_RESTARTS: u32 = 5;
+
+/// How a supervised subsystem is handled when it fails: it's restarted with exponential
+/// backoff, and the program exits after `max_restarts` consecutive failures
+#[derive(Debug, Clone, Copy)]
+pub(super) struct RestartPolicy {
+    pub max_restarts: u32,
+}
+
+pub(super) enum TaskFinishBehaviour {
     /// Exit the program when the task finishes
-    Abort(&'static str),
-    // Restart,
+    Abort(String),
+
+    /// The node shut down gracefully
+    Exit,
 }
 
-/// Run the full node service (server + router)
-pub async fn run_serve(
-    addr: String,
-    port: u16,
-    _id: Option<PathBuf>,
-    state_db_dir: Option<PathBuf>,
-) {
+/// Runs the node's subsystems and restarts them on failure according to their policies
+pub(super) struct Supervisor {
+    tasks: JoinSet<TaskFinishBehaviour>,
+    health: Health,
+    shutdown: Shutdown,
+}
+
+impl Supervisor {
+    pub fn new(health: Health, shutdown: Shutdown) -> Self {
+        Self {
+            tasks: JoinSet::new(),
+            health,
+            shutdown,
+        }
+    }
+
+    /// Run a subsystem, which is expected to run until the node shuts down
+    pub fn spawn<F, Fut>(&mut self, name: &'static str, policy: RestartPolicy, run: F)
+    where
+        F: Fn() -> Fut + Send + 'static,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        let health = self.health.clone();
+        let shutdown = self.shutdown.clone();
+
+        self.tasks.spawn(async move {
+            let mut failures = 0;
+            loop {
+                health.set_running(name);
+                let started = Instant::now();
+
+                // Run in a task of its own to catch panics
+                let error = match tokio::spawn(run()).await {
+                    Ok(()) if shutdown.is_draining() => return TaskFinishBehaviour::Exit,
+                    Ok(()) => "stopped unexpectedly".to_string(),
+                    Err(err) => err.to_string(),
+                };
+
+                if started.elapsed() >= STABLE_RUN {
+                    failures = 0;
+                }
+                failures += 1;
+
+                if failures > policy.max_restarts || shutdown.is_draining() {
+                    health.set_stopped(name, error.clone());
+                    return TaskFinishBehaviour::Abort(format!("Subsystem {name} failed: {error}"));
+                }
+
+                let backoff = INITIAL_BACKOFF
+                    .saturating_mul(1 << (failures - 1).min(16))
+                    .min(MAX_BACKOFF);
+                tracing::warn!(
+                    "Subsystem {name} failed: {error}, restarting in {}s",
+                    backoff.as_secs()
+                );
+                health.set_restarting(name, error);
+                tokio::time::sleep(backoff).await;
+            }
+        });
+    }
+
+    /// Wait for a subsystem to stop for good
+    pub async fn join_next(&mut self) -> TaskFinishBehaviour {
+        match self.tasks.join_next().await {
+            Some(Ok(finish_behaviour)) => finish_behaviour,
+            Some(Err(err)) => TaskFinishBehaviour::Abort(format!("Supervisor failed: {err}")),
+            None => TaskFinishBehaviour::Exit,
+        }
+    }
+}
+
+/// Run the full node service (server + router) with a validated config
+pub async fn run_serve(reloader: Arc<ConfigReloader>) {
+    let config = reloader.current();
     let state_db = Arc::new(
-        StateDb::load_or_create(&state_db_dir.unwrap_or(db::default_directory()))
+        StateDb::load_or_create(&config.state_db_dir.unwrap_or(db::default_directory()))
             .expect("Failed to create state db"),
     );
-    let server_options = ServerOptions { addr, port };
-    let router_instance = Arc::new(LocalRouter::new());
+    let router_instance = Arc::new(LocalRouter::with_options(config.router));
 
-    let mut tasks_js = JoinSet::new();
+    // Reload the configuration on SIGHUP
+    #[cfg(unix)]
+    {
+        let reloader = reloader.clone();
+        tokio::spawn(async move {
+            let mut hangup = signal(SignalKind::hangup()).expect("Failed to listen for SIGHUP");
+            while hangup.recv().await.is_some() {
+                match reloader.reload() {
+                    Ok(report) => {
+                        tracing::info!(
+                            "Configuration reloaded, applied: {:?}, requires restart: {:?}",
+                            report.applied,
+                            report.requires_restart
+                        );
+                    }
+                    Err(err) => tracing::error!("Failed to reload configuration: {err}"),
+                }
+            }
+        });
+    }
+
+    // Drain on SIGTERM or SIGINT
+    let shutdown = Shutdown::new();
+    let draining = shutdown.clone();
+    tokio::spawn(async move {
+        shutdown_signal().await;
+        tracing::info!("Shutdown requested, draining requests in flight");
+        draining.drain();
+    });
+
+    let health = Health::default();
+    let mut supervisor = Supervisor::new(health.clone(), shutdown.clone());
+    let restart = RestartPolicy {
+        max_restarts: MAX_CONSECUTIVE_RESTARTS,
+    };
 
     // The router task
     let router_cloned = router_instance.clone();
-    tasks_js.spawn(async move {
-        router_cloned.run().await;
-
-        // Should run forever
-        TaskFinishBehaviour::Abort("Router aborted unexpectedly")
+    supervisor.spawn("router", restart, move || {
+        let router = router_cloned.clone();
+        async move { router.run().await }
     });
 
-    // The server task
-    tasks_js.spawn(async move {
-        let ctx = ServiceContext::new(router_instance);
+    // The server task, sharing its state across restarts
+    let api_state = ApiState::new(
+        ServiceContext::new(router_instance),
+        state_db.clone(),
+        reloader.clone(),
+        shutdown.clone(),
+        health,
+    );
+    supervisor.spawn("api_server", restart, move || {
         tracing::info!("API server task created.");
-        server::serve(&server_options, ctx, state_db).await;
+        server::serve(api_state.clone())
+    });
 
-        // This should run forever,
-        TaskFinishBehaviour::Abort("API server aborted unexpectedly")
+    // Revocations of expired keys are dropped, so the state db stays bounded
+    let pruner_state_db = state_db.clone();
+    let pruner_reloader = reloader.clone();
+    let pruner_shutdown = shutdown.clone();
+    supervisor.spawn("revocation_pruner", restart, move || {
+        prune_revocations(
+            pruner_state_db.clone(),
+            pruner_reloader.clone(),
+            pruner_shutdown.clone(),
+        )
     });
 
-    while let Ok(finish_behaviour) = tasks_js
-        .join_next()
-        .await
-        // We guarantee the JoinSet is not empty
-        .unwrap()
+    let finish_behaviour = wait_for_stop(&mut supervisor, &shutdown, &reloader).await;
+
+    if let Err(err) = state_db.flush() {
+        tracing::error!("Failed to flush state db: {err}");
+    }
+
+    match finish_behaviour {
+        // Abort the process
+        TaskFinishBehaviour::Abort(reason) => {
+            tracing::error!("Process aborted: {reason}");
+            process::exit(1);
+        }
+        TaskFinishBehaviour::Exit => tracing::info!("Node shut down"),
+    }
+}
+
+/// Wait for a subsystem to stop for good, or for the shutdown grace period to end
+pub(super) async fn wait_for_stop(
+    supervisor: &mut Supervisor,
+    shutdown: &Shutdown,
+    reloader: &ConfigReloader,
+) -> TaskFinishBehaviour {
+    // Requests still in flight when the grace period ends are dropped
+    let grace_period = async {
+        shutdown.draining().await;
+        match reloader.options().timeouts.shutdown_grace() {
+            Some(grace) => tokio::time::sleep(grace).await,
+            None => std::future::pending().await,
+        }
+        tracing::warn!(
+            "Shutdown grace period elapsed with {} requests in flight",
+            shutdown.in_flight()
+        );
+    };
+
+    tokio::select! {
+        finish_behaviour = supervisor.join_next() => finish_behaviour,
+        _ = grace_period => TaskFinishBehaviour::Exit,
+    }
+}
+
+/// Prune the revocations of expired keys periodically, until the node drains
+pub(super) async fn prune_revocations(
+    state_db: Arc<StateDb>,
+    reloader: Arc<ConfigReloader>,
+    shutdown: Shutdown,
+) {
+    loop {
+        // Intervals and retention follow reloads
+        let keys = reloader.options().keys.clone();
+        tokio::select! {
+            _ = tokio::time::sleep(Duration::from_secs(keys.revocation_prune_interval)) => {}
+            _ = shutdown.draining() => break,
+        }
+
+        let now = Utc::now().timestamp_millis();
+        let retention_ms = (keys.revocation_retention as i64).saturating_mul(1000);
+        match state_db.revocation.prune_expired(now, retention_ms) {
+            Ok(0) => {}
+            Ok(pruned) => tracing::info!("Pruned {pruned} revocations of expired keys"),
+            Err(err) => tracing::error!("Failed to prune revocations: {err}"),
+        }
+    }
+
+    // The API server stops the node once its requests are drained, which a finished
+    // helper would preempt
+    std::future::pending().await
+}
+
+async fn shutdown_signal() {
+    #[cfg(unix)]
     {
-        match finish_behaviour {
-            // Abort the process
-            TaskFinishBehaviour::Abort(reason) => {
-                tracing::error!("Process aborted: {reason}");
-                process::exit(1);
-            }
+        let mut terminate = signal(SignalKind::terminate()).expect("Failed to listen for SIGTERM");
+        tokio::select! {
+            _ = terminate.recv() => {}
+            _ = tokio::signal::ctrl_c() => {}
         }
     }
+
+    #[cfg(not(unix))]
+    let _ = tokio::signal::ctrl_c().await;
 }
@@ -0,0 +1,55 @@
+use std::{env, sync::Arc, time::Duration};
+
+use tokio::time::timeout;
+
+use crate::{
+    config::{ConfigReloader, ConfigSource, NodeConfig},
+    core::health::Health,
+    db::StateDb,
+    node::*,
+    server::Shutdown,
+};
+
+#[tokio::test]
+async fn test_drain_waits_for_requests() {
+    let reloader = Arc::new(ConfigReloader::new(
+        ConfigSource::default(),
+        NodeConfig::default(),
+    ));
+    let directory = env::temp_dir().join(format!("aimo-{}", uuid::Uuid::new_v4()));
+    let state_db = Arc::new(StateDb::load_or_create(&directory).unwrap());
+    let shutdown = Shutdown::new();
+    let mut supervisor = Supervisor::new(Health::default(), shutdown.clone());
+    let restart = RestartPolicy { max_restarts: 0 };
+
+    let (pruner_reloader, pruner_shutdown) = (reloader.clone(), shutdown.clone());
+    supervisor.spawn("revocation_pruner", restart, move || {
+        prune_revocations(
+            state_db.clone(),
+            pruner_reloader.clone(),
+            pruner_shutdown.clone(),
+        )
+    });
+
+    // Stands in for the API server, which stops once its requests are drained
+    let server_shutdown = shutdown.clone();
+    supervisor.spawn("api_server", restart, move || {
+        let shutdown = server_shutdown.clone();
+        async move { shutdown.drained().await }
+    });
+
+    let stream = shutdown.track().unwrap();
+    shutdown.drain();
+
+    let stop = wait_for_stop(&mut supervisor, &shutdown, &reloader);
+    tokio::pin!(stop);
+    assert!(
+        timeout(Duration::from_millis(200), &mut stop)
+            .await
+            .is_err()
+    );
+
+    drop(stream);
+    let finish_behaviour = timeout(Duration::from_secs(1), stop).await.unwrap();
+    assert!(matches!(finish_behaviour, TaskFinishBehaviour::Exit));
+}
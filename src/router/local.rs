@@ -4,6 +4,7 @@ use anyhow::bail;
 use async_trait::async_trait;
 use tokio::sync::{Mutex, mpsc};
 
+use crate::config::RouterOptions;
 use crate::core::transport::{MessagePayload, Request, Response};
 
 use crate::core::router::{ResponseHandler, Router, make_connection};
@@ -14,17 +15,24 @@ pub struct LocalRouter {
     service_connections: Arc<Mutex<HashMap<String, mpsc::Sender<Request>>>>,
     message_tx: mpsc::Sender<MessagePayload>,
     message_rx: Arc<Mutex<mpsc::Receiver<MessagePayload>>>,
+    options: RouterOptions,
 }
 
 impl LocalRouter {
+    #[allow(dead_code)]
     pub fn new() -> Self {
-        let (message_tx, message_rx) = mpsc::channel(128);
+        Self::with_options(RouterOptions::default())
+    }
+
+    pub fn with_options(options: RouterOptions) -> Self {
+        let (message_tx, message_rx) = mpsc::channel(options.message_buffer);
 
         Self {
             client_connections: Arc::new(Mutex::new(HashMap::new())),
             service_connections: Arc::new(Mutex::new(HashMap::new())),
             message_tx,
             message_rx: Arc::new(Mutex::new(message_rx)),
+            options,
         }
     }
 
@@ -40,7 +48,11 @@ impl LocalRouter {
         loop {
             match message_rx.recv().await {
                 Some(MessagePayload::Request(request)) => {
-                    tracing::debug!("Received message request {:?}", request);
+                    tracing::debug!(
+                        "Received request {} for service {}",
+                        request.request_id,
+                        request.service_id
+                    );
                     let service_id = request.service_id.clone();
                     if let Some(connection) = service_connections_ptr.lock().await.get(&service_id)
                     {
@@ -56,7 +68,11 @@ impl LocalRouter {
                     }
                 }
                 Some(MessagePayload::Response(response)) => {
-                    tracing::debug!("Received message response {:?}", response);
+                    tracing::debug!(
+                        "Received response {} with status {}",
+                        response.request_id,
+                        response.status_code
+                    );
                     let request_id = response.request_id.clone();
                     if let Some(connection) = client_connections_ptr.lock().await.get(&request_id) {
                         let tx = connection.clone();
@@ -88,7 +104,8 @@ impl LocalRouter {}
 #[async_trait]
 impl Router for LocalRouter {
     async fn register_service(&self, service_id: String) -> anyhow::Result<ResponseHandler> {
-        let (client_handler, service_handler) = make_connection(16, 16);
+        let (client_handler, service_handler) =
+            make_connection(self.options.service_buffer, self.options.service_buffer);
 
         let tx = client_handler.tx.clone();
         self.service_connections
@@ -113,8 +130,16 @@ impl Router for LocalRouter {
         Ok(service_handler)
     }
 
+    #[tracing::instrument(
+        name = "route_request",
+        skip_all,
+        fields(service_id = %request.service_id, request_id = %request.request_id)
+    )]
     async fn route_request(&self, request: Request) -> anyhow::Result<mpsc::Receiver<Response>> {
-        let (mut req_handler, res_handler) = make_connection::<Request, _>(1, 1);
+        let (mut req_handler, res_handler) = make_connection::<Request, _>(
+            self.options.response_buffer,
+            self.options.response_buffer,
+        );
 
         self.client_connections
             .lock()
@@ -127,7 +152,7 @@ impl Router for LocalRouter {
             .send(MessagePayload::Request(request))
             .await?;
 
-        let (tx, rx) = mpsc::channel(1);
+        let (tx, rx) = mpsc::channel(self.options.response_buffer);
         tokio::spawn(async move {
             while let Ok(resp) = req_handler.recv().await {
                 if let Err(err) = tx.send(resp).await {
@@ -157,4 +182,12 @@ impl Router for LocalRouter {
 
         Ok(())
     }
+
+    async fn service_count(&self) -> usize {
+        self.service_connections.lock().await.len()
+    }
+
+    fn queue_depth(&self) -> usize {
+        self.message_tx.max_capacity() - self.message_tx.capacity()
+    }
 }
@@ -72,6 +72,7 @@ async fn test_local_router() {
                 headers: HashMap::new(),
                 payload_encrypted: false,
                 signature: None,
+                trace_context: HashMap::new(),
                 method: "GET".to_string(),
             })
             .await
@@ -91,3 +92,21 @@ async fn test_local_router() {
     .await
     .unwrap();
 }
+
+#[tokio::test]
+async fn test_service_count() {
+    let router = LocalRouter::new();
+    assert_eq!(router.service_count().await, 0);
+
+    let _connection = router
+        .register_service("test_service_id".to_string())
+        .await
+        .unwrap();
+    assert_eq!(router.service_count().await, 1);
+
+    router
+        .drop_service("test_service_id".to_string())
+        .await
+        .unwrap();
+    assert_eq!(router.service_count().await, 0);
+}
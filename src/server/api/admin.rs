@@ -0,0 +1,139 @@
+use axum::{
+    Json,
+    extract::{Path, State},
+    http::StatusCode,
+};
+
+use crate::{
+    config::ReloadReport,
+    core::keys::Wallet,
+    db::{StateStats, WatermarkDb, Watermarks},
+    server::{api::state::ApiState, types::admin::SetWatermarkRequest},
+};
+
+/// Reload the node configuration from its file and environment
+///
+/// POST /admin/config/reload
+pub async fn reload_config(
+    State(state): State<ApiState>,
+) -> Result<Json<ReloadReport>, (StatusCode, String)> {
+    let report = state
+        .config
+        .reload()
+        .map_err(|err| (StatusCode::UNPROCESSABLE_ENTITY, err.to_string()))?;
+
+    tracing::info!(
+        "Configuration reloaded, applied: {:?}, requires restart: {:?}",
+        report.applied,
+        report.requires_restart
+    );
+
+    Ok(Json(report))
+}
+
+/// Size of the state db
+///
+/// GET /admin/state/stats
+pub async fn state_stats(
+    State(state): State<ApiState>,
+) -> Result<Json<StateStats>, (StatusCode, String)> {
+    let stats = state.state_db.stats().map_err(|err| {
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            format!("Failed to read state db stats: {err}"),
+        )
+    })?;
+
+    Ok(Json(stats))
+}
+
+/// Revocation watermarks of every signer
+///
+/// GET /admin/watermarks
+pub async fn list_watermarks(
+    State(state): State<ApiState>,
+) -> Result<Json<Watermarks>, (StatusCode, String)> {
+    read_watermarks(&state)
+}
+
+fn read_watermarks(state: &ApiState) -> Result<Json<Watermarks>, (StatusCode, String)> {
+    let watermarks = state.state_db.watermarks.list().map_err(|err| {
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            format!("Failed to read watermarks: {err}"),
+        )
+    })?;
+
+    Ok(Json(watermarks))
+}
+
+/// Set the watermark of every signer
+///
+/// PUT /admin/watermarks/global
+pub async fn set_global_watermark(
+    State(state): State<ApiState>,
+    Json(body): Json<SetWatermarkRequest>,
+) -> Result<Json<Watermarks>, (StatusCode, String)> {
+    update_watermarks(state, |watermarks| {
+        watermarks.set_global(Some(body.issued_before))
+    })
+}
+
+/// DELETE /admin/watermarks/global
+pub async fn clear_global_watermark(
+    State(state): State<ApiState>,
+) -> Result<Json<Watermarks>, (StatusCode, String)> {
+    update_watermarks(state, |watermarks| watermarks.set_global(None))
+}
+
+/// Set the watermark of a signer, which unlike signed revocations may lower it
+///
+/// PUT /admin/watermarks/signers/{signer}
+pub async fn set_signer_watermark(
+    State(state): State<ApiState>,
+    Path(signer): Path<String>,
+    Json(body): Json<SetWatermarkRequest>,
+) -> Result<Json<Watermarks>, (StatusCode, String)> {
+    let wallet = parse_signer(&signer)?;
+    update_watermarks(state, |watermarks| {
+        watermarks.set_signer(wallet, &signer, Some(body.issued_before))
+    })
+}
+
+/// DELETE /admin/watermarks/signers/{signer}
+pub async fn clear_signer_watermark(
+    State(state): State<ApiState>,
+    Path(signer): Path<String>,
+) -> Result<Json<Watermarks>, (StatusCode, String)> {
+    let wallet = parse_signer(&signer)?;
+    update_watermarks(state, |watermarks| {
+        watermarks.set_signer(wallet, &signer, None)
+    })
+}
+
+fn parse_signer(signer: &str) -> Result<Wallet, (StatusCode, String)> {
+    let wallet = Wallet::of_signer(signer);
+    wallet
+        .decode_signer(signer)
+        .map_err(|err| (StatusCode::BAD_REQUEST, format!("Invalid signer: {err}")))?;
+    Ok(wallet)
+}
+
+fn update_watermarks(
+    state: ApiState,
+    update: impl FnOnce(&WatermarkDb) -> anyhow::Result<()>,
+) -> Result<Json<Watermarks>, (StatusCode, String)> {
+    update(&state.state_db.watermarks).map_err(|err| {
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            format!("Failed to update watermarks: {err}"),
+        )
+    })?;
+
+    // Cached keys were verified against the previous watermarks
+    state.key_cache.clear();
+    state.metrics.key_cache_entries.set(0);
+    tracing::info!("Revocation watermarks updated by admin");
+
+    read_watermarks(&state)
+}
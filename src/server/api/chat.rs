@@ -1,28 +1,291 @@
 use std::collections::HashMap;
+use std::pin::pin;
 
 use axum::extract::State;
-use axum::http::StatusCode;
+use axum::http::{HeaderMap, StatusCode};
 use axum::response::sse::{Event, KeepAlive};
 use axum::response::{IntoResponse, Response, Sse};
 use axum::{Extension, Json};
-use futures_util::stream;
-use serde_json::Value;
+use futures_util::stream::BoxStream;
+use futures_util::{StreamExt, future, stream};
+use serde_json::{Value, json};
 use solana_sdk::signature::Keypair;
 use solana_sdk::signer::Signer;
+use tokio::sync::mpsc;
+use tokio::time::{Instant, timeout, timeout_at};
 
-use crate::core::{keys::SecretKeyV1, transport::Request};
+use crate::config::TimeoutOptions;
+use crate::core::completion::{
+    ChatCompletionAggregator, DONE_MARKER, SseDecoder, TokenUsage, chunk_without_usage,
+    completion_into_chunks,
+};
+use crate::core::{keys::SecretKeyV2, transport};
+use crate::server::ServiceContext;
 use crate::server::api::state::ApiState;
+use crate::server::buffer::{StreamBuffers, event_id, parse_last_event_id};
+use crate::server::limiter::{SharedPermit, request_subjects};
+use crate::server::metrics::AuthFailure;
+use crate::telemetry;
+
+pub const LAST_EVENT_ID: &str = "last-event-id";
+
+/// What a completion request resolves to, in the shape the client asked for
+pub enum CompletionOutput {
+    /// Data of every `chat.completion.chunk` event, ending with `[DONE]`
+    Stream(BoxStream<'static, String>),
+
+    /// A single `chat.completion` object
+    Complete(Value),
+}
 
 /// Expose an openai-compatible API
 ///
+/// The response follows the client's `stream` field regardless of what the provider
+/// returns: streamed provider output is assembled into one `chat.completion` object for
+/// non-streaming clients, and a provider's completion object is split into chunks for
+/// streaming clients.
+///
+/// Streamed responses are buffered and every event carries an id. A client losing the
+/// connection can send the same request again with `Last-Event-ID` to receive the rest of
+/// the stream instead of starting a new completion.
+///
 /// POST /chat/completions
 // #[axum::debug_handler]
 pub async fn completions(
-    Extension(payload): Extension<SecretKeyV1>,
-    State(ApiState { ctx, .. }): State<ApiState>,
+    Extension(payload): Extension<SecretKeyV2>,
+    Extension(permit): Extension<SharedPermit>,
+    State(state): State<ApiState>,
+    headers: HeaderMap,
     Json(body): Json<Value>,
 ) -> Result<Response, (StatusCode, String)> {
+    let streams = &state.streams;
+    if headers.contains_key(LAST_EVENT_ID) {
+        return resume_stream(streams, &payload, &headers);
+    }
+
+    match dispatch_completion(&state, &payload, body).await? {
+        CompletionOutput::Stream(data) => {
+            // The stream counts as concurrent until the provider ends it, even if the client
+            // disconnects before
+            let stream_id = streams.buffer(payload.signer.clone(), data, permit.take())?;
+            resume_stream_from(streams, &payload, &stream_id, 0)
+        }
+        CompletionOutput::Complete(body) => Ok(Json(body).into_response()),
+    }
+}
+
+/// Continue a buffered completion stream after the event given in `Last-Event-ID`
+///
+/// GET /chat/completions/resume
+pub async fn resume(
+    Extension(payload): Extension<SecretKeyV2>,
+    State(ApiState { streams, .. }): State<ApiState>,
+    headers: HeaderMap,
+) -> Result<Response, (StatusCode, String)> {
+    resume_stream(&streams, &payload, &headers)
+}
+
+fn resume_stream(
+    streams: &StreamBuffers,
+    payload: &SecretKeyV2,
+    headers: &HeaderMap,
+) -> Result<Response, (StatusCode, String)> {
+    let last_event_id = headers
+        .get(LAST_EVENT_ID)
+        .ok_or((
+            StatusCode::BAD_REQUEST,
+            "`Last-Event-ID` header not specified".to_string(),
+        ))?
+        .to_str()
+        .map_err(|_| {
+            (
+                StatusCode::BAD_REQUEST,
+                "Invalid `Last-Event-ID` header".to_string(),
+            )
+        })?;
+
+    let (stream_id, from) = parse_last_event_id(last_event_id).ok_or((
+        StatusCode::BAD_REQUEST,
+        "Invalid `Last-Event-ID` header".to_string(),
+    ))?;
+
+    resume_stream_from(streams, payload, stream_id, from)
+}
+
+fn resume_stream_from(
+    streams: &StreamBuffers,
+    payload: &SecretKeyV2,
+    stream_id: &str,
+    from: usize,
+) -> Result<Response, (StatusCode, String)> {
+    let chunks = streams.subscribe(stream_id, &payload.signer, from).ok_or((
+        StatusCode::NOT_FOUND,
+        format!("Stream {stream_id} not found or expired"),
+    ))?;
+
+    let stream_id = stream_id.to_string();
+    let events = chunks.map(move |(index, data)| {
+        Ok::<Event, axum::Error>(Event::default().id(event_id(&stream_id, index)).data(data))
+    });
+
+    let sse = Sse::new(events).keep_alive(KeepAlive::default());
+    Ok(sse.into_response())
+}
+
+/// Reject keys which used up their token limit. A usage limit of 0 means unlimited.
+/// Delegated keys also share the limits of the keys they're delegated from.
+fn check_usage_limit(state: &ApiState, payload: &SecretKeyV2) -> Result<(), (StatusCode, String)> {
+    for key in payload.chain() {
+        let usage_limit = key.metadata.usage_limit;
+        if usage_limit == 0 {
+            continue;
+        }
+
+        let usage = state.state_db.usage.get(key).map_err(|err| {
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                format!("Failed to check key usage: {err}"),
+            )
+        })?;
+
+        if usage.total_tokens >= usage_limit {
+            state.metrics.record_auth_failure(AuthFailure::UsageLimit);
+            return Err((
+                StatusCode::PAYMENT_REQUIRED,
+                format!(
+                    "Usage limit exceeded: {} of {usage_limit} tokens used",
+                    usage.total_tokens
+                ),
+            ));
+        }
+    }
+
+    Ok(())
+}
+
+/// Route a chat completion request body to its provider and reconcile the provider's
+/// response with the client's `stream` field.
+///
+/// Keys past their usage limit are rejected. Requests reaching a provider and the tokens
+/// reported in the provider's `usage` block are counted against the secret key and
+/// charged to its token rate limits. Complete responses without a `usage` block, like
+/// streams assembled for clients that didn't ask for one, are charged an estimate.
+pub async fn dispatch_completion(
+    state @ ApiState {
+        ctx,
+        state_db,
+        limiter,
+        ..
+    }: &ApiState,
+    payload: &SecretKeyV2,
+    mut body: Value,
+) -> Result<CompletionOutput, (StatusCode, String)> {
+    check_usage_limit(state, payload)?;
+
+    // Ask streaming providers for a final usage chunk, so streamed tokens can be counted.
+    // Clients only get usage if they asked for it.
+    let wants_usage = body
+        .pointer("/stream_options/include_usage")
+        .and_then(Value::as_bool)
+        .unwrap_or(false);
+    let wants_stream = body.get("stream").and_then(Value::as_bool).unwrap_or(false);
+    if wants_stream
+        && !wants_usage
+        && let Some(body) = body.as_object_mut()
+        && let Value::Object(options) = body.entry("stream_options").or_insert_with(|| json!({}))
+    {
+        options.insert("include_usage".to_string(), Value::Bool(true));
+    }
+
+    let record_tokens = {
+        let state_db = state_db.clone();
+        let limiter = limiter.clone();
+        let payload = payload.clone();
+        let subjects = request_subjects(&payload, completion_target(&body));
+
+        move |tokens: TokenUsage| {
+            if let Err(err) = state_db.usage.record_tokens(&payload, tokens) {
+                tracing::warn!("Failed to record token usage: {err}");
+            }
+            limiter.record_tokens(&subjects, tokens.total_tokens);
+        }
+    };
+
+    let started = std::time::Instant::now();
+    let service = completion_target(&body).unwrap_or_default().to_string();
+    let messages = body.get("messages").cloned().unwrap_or_default();
+
+    let (output, first_response_at) =
+        route_completion(ctx, &state.options().timeouts, payload, body).await?;
+    if let Err(err) = state_db.usage.record_request(payload) {
+        tracing::warn!("Failed to record request usage: {err}");
+    }
+    let observer = state
+        .metrics
+        .observe_completion(&service, started, first_response_at - started);
+
+    match output {
+        CompletionOutput::Stream(data) => {
+            let data = data.filter_map(move |data| {
+                if data.trim() == DONE_MARKER {
+                    return future::ready(Some(data));
+                }
+                observer.chunk(&data);
+
+                let Ok(chunk) = serde_json::from_str::<Value>(&data) else {
+                    return future::ready(Some(data));
+                };
+                // Streams failing midway end with an error object
+                if chunk.get("error").is_some() {
+                    observer.stream_error();
+                }
+
+                let data = match TokenUsage::from_completion(&chunk) {
+                    Some(tokens) => {
+                        record_tokens(tokens);
+                        if wants_usage {
+                            Some(data)
+                        } else {
+                            chunk_without_usage(chunk).map(|chunk| chunk.to_string())
+                        }
+                    }
+                    None => Some(data),
+                };
+                future::ready(data)
+            });
+
+            Ok(CompletionOutput::Stream(data.boxed()))
+        }
+        CompletionOutput::Complete(body) => {
+            let tokens = TokenUsage::from_completion(&body).unwrap_or_else(|| {
+                tracing::debug!("Provider reported no usage, charging an estimate");
+                TokenUsage::estimate(&messages, &body)
+            });
+            record_tokens(tokens);
+
+            Ok(CompletionOutput::Complete(body))
+        }
+    }
+}
+
+/// The provider a completion request body targets, from its `<target>:<model_name>` model
+pub fn completion_target(body: &Value) -> Option<&str> {
+    body.get("model")?
+        .as_str()?
+        .split_once(':')
+        .map(|(target, _)| target)
+}
+
+/// Send a completion request to its provider. Returns the output along with the time the
+/// provider's first response arrived, as assembling a stream may take much longer.
+async fn route_completion(
+    ctx: &ServiceContext,
+    timeouts: &TimeoutOptions,
+    payload: &SecretKeyV2,
+    body: Value,
+) -> Result<(CompletionOutput, std::time::Instant), (StatusCode, String)> {
     let mut body_cloned = body.clone();
+    let wants_stream = body.get("stream").and_then(Value::as_bool).unwrap_or(false);
     let mut model = body
         .get("model")
         .ok_or((
@@ -46,17 +309,28 @@ pub async fn completions(
         "Can't parse model name: Invalid `model` field: Should be in this pattern: \"<target>:<model_name>\"".to_string(),
     ))?;
 
+    let restrictions = &payload.metadata.restrictions;
+    restrictions
+        .check_service(target)
+        .and_then(|_| restrictions.check_model(model_name))
+        .map_err(|err| (StatusCode::FORBIDDEN, err.to_string()))?;
+
     body_cloned["model"] = Value::String(model_name.to_string());
 
     let mut headers = HashMap::new();
     headers.insert("content-type".to_string(), "application/json".to_string());
 
+    let request_id = Keypair::new().pubkey().to_string();
+    tracing::Span::current()
+        .record("request_id", &request_id)
+        .record("service_id", target);
+
     let mut rx = ctx
         .router
-        .route_request(Request {
+        .route_request(transport::Request {
             service_id: target.to_string(),
             sender_id: payload.signer.clone(),
-            request_id: Keypair::new().pubkey().to_string(),
+            request_id,
             endpoint: None,
             request_type: "completion_model".to_string(),
             method: "POST".to_string(),
@@ -64,6 +338,7 @@ pub async fn completions(
             headers,
             payload_encrypted: false,
             signature: None,
+            trace_context: telemetry::current_context(),
         })
         .await
         .map_err(|err| {
@@ -73,10 +348,21 @@ pub async fn completions(
             )
         })?;
 
-    let response = rx.recv().await.ok_or((
+    let first_response = match timeouts.first_byte() {
+        Some(first_byte) => timeout(first_byte, rx.recv()).await.map_err(|_| {
+            (
+                StatusCode::GATEWAY_TIMEOUT,
+                format!("Provider didn't respond within {}s", timeouts.first_byte),
+            )
+        })?,
+        None => rx.recv().await,
+    };
+
+    let response = first_response.ok_or((
         StatusCode::NOT_FOUND,
-        format!("Failed to receive responses"),
+        "Failed to receive responses".to_string(),
     ))?;
+    let first_response_at = std::time::Instant::now();
 
     let status_code =
         StatusCode::from_u16(response.status_code).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR);
@@ -88,44 +374,143 @@ pub async fn completions(
         .map(|ct| ct.contains("text/event-stream"))
         .unwrap_or(false);
 
-    if is_stream {
-        // Handle streaming response
-        let first_chunk = response.payload.clone();
-        let stream = stream::unfold(
-            (Some(first_chunk), Some(rx)),
-            move |(chunk_opt, mut rx_opt)| async move {
-                if let Some(chunk) = chunk_opt {
-                    // Return the first chunk
-                    if !chunk.is_empty() {
-                        let event = Event::default().data(chunk);
-                        return Some((Ok::<Event, axum::Error>(event), (None, rx_opt)));
+    let output = match (is_stream, wants_stream) {
+        // Forward provider events as soon as they arrive
+        (true, true) => {
+            let responses = provider_responses(response, rx, *timeouts);
+            let data = async_stream::stream! {
+                let mut decoder = SseDecoder::default();
+                for await response in responses {
+                    match response {
+                        Ok(response) => {
+                            for data in decoder.feed(&response.payload) {
+                                yield data;
+                            }
+                        }
+                        // End the stream with an error event rather than just closing it
+                        Err((status, message)) => {
+                            yield error_event(status, &message);
+                            return;
+                        }
                     }
                 }
+                if let Some(data) = decoder.finish() {
+                    yield data;
+                }
+            };
 
-                // Continue receiving chunks
-                if let Some(ref mut rx) = rx_opt {
-                    if let Some(next_response) = rx.recv().await {
-                        if !next_response.payload.is_empty() {
-                            let event = Event::default().data(next_response.payload);
-                            return Some((Ok::<Event, axum::Error>(event), (None, rx_opt)));
-                        }
-                    }
+            Ok(CompletionOutput::Stream(data.boxed()))
+        }
+
+        // Assemble the provider's events into a single completion object
+        (true, false) => {
+            let mut decoder = SseDecoder::default();
+            let mut aggregator = ChatCompletionAggregator::default();
+            let mut responses = pin!(provider_responses(response, rx, *timeouts));
+
+            while let Some(response) = responses.next().await {
+                for data in decoder.feed(&response?.payload) {
+                    aggregator
+                        .push(&data)
+                        .map_err(|err| (StatusCode::BAD_GATEWAY, err.to_string()))?;
                 }
-                None
-            },
-        );
+            }
+            if let Some(data) = decoder.finish() {
+                aggregator
+                    .push(&data)
+                    .map_err(|err| (StatusCode::BAD_GATEWAY, err.to_string()))?;
+            }
+
+            Ok(CompletionOutput::Complete(aggregator.finish()))
+        }
+
+        // Replay the provider's completion object as a stream of chunks
+        (false, true) if status_code.is_success() => {
+            let completion = serde_json::from_str::<Value>(&response.payload).map_err(|err| {
+                (
+                    StatusCode::BAD_GATEWAY,
+                    format!("Provider returned an invalid completion: {err}"),
+                )
+            })?;
+
+            let data = completion_into_chunks(&completion)
+                .into_iter()
+                .map(|chunk| chunk.to_string())
+                .chain([DONE_MARKER.to_string()]);
+
+            Ok(CompletionOutput::Stream(stream::iter(data).boxed()))
+        }
 
-        let sse = Sse::new(stream).keep_alive(KeepAlive::default());
-        Ok(sse.into_response())
-    } else {
         // Handle regular JSON response
-        let body = serde_json::from_str::<Value>(&response.payload)
-            .unwrap_or(Value::String(response.payload));
+        (false, _) => {
+            let body = serde_json::from_str::<Value>(&response.payload)
+                .unwrap_or(Value::String(response.payload));
 
-        if !status_code.is_success() {
-            return Err((status_code, body.to_string()));
+            if !status_code.is_success() {
+                return Err((status_code, body.to_string()));
+            }
+
+            Ok(CompletionOutput::Complete(body))
         }
+    }?;
+
+    Ok((output, first_response_at))
+}
+
+/// Chain the first provider response with the rest of them, ending at `stream_done`.
+///
+/// Yields a `GATEWAY_TIMEOUT` error and ends if the provider goes idle for too long or the
+/// stream exceeds its total deadline.
+fn provider_responses(
+    first: transport::Response,
+    mut rx: mpsc::Receiver<transport::Response>,
+    timeouts: TimeoutOptions,
+) -> impl futures_core::Stream<Item = Result<transport::Response, (StatusCode, String)>> {
+    async_stream::stream! {
+        let deadline = timeouts.stream_total().map(|total| Instant::now() + total);
+        let mut done = first.stream_done;
+        yield Ok(first);
+
+        while !done {
+            let idle_deadline = timeouts.idle().map(|idle| Instant::now() + idle);
+            let next_deadline = match (deadline, idle_deadline) {
+                (Some(a), Some(b)) => Some(a.min(b)),
+                (a, b) => a.or(b),
+            };
+
+            let next = match next_deadline {
+                Some(next_deadline) => timeout_at(next_deadline, rx.recv()).await,
+                None => Ok(rx.recv().await),
+            };
 
-        Ok(Json(body).into_response())
+            match next {
+                Ok(Some(response)) => {
+                    done = response.stream_done;
+                    yield Ok(response);
+                }
+                Ok(None) => break,
+                Err(_) => {
+                    let message = if deadline == next_deadline {
+                        format!("Stream exceeded its {}s deadline", timeouts.stream_total)
+                    } else {
+                        format!("Provider sent nothing for {}s", timeouts.idle)
+                    };
+                    yield Err((StatusCode::GATEWAY_TIMEOUT, message));
+                    break;
+                }
+            }
+        }
     }
 }
+
+/// An OpenAI-style error object sent as the last event of a failed stream
+fn error_event(status: StatusCode, message: &str) -> String {
+    json!({
+        "error": {
+            "message": message,
+            "type": "timeout",
+            "code": status.as_u16(),
+        }
+    })
+    .to_string()
+}
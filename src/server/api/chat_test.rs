@@ -0,0 +1,130 @@
+use std::{collections::HashMap, env, sync::Arc, time::Duration};
+
+use axum::http::StatusCode;
+use serde_json::{Value, json};
+use solana_sdk::signature::Keypair;
+
+use super::chat::*;
+use crate::{
+    config::{ConfigReloader, ConfigSource, NodeConfig},
+    core::{
+        completion::TokenUsage, health::Health, keys::MetadataV2, router::Router,
+        transport::Response,
+    },
+    db::StateDb,
+    router::local::LocalRouter,
+    server::{ApiState, ServiceContext, Shutdown},
+    test_utils::{metadata_v2, sign_v2},
+};
+
+/// API state of a node with a single provider, which streams every completion without a
+/// usage block and waits `last_chunk_delay` before its last chunk
+async fn streaming_provider(last_chunk_delay: Duration) -> ApiState {
+    let router = Arc::new(LocalRouter::new());
+    tokio::spawn({
+        let router = router.clone();
+        async move { router.run().await }
+    });
+
+    let mut connection = router
+        .register_service("provider".to_string())
+        .await
+        .unwrap();
+    tokio::spawn(async move {
+        while let Ok(request) = connection.recv().await {
+            let chunk = json!({
+                "id": "chatcmpl-1",
+                "object": "chat.completion.chunk",
+                "choices": [{ "index": 0, "delta": { "content": "Hello" }, "finish_reason": "stop" }]
+            });
+            for (payload, stream_done) in [
+                (format!("data: {chunk}\n\n"), false),
+                ("data: [DONE]\n\n".to_string(), true),
+            ] {
+                if stream_done {
+                    tokio::time::sleep(last_chunk_delay).await;
+                }
+                let response = Response {
+                    request_id: request.request_id.clone(),
+                    status_code: 200,
+                    content_type: "text/event-stream".to_string(),
+                    payload,
+                    headers: HashMap::from([(
+                        "content-type".to_string(),
+                        "text/event-stream".to_string(),
+                    )]),
+                    is_stream_chunk: true,
+                    stream_done,
+                };
+                connection.tx.send(response).await.unwrap();
+            }
+        }
+    });
+
+    let reloader = Arc::new(ConfigReloader::new(
+        ConfigSource::default(),
+        NodeConfig::default(),
+    ));
+    let directory = env::temp_dir().join(format!("aimo-{}", uuid::Uuid::new_v4()));
+    ApiState::new(
+        ServiceContext::new(router),
+        Arc::new(StateDb::load_or_create(&directory).unwrap()),
+        reloader,
+        Shutdown::new(),
+        Health::default(),
+    )
+}
+
+#[tokio::test]
+async fn test_assembled_stream_usage() {
+    let state = streaming_provider(Duration::ZERO).await;
+    let key = sign_v2(
+        &Keypair::new(),
+        MetadataV2 {
+            usage_limit: 1,
+            ..metadata_v2()
+        },
+    );
+    let messages = json!([{ "role": "user", "content": "Hi" }]);
+    let body = json!({ "model": "provider:gpt-4o", "stream": false, "messages": messages });
+
+    // The provider streams anyway, and its events are assembled into one completion
+    let Ok(CompletionOutput::Complete(completion)) =
+        dispatch_completion(&state, &key, body.clone()).await
+    else {
+        panic!("Expected a complete response");
+    };
+    assert_eq!(completion["choices"][0]["message"]["content"], "Hello");
+    assert_eq!(completion.get("usage"), None::<&Value>);
+
+    // Tokens are still charged to the key, which is now past its limit
+    let usage = state.state_db.usage.get(&key).unwrap();
+    assert_eq!(
+        usage.total_tokens,
+        TokenUsage::estimate(&messages, &completion).total_tokens
+    );
+    assert!(usage.total_tokens > 0);
+    let Err((status, _)) = dispatch_completion(&state, &key, body).await else {
+        panic!("Expected the usage limit to be enforced");
+    };
+    assert_eq!(status, StatusCode::PAYMENT_REQUIRED);
+}
+
+#[tokio::test]
+async fn test_assembled_stream_first_byte() {
+    let state = streaming_provider(Duration::from_millis(300)).await;
+    let key = sign_v2(&Keypair::new(), metadata_v2());
+    let body = json!({ "model": "provider:gpt-4o", "stream": false, "messages": [] });
+    assert!(dispatch_completion(&state, &key, body).await.is_ok());
+
+    // The time to the first response doesn't include assembling the rest of the stream
+    let text = state.metrics.encode().unwrap();
+    let first_byte = text
+        .lines()
+        .find_map(|line| {
+            line.strip_prefix("aimo_completion_first_byte_seconds_sum{service=\"provider\"} ")
+        })
+        .and_then(|sum| sum.parse::<f64>().ok())
+        .unwrap();
+    assert!(first_byte < 0.3, "first byte after {first_byte}s");
+}
@@ -0,0 +1,123 @@
+use std::{collections::BTreeMap, time::Duration};
+
+use axum::{
+    Json,
+    extract::State,
+    http::{StatusCode, header},
+    response::{IntoResponse, Response},
+};
+use serde::Serialize;
+
+use crate::{
+    core::health::{SubsystemState, SubsystemStatus},
+    server::api::state::ApiState,
+};
+
+#[derive(Debug, Serialize)]
+pub struct StateDbStatus {
+    pub writable: bool,
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ProvidersStatus {
+    pub connected: usize,
+}
+
+#[derive(Debug, Serialize)]
+pub struct HealthResponse {
+    /// Whether the probe passed
+    pub ok: bool,
+    pub draining: bool,
+    pub subsystems: BTreeMap<&'static str, SubsystemStatus>,
+    pub state_db: StateDbStatus,
+    pub providers: ProvidersStatus,
+}
+
+/// How long a state db write check is reused by probes
+const WRITABLE_CHECK_TTL: Duration = Duration::from_secs(5);
+
+/// Alive unless a subsystem stopped for good. Subsystems being restarted are alive.
+///
+/// GET /healthz
+pub async fn healthz(State(state): State<ApiState>) -> (StatusCode, Json<HealthResponse>) {
+    let report = report(&state).await;
+    let ok = report
+        .subsystems
+        .values()
+        .all(|status| status.state != SubsystemState::Stopped);
+
+    respond(HealthResponse { ok, ..report })
+}
+
+/// Ready when every subsystem is running and the state db is writable. Fails while the
+/// node drains, so no new traffic is sent its way.
+///
+/// GET /readyz
+pub async fn readyz(State(state): State<ApiState>) -> (StatusCode, Json<HealthResponse>) {
+    let report = report(&state).await;
+    let ok = !report.draining && state.health.is_ready() && report.state_db.writable;
+
+    respond(HealthResponse { ok, ..report })
+}
+
+async fn report(state: &ApiState) -> HealthResponse {
+    let state_db = state.state_db.clone();
+    let writable =
+        tokio::task::spawn_blocking(move || state_db.check_writable_within(WRITABLE_CHECK_TTL))
+            .await
+            .map_err(anyhow::Error::from)
+            .and_then(|result| result);
+
+    HealthResponse {
+        ok: false,
+        draining: state.shutdown.is_draining(),
+        subsystems: state.health.subsystems(),
+        state_db: StateDbStatus {
+            writable: writable.is_ok(),
+            error: writable.err().map(|err| err.to_string()),
+        },
+        providers: ProvidersStatus {
+            connected: state.ctx.router.service_count().await,
+        },
+    }
+}
+
+fn respond(response: HealthResponse) -> (StatusCode, Json<HealthResponse>) {
+    let status = if response.ok {
+        StatusCode::OK
+    } else {
+        StatusCode::SERVICE_UNAVAILABLE
+    };
+
+    (status, Json(response))
+}
+
+/// Metrics in the Prometheus text format
+///
+/// GET /metrics
+pub async fn metrics(State(state): State<ApiState>) -> Result<Response, (StatusCode, String)> {
+    let metrics = &state.metrics;
+    metrics
+        .providers_connected
+        .set(state.ctx.router.service_count().await as i64);
+    metrics
+        .router_queue_depth
+        .set(state.ctx.router.queue_depth() as i64);
+
+    let body = metrics.encode().map_err(|err| {
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            format!("Failed to encode metrics: {err}"),
+        )
+    })?;
+
+    Ok((
+        [(
+            header::CONTENT_TYPE,
+            "application/openmetrics-text; version=1.0.0; charset=utf-8",
+        )],
+        body,
+    )
+        .into_response())
+}
@@ -1,19 +1,18 @@
-use std::str::FromStr;
-
-use axum::{Json, extract::State, http::StatusCode};
+use axum::{Extension, Json, extract::State, http::StatusCode};
+use chrono::Utc;
 use serde_json::{Value, json};
-use solana_sdk::{pubkey::Pubkey, signature::Signature};
 
 use crate::{
     core::{
-        keys::{MetadataRawV1, SecretKeyV1},
+        keys::{SecretKeyV2, Wallet},
+        revocation::{RevocationTarget, SignedRevocation},
         state::events::KeyRevocation,
     },
     server::{
         api::state::ApiState,
         types::keys::{
-            GenerateKeyRequest, GenerateKeyResponse, MetadataBytesRequest, RevokeKeyRequest,
-            VerifyKeyRequest, VerifyKeyResponse,
+            GenerateKeyRequest, GenerateKeyResponse, KeyUsageResponse, MetadataBytesRequest,
+            RevokeSignerResponse, VerifyKeyRequest, VerifyKeyResponse,
         },
     },
 };
@@ -22,21 +21,35 @@ use crate::{
 pub async fn metadata_bytes(
     Json(body): Json<MetadataBytesRequest>,
 ) -> Result<Json<Vec<u8>>, (StatusCode, String)> {
-    let metadata = body.metadata;
-    let bytes = MetadataRawV1::try_from(metadata)
-        .map_err(|err| (StatusCode::BAD_REQUEST, err.to_string()))?
-        .into_bytes();
+    let bytes = body
+        .metadata
+        .signing_bytes(body.version)
+        .map_err(|err| (StatusCode::BAD_REQUEST, err.to_string()))?;
 
     Ok(Json(bytes))
 }
 
 /// POST /keys/generate
 pub async fn generate_key(
+    State(state): State<ApiState>,
     Json(body): Json<GenerateKeyRequest>,
 ) -> Result<Json<GenerateKeyResponse>, (StatusCode, String)> {
+    // Keys are encoded with their signed tag, if any
+    let options = state.options();
+    let tag = match &body.payload.metadata.tag {
+        Some(tag) if !options.keys.accepts_tag(tag) => {
+            return Err((
+                StatusCode::BAD_REQUEST,
+                format!("Key tag `{tag}` isn't accepted by this node"),
+            ));
+        }
+        Some(tag) => tag.clone(),
+        None => options.keys.default_tag().to_string(),
+    };
+
     let sk_encoded = body
         .payload
-        .into_string("dev")
+        .into_string(&tag)
         .map_err(|err| (StatusCode::BAD_REQUEST, err.to_string()))?;
 
     Ok(Json(GenerateKeyResponse {
@@ -46,17 +59,17 @@ pub async fn generate_key(
 
 /// POST /keys/verify
 pub async fn verify_key(
+    State(state): State<ApiState>,
     Json(body): Json<VerifyKeyRequest>,
 ) -> Result<Json<VerifyKeyResponse>, (StatusCode, String)> {
-    let (scope, payload) = SecretKeyV1::decode(&body.secret_key)
+    let (scope, payload) = SecretKeyV2::decode(&body.secret_key)
         .map_err(|err| (StatusCode::BAD_REQUEST, err.to_string()))?;
 
-    if scope != "dev" {
-        return Err((
-            StatusCode::BAD_REQUEST,
-            format!("Scope {scope} not supported"),
-        ));
-    }
+    state
+        .options()
+        .keys
+        .check_tag(&scope, payload.metadata.tag.as_deref())
+        .map_err(|err| (StatusCode::BAD_REQUEST, err.to_string()))?;
 
     let result = payload.verify_signature();
 
@@ -67,51 +80,189 @@ pub async fn verify_key(
     }))
 }
 
+/// Check a signed revocation is meant for this node and fresh, is signed by `signer` of
+/// `wallet`, and record its nonce
+fn accept_revocation(
+    state: &ApiState,
+    body: &SignedRevocation,
+    wallet: Wallet,
+    signer: &str,
+) -> Result<(), (StatusCode, String)> {
+    let message = &body.message;
+    let options = state.options();
+    let window_ms = options.keys.revocation_window as i64 * 1000;
+    message
+        .check(
+            &options.keys.network_id,
+            Utc::now().timestamp_millis(),
+            window_ms,
+        )
+        .map_err(|err| (StatusCode::BAD_REQUEST, err.to_string()))?;
+
+    let is_valid = body.verify(wallet).map_err(|_| {
+        (
+            StatusCode::BAD_REQUEST,
+            "Invalid signer or signature format".to_string(),
+        )
+    })?;
+    if !is_valid {
+        return Err((StatusCode::UNAUTHORIZED, "Wrong signature".to_string()));
+    }
+
+    if !wallet.is_same_signer(&body.signer, signer) {
+        return Err((
+            StatusCode::UNAUTHORIZED,
+            "Request signer is different from secret key signer".to_string(),
+        ));
+    }
+
+    let fresh = state
+        .state_db
+        .revocation
+        .use_nonce(signer, message, window_ms)
+        .map_err(|err| {
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                format!("Failed to record revocation nonce: {err}"),
+            )
+        })?;
+    if !fresh {
+        return Err((
+            StatusCode::CONFLICT,
+            "Revocation nonce already used".to_string(),
+        ));
+    }
+
+    Ok(())
+}
+
 /// POST /keys/revoke
-#[axum::debug_handler]
+///
+/// Takes a revocation message signed by the key's wallet. Messages for other networks,
+/// outside the node's revocation window or with a used nonce are rejected.
 pub async fn revoke_key(
-    // Extension(payload): Extension<SecretKeyV1>,
-    State(ApiState { state_db, .. }): State<ApiState>,
-    Json(body): Json<RevokeKeyRequest>,
+    State(state): State<ApiState>,
+    Json(body): Json<SignedRevocation>,
 ) -> Result<Json<Value>, (StatusCode, String)> {
-    let signer = Pubkey::from_str(&body.signer)
-        .map_err(|_| (StatusCode::BAD_REQUEST, "Invalid signer".to_string()))?;
-    let signature = Signature::from_str(&body.signature).map_err(|_| {
+    let ApiState {
+        state_db,
+        key_cache,
+        metrics,
+        ..
+    } = &state;
+
+    let RevocationTarget::Key { secret_key } = &body.message.target else {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            "Expected a secret key to revoke".to_string(),
+        ));
+    };
+    let (_, payload) = SecretKeyV2::decode(secret_key).map_err(|err| {
         (
             StatusCode::BAD_REQUEST,
-            "Invalid signature format".to_string(),
+            format!("Invalid secret key: {err}"),
         )
     })?;
-    if !signature.verify(&signer.to_bytes(), body.secret_key.as_bytes()) {
-        return Err((StatusCode::UNAUTHORIZED, "Wrong signature".to_string()));
-    }
 
-    let (_, payload) = SecretKeyV1::decode(&body.secret_key).map_err(|err| {
+    // The request is signed with the key's wallet
+    accept_revocation(&state, &body, payload.wallet, &payload.signer)?;
+
+    let hash = payload.into_hash().map_err(|err| {
         (
             StatusCode::BAD_REQUEST,
             format!("Invalid secret key: {err}"),
         )
     })?;
+    let event = KeyRevocation {
+        key: secret_key.clone(),
+    };
 
-    if body.signer != payload.signer {
+    state_db.revocation.revoke_key(event).map_err(|err| {
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            format!("Failed to revoke key internally: {err}"),
+        )
+    })?;
+
+    // Cached keys delegated from it are dropped too
+    key_cache.invalidate(&hash);
+    metrics.key_cache_entries.set(key_cache.len() as i64);
+
+    Ok(Json(json!({})))
+}
+
+/// POST /keys/revoke_signer
+///
+/// Revoke every key the request signer created before `issued_before`, along with the keys
+/// delegated from them. Watermarks only move forward; lowering one takes an admin.
+///
+/// Requests are authenticated with a key of the signer with the `revoke` scope.
+pub async fn revoke_signer(
+    Extension(payload): Extension<SecretKeyV2>,
+    State(state): State<ApiState>,
+    Json(body): Json<SignedRevocation>,
+) -> Result<Json<RevokeSignerResponse>, (StatusCode, String)> {
+    let RevocationTarget::Signer { issued_before } = body.message.target else {
         return Err((
-            StatusCode::UNAUTHORIZED,
-            "Request signer is different from secret key signer".to_string(),
+            StatusCode::BAD_REQUEST,
+            "Expected a time to revoke keys issued before".to_string(),
         ));
-    }
-
-    let event = KeyRevocation {
-        key: body.secret_key,
     };
 
-    state_db
-        .revocation
-        .revoke_key(event)
-        .map(|_| Json(json!({})))
+    let wallet = Wallet::of_signer(&body.signer);
+    if !payload
+        .chain()
+        .any(|key| key.wallet == wallet && wallet.is_same_signer(&key.signer, &body.signer))
+    {
+        return Err((
+            StatusCode::FORBIDDEN,
+            "Secret key isn't issued by the revoking signer".to_string(),
+        ));
+    }
+    accept_revocation(&state, &body, wallet, &body.signer)?;
+
+    let issued_before = state
+        .state_db
+        .watermarks
+        .raise(wallet, &body.signer, issued_before)
         .map_err(|err| {
             (
                 StatusCode::INTERNAL_SERVER_ERROR,
-                format!("Failed to revoke key internally: {err}"),
+                format!("Failed to revoke signer keys internally: {err}"),
             )
-        })
+        })?;
+
+    // Any cached key may be delegated from the signer
+    state.key_cache.clear();
+    state.metrics.key_cache_entries.set(0);
+    tracing::info!(
+        "Revoked keys of {} issued before {issued_before}",
+        body.signer
+    );
+
+    Ok(Json(RevokeSignerResponse { issued_before }))
+}
+
+/// Usage counted against the requesting secret key
+///
+/// GET /keys/usage
+pub async fn key_usage(
+    Extension(payload): Extension<SecretKeyV2>,
+    State(ApiState { state_db, .. }): State<ApiState>,
+) -> Result<Json<KeyUsageResponse>, (StatusCode, String)> {
+    let usage = state_db.usage.get(&payload).map_err(|err| {
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            format!("Failed to read key usage: {err}"),
+        )
+    })?;
+
+    let usage_limit = payload.metadata.usage_limit;
+    let remaining = (usage_limit > 0).then(|| usage_limit.saturating_sub(usage.total_tokens));
+
+    Ok(Json(KeyUsageResponse {
+        usage,
+        usage_limit,
+        remaining,
+    }))
 }
@@ -1,7 +1,13 @@
-mod chat;
+mod admin;
+pub mod chat;
+mod health;
 mod keys;
 mod routes;
 pub mod state;
 mod subscribe;
+mod ws;
 
 pub use routes::*;
+
+#[cfg(test)]
+mod chat_test;
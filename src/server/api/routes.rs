@@ -1,50 +1,150 @@
-use std::sync::Arc;
-
 use axum::{
-    Router, middleware,
-    routing::{any, get, post},
+    Router,
+    extract::Request,
+    middleware,
+    routing::{any, get, post, put},
 };
-use tower::ServiceBuilder;
 use tower_http::trace::TraceLayer;
 
 use crate::{
-    config::ServerOptions,
-    db::StateDb,
+    config::{RouteGroup, ServerOptions},
+    core::keys::Scope,
     server::{
         api::{
-            chat::completions,
-            keys::{generate_key, metadata_bytes, revoke_key, verify_key},
-            subscribe,
+            admin::{
+                clear_global_watermark, clear_signer_watermark, list_watermarks, reload_config,
+                set_global_watermark, set_signer_watermark, state_stats,
+            },
+            chat::{completions, resume},
+            health::{healthz, metrics, readyz},
+            keys::{
+                generate_key, key_usage, metadata_bytes, revoke_key, revoke_signer, verify_key,
+            },
+            subscribe, ws,
+        },
+        middleware::{
+            AuthState, LiveLayer, admin_layer, auth_layer, cors_layer, drain_layer,
+            rate_limit_layer, timeout_layer,
         },
-        context::ServiceContext,
-        middleware::{auth_layer, cors_layer, timeout_layer},
     },
+    telemetry,
 };
 
 use super::state::ApiState;
 
-pub fn api_v1(options: &ServerOptions, ctx: ServiceContext, state_db: Arc<StateDb>) -> Router {
-    let state = ApiState::new(ctx, state_db);
-    Router::new()
+pub fn api_v1(state: ApiState) -> Router {
+    let config = state.config.clone();
+
+    // Policies are read from the running options on every request, so they follow reloads
+    let timeout = || LiveLayer::new(config.clone(), timeout_layer);
+
+    // Routes declare the scopes a secret key needs to access them
+    let auth = |scopes: &'static [Scope]| {
+        middleware::from_fn_with_state(AuthState::new(state.clone(), scopes), auth_layer)
+    };
+    let cors = |group: Option<RouteGroup>| {
+        LiveLayer::new(config.clone(), move |options: &ServerOptions| {
+            cors_layer(options, group)
+        })
+    };
+
+    // Routes answering with a single response are timed out as a whole, streams time out
+    // between chunks instead, and provider sockets never do
+    let ping = Router::new()
         .route("/ping", get(|| async { "pong" }))
+        .layer(timeout())
+        .layer(cors(None));
+
+    let keys = Router::new()
         .route("/keys/metadata_bytes", get(metadata_bytes))
         .route("/keys/generate", post(generate_key))
         .route("/keys/verify", post(verify_key))
         .route("/keys/revoke", post(revoke_key))
+        .route(
+            "/keys/revoke_signer",
+            post(revoke_signer).layer(auth(&[Scope::Revoke])),
+        )
+        .route("/keys/usage", get(key_usage).layer(auth(&[])))
+        .layer(timeout())
+        .layer(cors(Some(RouteGroup::Keys)));
+
+    let chat = Router::new()
         .route(
             "/chat/completions",
-            post(completions).layer(middleware::from_fn_with_state(state.clone(), auth_layer)),
+            post(completions)
+                .layer(middleware::from_fn_with_state(
+                    state.clone(),
+                    rate_limit_layer,
+                ))
+                .layer(auth(&[Scope::CompletionModel])),
         )
+        .route(
+            "/chat/completions/resume",
+            get(resume)
+                .layer(middleware::from_fn_with_state(
+                    state.clone(),
+                    rate_limit_layer,
+                ))
+                .layer(auth(&[Scope::CompletionModel])),
+        )
+        .route(
+            "/ws",
+            any(ws::handler).layer(auth(&[Scope::CompletionModel])),
+        )
+        .layer(cors(Some(RouteGroup::Chat)));
+
+    let providers = Router::new()
         .route(
             "/providers/subscribe",
-            any(subscribe::handler)
-                .layer(middleware::from_fn_with_state(state.clone(), auth_layer)),
+            any(subscribe::handler).layer(auth(&[Scope::Provider])),
         )
-        .with_state(state)
-        .layer(
-            ServiceBuilder::new()
-                .layer(TraceLayer::new_for_http())
-                .layer(cors_layer(&options))
-                .layer(timeout_layer(&options)),
+        .layer(cors(Some(RouteGroup::Providers)));
+
+    let admin = Router::new()
+        .route("/admin/config/reload", post(reload_config))
+        .route("/admin/state/stats", get(state_stats))
+        .route("/admin/watermarks", get(list_watermarks))
+        .route(
+            "/admin/watermarks/global",
+            put(set_global_watermark).delete(clear_global_watermark),
         )
+        .route(
+            "/admin/watermarks/signers/{signer}",
+            put(set_signer_watermark).delete(clear_signer_watermark),
+        )
+        .layer(auth(&[Scope::Admin]))
+        .layer(middleware::from_fn_with_state(state.clone(), admin_layer))
+        .layer(timeout())
+        .layer(cors(Some(RouteGroup::Admin)));
+
+    Router::new()
+        .merge(ping)
+        .merge(keys)
+        .merge(chat)
+        .merge(providers)
+        .merge(admin)
+        .layer(middleware::from_fn_with_state(state.clone(), drain_layer))
+        .with_state(state)
+        .layer(TraceLayer::new_for_http().make_span_with(|req: &Request| {
+            let span = tracing::info_span!(
+                "request",
+                method = %req.method(),
+                uri = %req.uri(),
+                otel.kind = "server",
+                request_id = tracing::field::Empty,
+                service_id = tracing::field::Empty,
+                signer = tracing::field::Empty,
+            );
+            telemetry::set_parent_from_headers(&span, req.headers());
+            span
+        }))
+}
+
+/// Probes and metrics of orchestrators, served outside of the versioned API
+pub fn probes(state: ApiState) -> Router {
+    Router::new()
+        .route("/healthz", get(healthz))
+        .route("/readyz", get(readyz))
+        .route("/metrics", get(metrics))
+        .with_state(state)
 }
@@ -1,15 +1,85 @@
-use std::sync::Arc;
+use std::{sync::Arc, time::Duration};
 
-use crate::{db::StateDb, server::context::ServiceContext};
+use crate::{
+    config::{ConfigReloader, ServerOptions},
+    core::health::Health,
+    db::StateDb,
+    server::{
+        buffer::{BufferLimits, StreamBuffers},
+        context::ServiceContext,
+        key_cache::KeyCache,
+        limiter::RateLimiter,
+        metrics::Metrics,
+        shutdown::Shutdown,
+    },
+};
 
 #[derive(Clone)]
 pub struct ApiState {
     pub ctx: ServiceContext,
     pub state_db: Arc<StateDb>,
+    pub config: Arc<ConfigReloader>,
+    pub streams: Arc<StreamBuffers>,
+    pub limiter: Arc<RateLimiter>,
+    pub key_cache: Arc<KeyCache>,
+    pub shutdown: Shutdown,
+    pub health: Health,
+    pub metrics: Arc<Metrics>,
 }
 
 impl ApiState {
-    pub fn new(ctx: ServiceContext, state_db: Arc<StateDb>) -> Self {
-        Self { ctx, state_db }
+    pub fn new(
+        ctx: ServiceContext,
+        state_db: Arc<StateDb>,
+        config: Arc<ConfigReloader>,
+        shutdown: Shutdown,
+        health: Health,
+    ) -> Self {
+        let options = config.options();
+        let streams = Arc::new(StreamBuffers::new(
+            Duration::from_secs(options.stream_resume_window),
+            BufferLimits {
+                streams: options.max_buffered_streams,
+                streams_per_owner: options.max_buffered_streams_per_signer,
+                stream_bytes: options.max_buffered_stream_bytes,
+            },
+        ));
+        let limiter = Arc::new(RateLimiter::new(options.rate_limits.clone()));
+        let key_cache = Arc::new(KeyCache::new(options.keys.cache_size));
+
+        // Apply reloaded rate limits and cache size, keeping the current buckets and keys
+        let mut options_rx = config.subscribe();
+        let limiter_weak = Arc::downgrade(&limiter);
+        let key_cache_weak = Arc::downgrade(&key_cache);
+        tokio::spawn(async move {
+            while options_rx.changed().await.is_ok() {
+                // Stop with the state
+                let (Some(limiter), Some(key_cache)) =
+                    (limiter_weak.upgrade(), key_cache_weak.upgrade())
+                else {
+                    break;
+                };
+                let options = options_rx.borrow_and_update().clone();
+                limiter.set_limits(options.rate_limits.clone());
+                key_cache.resize(options.keys.cache_size);
+            }
+        });
+
+        Self {
+            ctx,
+            state_db,
+            config,
+            streams,
+            limiter,
+            key_cache,
+            shutdown,
+            health,
+            metrics: Arc::new(Metrics::new()),
+        }
+    }
+
+    /// The running server options, which may change on reload
+    pub fn options(&self) -> Arc<ServerOptions> {
+        self.config.options()
     }
 }
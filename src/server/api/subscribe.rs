@@ -4,25 +4,36 @@ use axum::{
         State, WebSocketUpgrade,
         ws::{Message, WebSocket},
     },
-    response::Response,
+    http::StatusCode,
+    response::{IntoResponse, Response},
 };
 use futures_util::{sink::SinkExt, stream::StreamExt};
 use tokio::task::JoinSet;
 
 use crate::{
-    core::{keys::SecretKeyV1, transport},
-    server::{ServiceContext, api::state::ApiState},
+    core::{
+        keys::SecretKeyV2,
+        transport::{self, ProviderNotice},
+    },
+    server::api::state::ApiState,
 };
 
 pub async fn handler(
-    Extension(payload): Extension<SecretKeyV1>,
+    Extension(payload): Extension<SecretKeyV2>,
     ws: WebSocketUpgrade,
-    State(ApiState { ctx, .. }): State<ApiState>,
+    State(state): State<ApiState>,
 ) -> Response {
-    ws.on_upgrade(|socket| handle_socket(socket, ctx, payload))
+    // Providers serve as the service id of their signer
+    if let Err(err) = payload.metadata.restrictions.check_service(&payload.signer) {
+        return (StatusCode::FORBIDDEN, err.to_string()).into_response();
+    }
+
+    ws.on_upgrade(|socket| handle_socket(socket, state, payload))
 }
 
-async fn handle_socket(mut socket: WebSocket, ctx: ServiceContext, payload: SecretKeyV1) {
+async fn handle_socket(mut socket: WebSocket, state: ApiState, payload: SecretKeyV2) {
+    let ctx = state.ctx;
+    let shutdown = state.shutdown;
     match ctx.router.register_service(payload.signer.clone()).await {
         Ok(connection) => {
             let (mut ws_sender, mut ws_receiver) = socket.split();
@@ -30,28 +41,46 @@ async fn handle_socket(mut socket: WebSocket, ctx: ServiceContext, payload: Secr
             let mut rx = connection.rx;
             let mut js = JoinSet::new();
 
-            // Forward requests to service provider
+            // Forward requests to service provider, and tell it when the node drains
+            let draining = shutdown.clone();
+            let config = state.config.clone();
             js.spawn(async move {
-                while let Some(request) = rx.recv().await {
-                    if let Ok(msg) = serde_json::to_string(&request) {
-                        if ws_sender.send(Message::Text(msg.into())).await.is_err() {
-                            tracing::warn!("Service provider disconnected");
-                            break;
+                let mut notified = false;
+                loop {
+                    let msg = tokio::select! {
+                        request = rx.recv() => match request {
+                            Some(request) => serde_json::to_string(&request),
+                            None => break,
+                        },
+                        _ = draining.draining(), if !notified => {
+                            notified = true;
+                            serde_json::to_string(&ProviderNotice::Draining {
+                                grace_period: config.options().timeouts.shutdown_grace,
+                            })
                         }
+                    };
+
+                    if let Ok(msg) = msg
+                        && ws_sender.send(Message::Text(msg.into())).await.is_err()
+                    {
+                        tracing::warn!("Service provider disconnected");
+                        break;
                     }
                 }
             });
 
+            // Close the socket once requests in flight are served
+            js.spawn(async move { shutdown.drained().await });
+
             // Client to router
             js.spawn(async move {
                 while let Some(Ok(Message::Text(text))) = ws_receiver.next().await {
                     let str = text.to_string();
                     if let Ok(response) = serde_json::from_str::<transport::Response>(&str)
                         .inspect_err(|err| tracing::debug!("Failed to deserialize response: {err}"))
+                        && tx.send(response).await.is_err()
                     {
-                        if let Err(_) = tx.send(response).await {
-                            tracing::info!("Connection closed");
-                        }
+                        tracing::info!("Connection closed");
                     }
                 }
             });
@@ -0,0 +1,239 @@
+use std::collections::HashMap;
+
+use axum::{
+    Extension,
+    extract::{
+        State, WebSocketUpgrade,
+        ws::{Message, WebSocket},
+    },
+    http::StatusCode,
+    response::Response,
+};
+use futures_util::{sink::SinkExt, stream::StreamExt};
+use serde_json::Value;
+use tokio::{sync::mpsc, task::AbortHandle};
+use tracing::Instrument;
+
+use crate::{
+    core::{completion::DONE_MARKER, keys::SecretKeyV2},
+    server::{
+        api::{
+            chat::{CompletionOutput, completion_target, dispatch_completion},
+            state::ApiState,
+        },
+        limiter::request_subjects,
+        types::ws::{ClientMessage, ServerMessage},
+    },
+};
+
+/// Multiplexed chat completions over a single WebSocket
+///
+/// GET /ws
+pub async fn handler(
+    Extension(payload): Extension<SecretKeyV2>,
+    ws: WebSocketUpgrade,
+    State(state): State<ApiState>,
+) -> Response {
+    ws.on_upgrade(|socket| handle_socket(socket, state, payload))
+}
+
+async fn handle_socket(socket: WebSocket, state: ApiState, payload: SecretKeyV2) {
+    let (mut ws_sender, mut ws_receiver) = socket.split();
+    let (message_tx, mut message_rx) = mpsc::channel::<ServerMessage>(64);
+
+    // Interleave messages of all requests onto the socket
+    let sender_task = tokio::spawn(async move {
+        while let Some(message) = message_rx.recv().await {
+            if let Ok(msg) = serde_json::to_string(&message)
+                && ws_sender.send(Message::Text(msg.into())).await.is_err()
+            {
+                tracing::info!("Client disconnected");
+                break;
+            }
+        }
+    });
+
+    let mut in_flight: HashMap<String, AbortHandle> = HashMap::new();
+
+    loop {
+        let message = tokio::select! {
+            message = ws_receiver.next() => message,
+            // Close the socket once requests in flight are served
+            _ = state.shutdown.drained() => break,
+        };
+        let Some(Ok(message)) = message else {
+            break;
+        };
+
+        let text = match message {
+            Message::Text(text) => text,
+            Message::Close(_) => break,
+            _ => continue,
+        };
+
+        in_flight.retain(|_, handle| !handle.is_finished());
+
+        match serde_json::from_str::<ClientMessage>(&text) {
+            Ok(ClientMessage::Request { id, body }) => {
+                if in_flight.contains_key(&id) {
+                    let _ = message_tx
+                        .send(error_message(
+                            Some(id),
+                            StatusCode::CONFLICT,
+                            "Request id already in flight".to_string(),
+                        ))
+                        .await;
+                    continue;
+                }
+
+                let span = tracing::info_span!(
+                    "ws_request",
+                    id = %id,
+                    signer = %payload.signer_fingerprint(),
+                    request_id = tracing::field::Empty,
+                    service_id = tracing::field::Empty,
+                );
+                let handle = tokio::spawn(
+                    run_request(
+                        state.clone(),
+                        payload.clone(),
+                        id.clone(),
+                        body,
+                        message_tx.clone(),
+                    )
+                    .instrument(span),
+                )
+                .abort_handle();
+                in_flight.insert(id, handle);
+            }
+            Ok(ClientMessage::Cancel { id }) => {
+                if let Some(handle) = in_flight.remove(&id) {
+                    handle.abort();
+                    tracing::debug!("Request {id} cancelled by client");
+                    let _ = message_tx.send(ServerMessage::Done { id }).await;
+                }
+            }
+            Err(err) => {
+                let _ = message_tx
+                    .send(error_message(
+                        None,
+                        StatusCode::BAD_REQUEST,
+                        format!("Invalid message: {err}"),
+                    ))
+                    .await;
+            }
+        }
+    }
+
+    for handle in in_flight.into_values() {
+        handle.abort();
+    }
+    sender_task.abort();
+}
+
+async fn run_request(
+    state: ApiState,
+    payload: SecretKeyV2,
+    id: String,
+    body: Value,
+    message_tx: mpsc::Sender<ServerMessage>,
+) {
+    let Some(_in_flight) = state.shutdown.track() else {
+        let _ = message_tx
+            .send(error_message(
+                Some(id),
+                StatusCode::SERVICE_UNAVAILABLE,
+                "Node is shutting down".to_string(),
+            ))
+            .await;
+        return;
+    };
+
+    // Each request over the socket is limited like a `POST /chat/completions`
+    let _permit = match state.limiter.acquire(
+        request_subjects(&payload, completion_target(&body)),
+        payload.metadata.restrictions.max_streams,
+    ) {
+        Ok(permit) => permit,
+        Err(limited) => {
+            let message = format!(
+                "{}, retry after {}s",
+                limited.reason,
+                limited.retry_after.as_secs_f64().ceil()
+            );
+            let _ = message_tx
+                .send(error_message(
+                    Some(id),
+                    StatusCode::TOO_MANY_REQUESTS,
+                    message,
+                ))
+                .await;
+            return;
+        }
+    };
+
+    match dispatch_completion(&state, &payload, body).await {
+        Ok(CompletionOutput::Stream(mut data)) => {
+            while let Some(data) = data.next().await {
+                if data.trim() == DONE_MARKER {
+                    continue;
+                }
+
+                let data = serde_json::from_str::<Value>(&data).unwrap_or(Value::String(data));
+
+                // Streams failing midway end with an error object
+                if let Some(error) = data.get("error") {
+                    let status = error
+                        .get("code")
+                        .and_then(Value::as_u64)
+                        .and_then(|code| StatusCode::from_u16(code as u16).ok())
+                        .unwrap_or(StatusCode::BAD_GATEWAY);
+                    let message = error
+                        .get("message")
+                        .and_then(Value::as_str)
+                        .unwrap_or("Stream failed")
+                        .to_string();
+                    let _ = message_tx
+                        .send(error_message(Some(id), status, message))
+                        .await;
+                    return;
+                }
+
+                if message_tx
+                    .send(ServerMessage::Chunk {
+                        id: id.clone(),
+                        data,
+                    })
+                    .await
+                    .is_err()
+                {
+                    return;
+                }
+            }
+        }
+        Ok(CompletionOutput::Complete(body)) => {
+            let _ = message_tx
+                .send(ServerMessage::Response {
+                    id: id.clone(),
+                    body,
+                })
+                .await;
+        }
+        Err((status, message)) => {
+            let _ = message_tx
+                .send(error_message(Some(id), status, message))
+                .await;
+            return;
+        }
+    }
+
+    let _ = message_tx.send(ServerMessage::Done { id }).await;
+}
+
+fn error_message(id: Option<String>, status: StatusCode, message: String) -> ServerMessage {
+    ServerMessage::Error {
+        id,
+        status: status.as_u16(),
+        message,
+    }
+}
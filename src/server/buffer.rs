@@ -0,0 +1,222 @@
+//! Buffers of streamed completions, kept around so that clients losing their connection
+//! can pick the stream up again with `Last-Event-ID`
+
+use std::{
+    collections::{HashMap, VecDeque},
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use axum::http::StatusCode;
+use futures_util::stream::{BoxStream, StreamExt};
+use tokio::sync::Notify;
+use uuid::Uuid;
+
+use crate::server::limiter::RateLimitPermit;
+
+/// Caps on the memory held by stream buffers
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BufferLimits {
+    /// Streams buffered at once over all signers
+    pub streams: usize,
+    /// Streams buffered at once per signer
+    pub streams_per_owner: usize,
+    /// Bytes of chunks kept per stream. The oldest chunks are dropped past it.
+    pub stream_bytes: usize,
+}
+
+#[derive(Default)]
+struct BufferState {
+    chunks: VecDeque<String>,
+    /// Index of the first chunk still buffered
+    first: usize,
+    bytes: usize,
+    finished_at: Option<Instant>,
+}
+
+impl BufferState {
+    fn push(&mut self, chunk: String, max_bytes: usize) {
+        self.bytes += chunk.len();
+        self.chunks.push_back(chunk);
+        // Always keep the latest chunk, so live subscribers get it
+        while self.bytes > max_bytes && self.chunks.len() > 1 {
+            let dropped = self.chunks.pop_front().unwrap();
+            self.bytes -= dropped.len();
+            self.first += 1;
+        }
+    }
+}
+
+struct BufferedStream {
+    /// Signer of the secret key that started the stream
+    owner: String,
+    state: Mutex<BufferState>,
+    notify: Notify,
+}
+
+pub struct StreamBuffers {
+    /// How long a stream is kept after the provider finishes it
+    window: Duration,
+    limits: BufferLimits,
+    streams: Mutex<HashMap<String, Arc<BufferedStream>>>,
+}
+
+impl StreamBuffers {
+    pub fn new(window: Duration, limits: BufferLimits) -> Self {
+        Self {
+            window,
+            limits,
+            streams: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Drive `data` into a new buffer in the background and return the stream id.
+    ///
+    /// The provider stream is consumed to the end whether or not a client is listening,
+    /// holding `permit` until then.
+    ///
+    /// When a stream cap is reached, the oldest finished stream under it is evicted. If
+    /// all of them are still live, `data` is dropped and the stream rejected.
+    pub fn buffer(
+        self: &Arc<Self>,
+        owner: String,
+        mut data: BoxStream<'static, String>,
+        permit: Option<RateLimitPermit>,
+    ) -> Result<String, (StatusCode, String)> {
+        let stream_id = Uuid::new_v4().simple().to_string();
+        let stream = Arc::new(BufferedStream {
+            owner,
+            state: Mutex::new(BufferState::default()),
+            notify: Notify::new(),
+        });
+
+        {
+            let mut streams = self.streams.lock().unwrap();
+            let owned = streams
+                .values()
+                .filter(|buffered| buffered.owner == stream.owner)
+                .count();
+            if owned >= self.limits.streams_per_owner
+                && !evict_oldest_finished(&mut streams, Some(&stream.owner))
+            {
+                return Err((
+                    StatusCode::TOO_MANY_REQUESTS,
+                    "Too many buffered streams for this signer".to_string(),
+                ));
+            }
+
+            if streams.len() >= self.limits.streams && !evict_oldest_finished(&mut streams, None) {
+                return Err((
+                    StatusCode::SERVICE_UNAVAILABLE,
+                    "Too many buffered streams".to_string(),
+                ));
+            }
+
+            streams.insert(stream_id.clone(), stream.clone());
+        }
+
+        let buffers = self.clone();
+        let id = stream_id.clone();
+        tokio::spawn(async move {
+            while let Some(chunk) = data.next().await {
+                stream
+                    .state
+                    .lock()
+                    .unwrap()
+                    .push(chunk, buffers.limits.stream_bytes);
+                stream.notify.notify_waiters();
+            }
+            stream.state.lock().unwrap().finished_at = Some(Instant::now());
+            stream.notify.notify_waiters();
+            drop(permit);
+
+            tokio::time::sleep(buffers.window).await;
+            buffers.streams.lock().unwrap().remove(&id);
+            tracing::debug!("Stream buffer {id} expired");
+        });
+
+        Ok(stream_id)
+    }
+
+    /// Subscribe to a buffered stream starting at chunk index `from`, replaying buffered
+    /// chunks first and then following the live stream.
+    ///
+    /// Returns `None` if the stream doesn't exist, has expired, or belongs to another signer,
+    /// or if chunk `from` was already dropped. The stream ends early if the subscriber falls
+    /// behind the chunks still buffered.
+    pub fn subscribe(
+        &self,
+        stream_id: &str,
+        owner: &str,
+        from: usize,
+    ) -> Option<BoxStream<'static, (usize, String)>> {
+        let stream = self.streams.lock().unwrap().get(stream_id)?.clone();
+        if stream.owner != owner || from < stream.state.lock().unwrap().first {
+            return None;
+        }
+
+        let chunks = async_stream::stream! {
+            let mut next = from;
+            loop {
+                // Register for notification before reading, so no chunk is missed in between
+                let notified = stream.notify.notified();
+                let (chunks, finished) = {
+                    let state = stream.state.lock().unwrap();
+                    let Some(skip) = next.checked_sub(state.first) else {
+                        break;
+                    };
+                    (
+                        state.chunks.iter().skip(skip).cloned().collect::<Vec<_>>(),
+                        state.finished_at.is_some(),
+                    )
+                };
+
+                for chunk in chunks {
+                    yield (next, chunk);
+                    next += 1;
+                }
+
+                if finished {
+                    break;
+                }
+                notified.await;
+            }
+        };
+
+        Some(chunks.boxed())
+    }
+}
+
+/// Remove the stream which finished first, among those of `owner` if given. Returns
+/// whether one was removed.
+fn evict_oldest_finished(
+    streams: &mut HashMap<String, Arc<BufferedStream>>,
+    owner: Option<&str>,
+) -> bool {
+    let oldest = streams
+        .iter()
+        .filter(|(_, stream)| owner.is_none_or(|owner| stream.owner == owner))
+        .filter_map(|(id, stream)| Some((stream.state.lock().unwrap().finished_at?, id)))
+        .min()
+        .map(|(_, id)| id.clone());
+
+    match oldest {
+        Some(id) => {
+            streams.remove(&id);
+            tracing::debug!("Stream buffer {id} evicted");
+            true
+        }
+        None => false,
+    }
+}
+
+/// Event id of the chunk at `index` of a buffered stream
+pub fn event_id(stream_id: &str, index: usize) -> String {
+    format!("{stream_id}:{index}")
+}
+
+/// Parse a `Last-Event-ID` into the stream id and the index of the next chunk to send
+pub fn parse_last_event_id(last_event_id: &str) -> Option<(&str, usize)> {
+    let (stream_id, index) = last_event_id.rsplit_once(':')?;
+    Some((stream_id, index.parse::<usize>().ok()?.checked_add(1)?))
+}
@@ -0,0 +1,122 @@
+use std::{sync::Arc, time::Duration};
+
+use axum::http::StatusCode;
+
+use futures_util::{
+    StreamExt,
+    stream::{self, BoxStream},
+};
+
+use super::buffer::*;
+
+const LIMITS: BufferLimits = BufferLimits {
+    streams: 100,
+    streams_per_owner: 100,
+    stream_bytes: 1024,
+};
+
+fn chunks(chunks: &[&str]) -> BoxStream<'static, String> {
+    stream::iter(
+        chunks
+            .iter()
+            .map(|chunk| chunk.to_string())
+            .collect::<Vec<_>>(),
+    )
+    .boxed()
+}
+
+#[tokio::test]
+async fn test_resume_buffered_stream() {
+    let buffers = Arc::new(StreamBuffers::new(Duration::from_secs(60), LIMITS));
+    let stream_id = buffers
+        .buffer("signer".to_string(), chunks(&["a", "b", "c"]), None)
+        .unwrap();
+
+    let all = buffers
+        .subscribe(&stream_id, "signer", 0)
+        .unwrap()
+        .collect::<Vec<_>>()
+        .await;
+    assert_eq!(all.len(), 3);
+
+    // Reconnect after receiving the first event
+    let (id, from) = parse_last_event_id(&event_id(&stream_id, all[0].0))
+        .map(|(id, from)| (id.to_string(), from))
+        .unwrap();
+    let rest = buffers
+        .subscribe(&id, "signer", from)
+        .unwrap()
+        .map(|(_, chunk)| chunk)
+        .collect::<Vec<_>>()
+        .await;
+    assert_eq!(rest, vec!["b", "c"]);
+
+    // Out of range event indices are ignored
+    assert!(parse_last_event_id(&format!("{stream_id}:{}", usize::MAX)).is_none());
+
+    // Streams can't be resumed by other signers
+    assert!(buffers.subscribe(&stream_id, "someone else", 0).is_none());
+}
+
+#[tokio::test]
+async fn test_buffer_expiry() {
+    let buffers = Arc::new(StreamBuffers::new(Duration::from_millis(10), LIMITS));
+    let stream_id = buffers
+        .buffer("signer".to_string(), stream::empty().boxed(), None)
+        .unwrap();
+
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    assert!(buffers.subscribe(&stream_id, "signer", 0).is_none());
+}
+
+#[tokio::test]
+async fn test_stream_limits() {
+    let limits = BufferLimits {
+        streams: 3,
+        streams_per_owner: 2,
+        ..LIMITS
+    };
+    let buffers = Arc::new(StreamBuffers::new(Duration::from_secs(60), limits));
+    let live = || stream::pending().boxed();
+
+    // Signers can't buffer more live streams than their share
+    let first = buffers.buffer("a".to_string(), live(), None).unwrap();
+    buffers.buffer("a".to_string(), live(), None).unwrap();
+    let (status, _) = buffers.buffer("a".to_string(), live(), None).unwrap_err();
+    assert_eq!(status, StatusCode::TOO_MANY_REQUESTS);
+
+    // Finished streams make room for new ones
+    let finished = buffers
+        .buffer("b".to_string(), stream::empty().boxed(), None)
+        .unwrap();
+    tokio::time::sleep(Duration::from_millis(10)).await;
+    buffers.buffer("c".to_string(), live(), None).unwrap();
+    assert!(buffers.subscribe(&finished, "b", 0).is_none());
+
+    // Live ones are kept, so new streams are rejected once all signers hit the cap
+    let (status, _) = buffers.buffer("c".to_string(), live(), None).unwrap_err();
+    assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+    assert!(buffers.subscribe(&first, "a", 0).is_some());
+}
+
+#[tokio::test]
+async fn test_stream_bytes_limit() {
+    let limits = BufferLimits {
+        stream_bytes: 4,
+        ..LIMITS
+    };
+    let buffers = Arc::new(StreamBuffers::new(Duration::from_secs(60), limits));
+    let stream_id = buffers
+        .buffer("signer".to_string(), chunks(&["ab", "cd", "ef"]), None)
+        .unwrap();
+    tokio::time::sleep(Duration::from_millis(10)).await;
+
+    // The oldest chunks are dropped, so the stream can only be resumed from later ones
+    assert!(buffers.subscribe(&stream_id, "signer", 0).is_none());
+    let rest = buffers
+        .subscribe(&stream_id, "signer", 1)
+        .unwrap()
+        .collect::<Vec<_>>()
+        .await;
+    assert_eq!(rest, vec![(1, "cd".to_string()), (2, "ef".to_string())]);
+}
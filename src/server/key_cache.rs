@@ -0,0 +1,140 @@
+//! Cache of verified secret keys
+//!
+//! Authenticating a key decodes it, verifies the signatures of its whole delegation chain
+//! and looks up its revocation. Verified keys are cached by their hash, so repeated
+//! requests with the same key skip all three, until the key expires or is revoked.
+
+use std::{num::NonZeroUsize, sync::Mutex};
+
+use lru::LruCache;
+
+use crate::core::keys::SecretKeyV2;
+
+type KeyHash = [u8; 32];
+
+struct CachedKey {
+    /// Tag of the `aimo-sk-{tag}-` prefix the key was verified with
+    tag: String,
+    key: SecretKeyV2,
+    /// Hashes of the key and its ancestors, any of which revokes the key
+    chain: Vec<KeyHash>,
+    expires_at: i64,
+}
+
+struct Entries {
+    /// `None` when the cache is disabled
+    keys: Option<LruCache<KeyHash, CachedKey>>,
+    /// Bumped on every invalidation, so keys verified before one aren't cached after it
+    generation: u64,
+}
+
+pub struct KeyCache {
+    entries: Mutex<Entries>,
+}
+
+impl KeyCache {
+    /// A cache of up to `capacity` keys, disabled if `0`
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            entries: Mutex::new(Entries {
+                keys: NonZeroUsize::new(capacity).map(LruCache::new),
+                generation: 0,
+            }),
+        }
+    }
+
+    /// Resize the cache, dropping the least recently used keys if it shrinks
+    pub fn resize(&self, capacity: usize) {
+        let mut entries = self.entries.lock().unwrap();
+        entries.keys = match (entries.keys.take(), NonZeroUsize::new(capacity)) {
+            (Some(mut keys), Some(capacity)) => {
+                keys.resize(capacity);
+                Some(keys)
+            }
+            (None, Some(capacity)) => Some(LruCache::new(capacity)),
+            (_, None) => None,
+        };
+    }
+
+    /// Take before verifying a key to cache with `insert`
+    pub fn generation(&self) -> u64 {
+        self.entries.lock().unwrap().generation
+    }
+
+    /// A verified key with `hash`, if it was encoded with `tag` and hasn't expired at
+    /// `now`, in unix milliseconds
+    pub fn get(&self, hash: &KeyHash, tag: &str, now: i64) -> Option<SecretKeyV2> {
+        let mut entries = self.entries.lock().unwrap();
+        let keys = entries.keys.as_mut()?;
+
+        let cached = keys.get(hash)?;
+        if cached.expires_at <= now {
+            keys.pop(hash);
+            return None;
+        }
+
+        (cached.tag == tag).then(|| cached.key.clone())
+    }
+
+    /// Cache a key verified since `generation`. Keys invalidated meanwhile are skipped.
+    pub fn insert(
+        &self,
+        hash: KeyHash,
+        tag: &str,
+        key: &SecretKeyV2,
+        generation: u64,
+    ) -> anyhow::Result<()> {
+        let chain = key
+            .chain()
+            .map(|key| key.clone().into_hash())
+            .collect::<anyhow::Result<Vec<_>>>()?;
+        let cached = CachedKey {
+            tag: tag.to_string(),
+            key: key.clone(),
+            chain,
+            expires_at: key.expires_at(),
+        };
+
+        let mut entries = self.entries.lock().unwrap();
+        if entries.generation == generation
+            && let Some(keys) = entries.keys.as_mut()
+        {
+            keys.put(hash, cached);
+        }
+
+        Ok(())
+    }
+
+    /// Drop a revoked key and every key delegated from it
+    pub fn invalidate(&self, hash: &KeyHash) {
+        let mut entries = self.entries.lock().unwrap();
+        entries.generation += 1;
+
+        let Some(keys) = entries.keys.as_mut() else {
+            return;
+        };
+        let revoked: Vec<KeyHash> = keys
+            .iter()
+            .filter(|(_, cached)| cached.chain.contains(hash))
+            .map(|(hash, _)| *hash)
+            .collect();
+        for hash in revoked {
+            keys.pop(&hash);
+        }
+    }
+
+    /// Drop every cached key, e.g. when revocation watermarks change
+    pub fn clear(&self) {
+        let mut entries = self.entries.lock().unwrap();
+        entries.generation += 1;
+
+        if let Some(keys) = entries.keys.as_mut() {
+            keys.clear();
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        let entries = self.entries.lock().unwrap();
+        entries.keys.as_ref().map_or(0, LruCache::len)
+    }
+}
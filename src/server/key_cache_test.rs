@@ -0,0 +1,113 @@
+use chrono::Utc;
+use solana_sdk::signature::Keypair;
+
+use super::key_cache::KeyCache;
+use crate::{
+    core::keys::{MetadataV2, SecretKeyV2},
+    test_utils::{metadata_v2, sign_v2},
+};
+
+fn cache_key(cache: &KeyCache, key: &SecretKeyV2) -> [u8; 32] {
+    let sk = key.clone().into_string("dev").unwrap();
+    let (_, hash) = SecretKeyV2::hash_encoded(&sk).unwrap();
+    cache.insert(hash, "dev", key, cache.generation()).unwrap();
+    hash
+}
+
+#[test]
+fn test_hash_encoded() {
+    let key = sign_v2(&Keypair::new(), metadata_v2());
+    let (tag, hash) = SecretKeyV2::hash_encoded(&key.clone().into_string("dev").unwrap()).unwrap();
+
+    assert_eq!(tag, "dev");
+
+    // Copies of the key with another signature share its identity, not its cache entry
+    let copy = SecretKeyV2 {
+        signature: sign_v2(&Keypair::new(), metadata_v2()).signature,
+        ..key.clone()
+    };
+    let (_, copy_hash) =
+        SecretKeyV2::hash_encoded(&copy.clone().into_string("dev").unwrap()).unwrap();
+    assert_ne!(hash, copy_hash);
+    assert_eq!(copy.into_hash().unwrap(), key.into_hash().unwrap());
+}
+
+#[test]
+fn test_cache_hit() {
+    let cache = KeyCache::new(2);
+    let key = sign_v2(&Keypair::new(), metadata_v2());
+    let hash = cache_key(&cache, &key);
+    let now = Utc::now().timestamp_millis();
+
+    assert_eq!(cache.get(&hash, "dev", now), Some(key.clone()));
+    // Keys relabelled with another tag miss
+    assert_eq!(cache.get(&hash, "prod", now), None);
+
+    // Expired keys are dropped
+    assert_eq!(cache.get(&hash, "dev", key.expires_at()), None);
+    assert_eq!(cache.len(), 0);
+}
+
+#[test]
+fn test_cache_bounded() {
+    let cache = KeyCache::new(2);
+    let hashes: Vec<_> = (0..3)
+        .map(|_| cache_key(&cache, &sign_v2(&Keypair::new(), metadata_v2())))
+        .collect();
+    let now = Utc::now().timestamp_millis();
+
+    assert_eq!(cache.len(), 2);
+    assert!(cache.get(&hashes[0], "dev", now).is_none());
+
+    cache.resize(0);
+    assert_eq!(cache.len(), 0);
+    cache_key(&cache, &sign_v2(&Keypair::new(), metadata_v2()));
+    assert_eq!(cache.len(), 0);
+}
+
+#[test]
+fn test_cache_invalidate() {
+    let cache = KeyCache::new(10);
+    let parent = sign_v2(&Keypair::new(), metadata_v2());
+    let child = sign_v2(
+        &Keypair::new(),
+        MetadataV2 {
+            parent: Some(Box::new(parent.clone())),
+            ..metadata_v2()
+        },
+    );
+    let other = sign_v2(&Keypair::new(), metadata_v2());
+    let (parent_hash, child_hash, other_hash) = (
+        cache_key(&cache, &parent),
+        cache_key(&cache, &child),
+        cache_key(&cache, &other),
+    );
+    let now = Utc::now().timestamp_millis();
+
+    // Revoking a parent drops its delegated keys
+    cache.invalidate(&parent.into_hash().unwrap());
+    assert!(cache.get(&parent_hash, "dev", now).is_none());
+    assert!(cache.get(&child_hash, "dev", now).is_none());
+    assert!(cache.get(&other_hash, "dev", now).is_some());
+
+    // Keys verified before a revocation aren't cached after it
+    let generation = cache.generation();
+    cache.invalidate(&other.into_hash().unwrap());
+    cache.insert(child_hash, "dev", &child, generation).unwrap();
+    assert!(cache.get(&child_hash, "dev", now).is_none());
+}
+
+#[test]
+fn test_cache_clear() {
+    let cache = KeyCache::new(10);
+    let key = sign_v2(&Keypair::new(), metadata_v2());
+    let hash = cache_key(&cache, &key);
+    let generation = cache.generation();
+
+    cache.clear();
+    assert_eq!(cache.len(), 0);
+
+    // Keys verified before the watermarks changed aren't cached after
+    cache.insert(hash, "dev", &key, generation).unwrap();
+    assert_eq!(cache.len(), 0);
+}
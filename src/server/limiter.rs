@@ -0,0 +1,415 @@
+//! Token bucket rate limiting of completion requests
+//!
+//! Limits come from the node configuration, so every key is subject to the same
+//! `per_key` options. Keys may only lower their own concurrent stream limit, with the
+//! `max_streams` restriction of their metadata.
+
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use axum::{
+    http::{HeaderMap, HeaderValue, StatusCode},
+    response::{IntoResponse, Response},
+};
+
+use crate::{
+    config::{RateLimitOptions, RateLimits},
+    core::keys::SecretKeyV2,
+};
+
+/// Buckets which refilled completely and sat idle are dropped past this many entries
+const MAX_IDLE_BUCKETS: usize = 10_000;
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Subject {
+    /// A secret key, by its base58 encoded hash
+    Key(String),
+    Signer(String),
+    Provider(String),
+}
+
+impl Subject {
+    fn kind(&self) -> &'static str {
+        match self {
+            Subject::Key(_) => "key",
+            Subject::Signer(_) => "signer",
+            Subject::Provider(_) => "provider",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Bucket {
+    tokens: f64,
+    updated: Instant,
+}
+
+impl Bucket {
+    fn full(capacity: f64) -> Self {
+        Self {
+            tokens: capacity,
+            updated: Instant::now(),
+        }
+    }
+
+    fn refill(&mut self, capacity: f64, rate: f64) {
+        let now = Instant::now();
+        let elapsed = now.duration_since(self.updated).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * rate).min(capacity);
+        self.updated = now;
+    }
+
+    /// Time until the bucket holds at least `amount`
+    fn wait_for(&self, amount: f64, rate: f64) -> Duration {
+        Duration::from_secs_f64(((amount - self.tokens) / rate).max(0.0))
+    }
+}
+
+#[derive(Debug, Default)]
+struct LimiterState {
+    limits: RateLimits,
+    requests: HashMap<Subject, Bucket>,
+    tokens: HashMap<Subject, Bucket>,
+    streams: HashMap<Subject, u32>,
+}
+
+/// Remaining budget of the most constrained subject, reported with `x-ratelimit-*` headers
+#[derive(Debug, Clone, Default)]
+pub struct RateLimitStatus {
+    requests: Option<(u64, u64, Duration)>,
+    tokens: Option<(u64, u64, Duration)>,
+}
+
+impl RateLimitStatus {
+    pub fn headers(&self) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        for (name, status) in [("requests", self.requests), ("tokens", self.tokens)] {
+            if let Some((limit, remaining, reset)) = status {
+                for (field, value) in [
+                    ("limit", limit.to_string()),
+                    ("remaining", remaining.to_string()),
+                    ("reset", format!("{}s", reset.as_secs_f64().ceil())),
+                ] {
+                    if let (Ok(header), Ok(value)) = (
+                        format!("x-ratelimit-{field}-{name}").parse::<http::HeaderName>(),
+                        HeaderValue::from_str(&value),
+                    ) {
+                        headers.insert(header, value);
+                    }
+                }
+            }
+        }
+
+        headers
+    }
+}
+
+/// Held while a request is being served, releasing its concurrent stream slots on drop
+pub struct RateLimitPermit {
+    limiter: Arc<RateLimiter>,
+    subjects: Vec<Subject>,
+    pub status: RateLimitStatus,
+}
+
+impl Drop for RateLimitPermit {
+    fn drop(&mut self) {
+        let mut state = self.limiter.state.lock().unwrap();
+        for subject in &self.subjects {
+            // Released regardless of the current limits, which may have been reloaded
+            if let Some(count) = state.streams.get_mut(subject) {
+                *count = count.saturating_sub(1);
+                if *count == 0 {
+                    state.streams.remove(subject);
+                }
+            }
+        }
+    }
+}
+
+/// A permit shared between `rate_limit_layer` and the handler, which may take it to hold
+/// it for longer than the response, e.g. until a buffered stream ends
+#[derive(Clone)]
+pub struct SharedPermit(Arc<Mutex<Option<RateLimitPermit>>>);
+
+impl SharedPermit {
+    pub fn new(permit: RateLimitPermit) -> Self {
+        Self(Arc::new(Mutex::new(Some(permit))))
+    }
+
+    pub fn take(&self) -> Option<RateLimitPermit> {
+        self.0.lock().unwrap().take()
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct RateLimited {
+    pub reason: String,
+    pub retry_after: Duration,
+    pub status: RateLimitStatus,
+}
+
+impl IntoResponse for RateLimited {
+    fn into_response(self) -> Response {
+        let mut headers = self.status.headers();
+        if let Ok(value) = HeaderValue::from_str(&self.retry_after.as_secs_f64().ceil().to_string())
+        {
+            headers.insert(http::header::RETRY_AFTER, value);
+        }
+
+        (StatusCode::TOO_MANY_REQUESTS, headers, self.reason).into_response()
+    }
+}
+
+pub struct RateLimiter {
+    state: Mutex<LimiterState>,
+}
+
+impl RateLimiter {
+    pub fn new(limits: RateLimits) -> Self {
+        Self {
+            state: Mutex::new(LimiterState {
+                limits,
+                ..Default::default()
+            }),
+        }
+    }
+
+    /// Replace the limits. Buckets are kept and capped to the new capacities on refill.
+    pub fn set_limits(&self, limits: RateLimits) {
+        self.state.lock().unwrap().limits = limits;
+    }
+
+    /// Admit a request on behalf of all `subjects`, or reject it if any of them is
+    /// over a limit. Nothing is consumed from any subject when the request is rejected.
+    ///
+    /// Key subjects are also limited to the key's own `key_max_streams`, if any.
+    pub fn acquire(
+        self: &Arc<Self>,
+        subjects: Vec<Subject>,
+        key_max_streams: Option<u32>,
+    ) -> Result<RateLimitPermit, RateLimited> {
+        self.admit(subjects, key_max_streams, true)
+    }
+
+    /// Admit a request without taking a concurrent stream slot, e.g. to resume a stream
+    /// which already holds one
+    pub fn acquire_request(
+        self: &Arc<Self>,
+        subjects: Vec<Subject>,
+    ) -> Result<RateLimitPermit, RateLimited> {
+        self.admit(subjects, None, false)
+    }
+
+    fn admit(
+        self: &Arc<Self>,
+        subjects: Vec<Subject>,
+        key_max_streams: Option<u32>,
+        stream: bool,
+    ) -> Result<RateLimitPermit, RateLimited> {
+        let mut state = self.state.lock().unwrap();
+        let limits = state.limits.clone();
+        let mut status = RateLimitStatus::default();
+        let mut rejection: Option<RateLimited> = None;
+
+        let mut reject = |reason: String, retry_after: Duration| {
+            if rejection
+                .as_ref()
+                .is_none_or(|current| current.retry_after < retry_after)
+            {
+                rejection = Some(RateLimited {
+                    reason,
+                    retry_after,
+                    status: RateLimitStatus::default(),
+                });
+            }
+        };
+
+        for subject in &subjects {
+            let options = limits.for_subject(subject);
+
+            if options.requests_per_second > 0.0 {
+                let capacity = request_capacity(options);
+                let bucket = state
+                    .requests
+                    .entry(subject.clone())
+                    .or_insert(Bucket::full(capacity));
+                bucket.refill(capacity, options.requests_per_second);
+
+                if bucket.tokens < 1.0 {
+                    reject(
+                        format!("Request rate limit of {} exceeded", subject.kind()),
+                        bucket.wait_for(1.0, options.requests_per_second),
+                    );
+                }
+
+                let remaining = (bucket.tokens - 1.0).max(0.0) as u64;
+                if status
+                    .requests
+                    .is_none_or(|(_, current, _)| remaining < current)
+                {
+                    status.requests = Some((
+                        capacity as u64,
+                        remaining,
+                        bucket.wait_for(capacity, options.requests_per_second),
+                    ));
+                }
+            }
+
+            if options.tokens_per_minute > 0 {
+                let capacity = options.tokens_per_minute as f64;
+                let rate = capacity / 60.0;
+                let bucket = state
+                    .tokens
+                    .entry(subject.clone())
+                    .or_insert(Bucket::full(capacity));
+                bucket.refill(capacity, rate);
+
+                if bucket.tokens <= 0.0 {
+                    reject(
+                        format!("Token rate limit of {} exceeded", subject.kind()),
+                        bucket.wait_for(1.0, rate),
+                    );
+                }
+
+                let remaining = bucket.tokens.max(0.0) as u64;
+                if status
+                    .tokens
+                    .is_none_or(|(_, current, _)| remaining < current)
+                {
+                    status.tokens = Some((
+                        options.tokens_per_minute,
+                        remaining,
+                        bucket.wait_for(capacity, rate),
+                    ));
+                }
+            }
+
+            let key_max_streams = key_max_streams.filter(|_| matches!(subject, Subject::Key(_)));
+            if stream
+                && let Some(max_streams) = stream_limit(options, key_max_streams)
+                && state.streams.get(subject).copied().unwrap_or(0) >= max_streams
+            {
+                let reason = if key_max_streams == Some(max_streams) {
+                    format!("Secret key allows {max_streams} concurrent streams")
+                } else {
+                    format!("Concurrent stream limit of {} exceeded", subject.kind())
+                };
+
+                // There's no telling when a stream finishes, so suggest a short retry
+                reject(reason, Duration::from_secs(1));
+            }
+        }
+
+        if let Some(mut rejection) = rejection {
+            rejection.status = status;
+            return Err(rejection);
+        }
+
+        // Admitted: consume from every subject
+        for subject in &subjects {
+            let options = limits.for_subject(subject);
+            if options.requests_per_second > 0.0
+                && let Some(bucket) = state.requests.get_mut(subject)
+            {
+                bucket.tokens -= 1.0;
+            }
+            let key_max_streams = key_max_streams.filter(|_| matches!(subject, Subject::Key(_)));
+            if stream && stream_limit(options, key_max_streams).is_some() {
+                *state.streams.entry(subject.clone()).or_default() += 1;
+            }
+        }
+
+        prune_idle(&limits, &mut state);
+
+        Ok(RateLimitPermit {
+            limiter: self.clone(),
+            // Only subjects holding a stream slot are released
+            subjects: if stream { subjects } else { vec![] },
+            status,
+        })
+    }
+
+    /// Charge tokens used by a finished request to all `subjects`.
+    ///
+    /// Buckets may go below zero, which makes subsequent requests wait until the debt
+    /// is refilled.
+    pub fn record_tokens(&self, subjects: &[Subject], tokens: u64) {
+        let mut state = self.state.lock().unwrap();
+        for subject in subjects {
+            let options = state.limits.for_subject(subject).clone();
+            if options.tokens_per_minute == 0 {
+                continue;
+            }
+
+            let capacity = options.tokens_per_minute as f64;
+            let bucket = state
+                .tokens
+                .entry(subject.clone())
+                .or_insert(Bucket::full(capacity));
+            bucket.refill(capacity, capacity / 60.0);
+            bucket.tokens -= tokens as f64;
+        }
+    }
+}
+
+impl RateLimits {
+    fn for_subject(&self, subject: &Subject) -> &RateLimitOptions {
+        match subject {
+            Subject::Key(_) => &self.per_key,
+            Subject::Signer(_) => &self.per_signer,
+            Subject::Provider(_) => &self.per_provider,
+        }
+    }
+}
+
+fn prune_idle(limits: &RateLimits, state: &mut LimiterState) {
+    if state.requests.len() > MAX_IDLE_BUCKETS {
+        state.requests.retain(|subject, bucket| {
+            let options = limits.for_subject(subject);
+            let capacity = request_capacity(options);
+            bucket.refill(capacity, options.requests_per_second);
+            bucket.tokens < capacity
+        });
+    }
+
+    if state.tokens.len() > MAX_IDLE_BUCKETS {
+        state.tokens.retain(|subject, bucket| {
+            let capacity = limits.for_subject(subject).tokens_per_minute as f64;
+            bucket.refill(capacity, capacity / 60.0);
+            bucket.tokens < capacity
+        });
+    }
+}
+
+/// The lower of a subject's configured stream limit, 0 for unlimited, and its own
+fn stream_limit(options: &RateLimitOptions, own_limit: Option<u32>) -> Option<u32> {
+    let configured = (options.max_concurrent_streams > 0).then_some(options.max_concurrent_streams);
+    match (configured, own_limit) {
+        (Some(configured), Some(own)) => Some(configured.min(own)),
+        (configured, own) => configured.or(own),
+    }
+}
+
+fn request_capacity(options: &RateLimitOptions) -> f64 {
+    options
+        .requests_per_second
+        .ceil()
+        .max(options.burst as f64)
+        .max(1.0)
+}
+
+/// Subjects limited on behalf of a request from `payload`'s key to `provider`
+pub fn request_subjects(payload: &SecretKeyV2, provider: Option<&str>) -> Vec<Subject> {
+    let mut subjects = vec![Subject::Signer(payload.signer.clone())];
+    if let Ok(hash) = payload.clone().into_hash() {
+        subjects.push(Subject::Key(bs58::encode(hash).into_string()));
+    }
+    if let Some(provider) = provider {
+        subjects.push(Subject::Provider(provider.to_string()));
+    }
+
+    subjects
+}
@@ -0,0 +1,146 @@
+use std::sync::Arc;
+
+use super::limiter::*;
+use crate::config::{RateLimitOptions, RateLimits};
+
+fn key() -> Vec<Subject> {
+    vec![Subject::Key("key".to_string())]
+}
+
+#[test]
+fn test_request_rate() {
+    let limiter = Arc::new(RateLimiter::new(RateLimits {
+        per_key: RateLimitOptions {
+            requests_per_second: 1.0,
+            burst: 2,
+            ..Default::default()
+        },
+        ..Default::default()
+    }));
+
+    let permit = limiter.acquire(key(), None).unwrap();
+    assert_eq!(
+        permit.status.headers()["x-ratelimit-remaining-requests"],
+        "1"
+    );
+    limiter.acquire(key(), None).unwrap();
+
+    let limited = limiter.acquire(key(), None).err().unwrap();
+    assert!(limited.retry_after.as_secs_f64() > 0.0);
+
+    // Other subjects are not affected
+    assert!(
+        limiter
+            .acquire(vec![Subject::Key("other".to_string())], None)
+            .is_ok()
+    );
+}
+
+#[test]
+fn test_concurrent_streams() {
+    let limiter = Arc::new(RateLimiter::new(RateLimits {
+        per_signer: RateLimitOptions {
+            max_concurrent_streams: 1,
+            ..Default::default()
+        },
+        ..Default::default()
+    }));
+    let signer = || vec![Subject::Signer("signer".to_string())];
+
+    let permit = limiter.acquire(signer(), None).unwrap();
+    assert!(limiter.acquire(signer(), None).is_err());
+
+    drop(permit);
+    assert!(limiter.acquire(signer(), None).is_ok());
+}
+
+#[test]
+fn test_resume_requests() {
+    let limiter = Arc::new(RateLimiter::new(RateLimits {
+        per_key: RateLimitOptions {
+            requests_per_second: 1.0,
+            burst: 2,
+            max_concurrent_streams: 1,
+            ..Default::default()
+        },
+        ..Default::default()
+    }));
+
+    // Resumes don't need a stream slot of their own, but are charged as requests
+    let permit = limiter.acquire(key(), None).unwrap();
+    let resume = limiter.acquire_request(key()).unwrap();
+    assert!(limiter.acquire_request(key()).is_err());
+
+    // Nor do they release one
+    drop(resume);
+    assert!(limiter.acquire(key(), None).is_err());
+    drop(permit);
+}
+
+#[test]
+fn test_tokens_per_minute() {
+    let limiter = Arc::new(RateLimiter::new(RateLimits {
+        per_provider: RateLimitOptions {
+            tokens_per_minute: 100,
+            ..Default::default()
+        },
+        ..Default::default()
+    }));
+    let provider = || vec![Subject::Provider("provider".to_string())];
+
+    drop(limiter.acquire(provider(), None).unwrap());
+    limiter.record_tokens(&provider(), 150);
+
+    let limited = limiter.acquire(provider(), None).err().unwrap();
+    assert!(limited.retry_after.as_secs() >= 29);
+}
+
+#[test]
+fn test_set_limits() {
+    let limiter = Arc::new(RateLimiter::new(RateLimits::default()));
+    let permit = limiter.acquire(key(), None).unwrap();
+
+    limiter.set_limits(RateLimits {
+        per_key: RateLimitOptions {
+            max_concurrent_streams: 1,
+            ..Default::default()
+        },
+        ..Default::default()
+    });
+
+    // Requests admitted under the previous limits aren't counted
+    let second = limiter.acquire(key(), None).unwrap();
+    assert!(limiter.acquire(key(), None).is_err());
+
+    drop(permit);
+    drop(second);
+    assert!(limiter.acquire(key(), None).is_ok());
+}
+
+#[test]
+fn test_key_max_streams() {
+    let limiter = Arc::new(RateLimiter::new(RateLimits {
+        per_key: RateLimitOptions {
+            max_concurrent_streams: 3,
+            ..Default::default()
+        },
+        ..Default::default()
+    }));
+
+    // The key's own limit applies when it's lower than the configured one
+    let permit = limiter.acquire(key(), Some(1)).unwrap();
+    let limited = limiter.acquire(key(), Some(1)).err().unwrap();
+    assert_eq!(limited.reason, "Secret key allows 1 concurrent streams");
+
+    drop(permit);
+    let _first = limiter.acquire(key(), Some(5)).unwrap();
+    let _second = limiter.acquire(key(), Some(5)).unwrap();
+    let _third = limiter.acquire(key(), Some(5)).unwrap();
+    let limited = limiter.acquire(key(), Some(5)).err().unwrap();
+    assert_eq!(limited.reason, "Concurrent stream limit of key exceeded");
+
+    // Other subjects aren't limited by the key
+    let signer = vec![Subject::Signer("signer".to_string())];
+    let _permit = limiter.acquire(signer.clone(), Some(1)).unwrap();
+    assert!(limiter.acquire(signer, Some(1)).is_ok());
+}
@@ -0,0 +1,321 @@
+//! Prometheus metrics of the API server
+//!
+//! Label values are bounded: routes are matched path templates, services are only
+//! labelled once they answered a request, and other labels are fixed enums.
+
+use std::{
+    fmt::{self, Write},
+    sync::{Arc, atomic::AtomicI64},
+    time::{Duration, Instant},
+};
+
+use http::Method;
+use prometheus_client::{
+    encoding::{EncodeLabelSet, EncodeLabelValue, LabelValueEncoder, text::encode},
+    metrics::{
+        counter::Counter,
+        family::Family,
+        gauge::Gauge,
+        histogram::{Histogram, exponential_buckets},
+    },
+    registry::Registry,
+};
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, EncodeLabelSet)]
+pub struct RequestLabels {
+    /// Matched route template, e.g. `/api/v1/chat/completions`
+    pub route: String,
+    pub method: String,
+    pub status: u16,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, EncodeLabelSet)]
+pub struct ServiceLabels {
+    pub service: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum AuthFailure {
+    Decode,
+    Revoked,
+    KeyTag,
+    Signature,
+    Scope,
+    Restricted,
+    UsageLimit,
+}
+
+impl EncodeLabelValue for AuthFailure {
+    fn encode(&self, encoder: &mut LabelValueEncoder) -> Result<(), fmt::Error> {
+        encoder.write_str(match self {
+            AuthFailure::Decode => "decode",
+            AuthFailure::Revoked => "revoked",
+            AuthFailure::KeyTag => "key_tag",
+            AuthFailure::Signature => "signature",
+            AuthFailure::Scope => "scope",
+            AuthFailure::Restricted => "restricted",
+            AuthFailure::UsageLimit => "usage_limit",
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, EncodeLabelSet)]
+pub struct AuthFailureLabels {
+    pub reason: AuthFailure,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum LookupResult {
+    Revoked,
+    NotRevoked,
+    Error,
+}
+
+impl EncodeLabelValue for LookupResult {
+    fn encode(&self, encoder: &mut LabelValueEncoder) -> Result<(), fmt::Error> {
+        encoder.write_str(match self {
+            LookupResult::Revoked => "revoked",
+            LookupResult::NotRevoked => "not_revoked",
+            LookupResult::Error => "error",
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, EncodeLabelSet)]
+pub struct LookupLabels {
+    pub result: LookupResult,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum CacheResult {
+    Hit,
+    Miss,
+}
+
+impl EncodeLabelValue for CacheResult {
+    fn encode(&self, encoder: &mut LabelValueEncoder) -> Result<(), fmt::Error> {
+        encoder.write_str(match self {
+            CacheResult::Hit => "hit",
+            CacheResult::Miss => "miss",
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, EncodeLabelSet)]
+pub struct CacheLabels {
+    pub result: CacheResult,
+}
+
+type HistogramFamily<L> = Family<L, Histogram, fn() -> Histogram>;
+
+pub struct Metrics {
+    registry: Registry,
+    pub requests: Family<RequestLabels, Counter>,
+    pub first_byte_seconds: HistogramFamily<ServiceLabels>,
+    pub completion_seconds: HistogramFamily<ServiceLabels>,
+    pub chunks: Family<ServiceLabels, Counter>,
+    pub chunk_bytes: HistogramFamily<ServiceLabels>,
+    pub stream_errors: Family<ServiceLabels, Counter>,
+    pub router_queue_depth: Gauge<i64, AtomicI64>,
+    pub providers_connected: Gauge<i64, AtomicI64>,
+    pub auth_failures: Family<AuthFailureLabels, Counter>,
+    pub revocation_lookups: Family<LookupLabels, Counter>,
+    pub key_cache_lookups: Family<CacheLabels, Counter>,
+    pub key_cache_entries: Gauge<i64, AtomicI64>,
+}
+
+impl Default for Metrics {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Metrics {
+    pub fn new() -> Self {
+        let mut metrics = Self {
+            registry: Registry::with_prefix("aimo"),
+            requests: Family::default(),
+            // 5ms to 40s
+            first_byte_seconds: Family::new_with_constructor(|| {
+                Histogram::new(exponential_buckets(0.005, 2.0, 14))
+            }),
+            // 50ms to 7min
+            completion_seconds: Family::new_with_constructor(|| {
+                Histogram::new(exponential_buckets(0.05, 2.0, 14))
+            }),
+            chunks: Family::default(),
+            // 16B to 256KiB
+            chunk_bytes: Family::new_with_constructor(|| {
+                Histogram::new(exponential_buckets(16.0, 4.0, 8))
+            }),
+            stream_errors: Family::default(),
+            router_queue_depth: Gauge::default(),
+            providers_connected: Gauge::default(),
+            auth_failures: Family::default(),
+            revocation_lookups: Family::default(),
+            key_cache_lookups: Family::default(),
+            key_cache_entries: Gauge::default(),
+        };
+
+        let registry = &mut metrics.registry;
+        registry.register(
+            "http_requests",
+            "HTTP requests by route, method and status",
+            metrics.requests.clone(),
+        );
+        registry.register(
+            "completion_first_byte_seconds",
+            "Time until a provider's first response to a completion request",
+            metrics.first_byte_seconds.clone(),
+        );
+        registry.register(
+            "completion_seconds",
+            "Time until a completion request is fully served",
+            metrics.completion_seconds.clone(),
+        );
+        registry.register(
+            "completion_chunks",
+            "Chunks delivered in completion streams",
+            metrics.chunks.clone(),
+        );
+        registry.register(
+            "completion_chunk_bytes",
+            "Size of chunks delivered in completion streams",
+            metrics.chunk_bytes.clone(),
+        );
+        registry.register(
+            "completion_stream_errors",
+            "Completion streams failing after they started",
+            metrics.stream_errors.clone(),
+        );
+        registry.register(
+            "router_queue_depth",
+            "Messages waiting in the router's dispatch queue",
+            metrics.router_queue_depth.clone(),
+        );
+        registry.register(
+            "providers_connected",
+            "Service providers currently connected",
+            metrics.providers_connected.clone(),
+        );
+        registry.register(
+            "auth_failures",
+            "Rejected secret keys by reason",
+            metrics.auth_failures.clone(),
+        );
+        registry.register(
+            "revocation_lookups",
+            "Key revocation lookups by result",
+            metrics.revocation_lookups.clone(),
+        );
+        registry.register(
+            "key_cache_lookups",
+            "Verified secret key cache lookups by result",
+            metrics.key_cache_lookups.clone(),
+        );
+        registry.register(
+            "key_cache_entries",
+            "Secret keys in the verified key cache",
+            metrics.key_cache_entries.clone(),
+        );
+
+        metrics
+    }
+
+    pub fn record_request(&self, route: Option<&str>, method: &Method, status: u16) {
+        let method = match *method {
+            Method::GET
+            | Method::POST
+            | Method::PUT
+            | Method::DELETE
+            | Method::PATCH
+            | Method::HEAD
+            | Method::OPTIONS => method.as_str(),
+            _ => "OTHER",
+        };
+
+        self.requests
+            .get_or_create(&RequestLabels {
+                route: route.unwrap_or("unmatched").to_string(),
+                method: method.to_string(),
+                status,
+            })
+            .inc();
+    }
+
+    pub fn record_auth_failure(&self, reason: AuthFailure) {
+        self.auth_failures
+            .get_or_create(&AuthFailureLabels { reason })
+            .inc();
+    }
+
+    pub fn record_revocation_lookup(&self, result: LookupResult) {
+        self.revocation_lookups
+            .get_or_create(&LookupLabels { result })
+            .inc();
+    }
+
+    pub fn record_key_cache_lookup(&self, result: CacheResult) {
+        self.key_cache_lookups
+            .get_or_create(&CacheLabels { result })
+            .inc();
+    }
+
+    /// Start observing a completion served by `service`, which responded after `first_byte`
+    pub fn observe_completion(
+        self: &Arc<Self>,
+        service: &str,
+        started: Instant,
+        first_byte: Duration,
+    ) -> CompletionObserver {
+        let labels = ServiceLabels {
+            service: service.to_string(),
+        };
+        self.first_byte_seconds
+            .get_or_create(&labels)
+            .observe(first_byte.as_secs_f64());
+
+        CompletionObserver {
+            metrics: self.clone(),
+            labels,
+            started,
+        }
+    }
+
+    /// Metrics in the Prometheus text format
+    pub fn encode(&self) -> Result<String, std::fmt::Error> {
+        let mut buffer = String::new();
+        encode(&mut buffer, &self.registry)?;
+        Ok(buffer)
+    }
+}
+
+/// Records chunks of a completion, and its total duration when dropped
+pub struct CompletionObserver {
+    metrics: Arc<Metrics>,
+    labels: ServiceLabels,
+    started: Instant,
+}
+
+impl CompletionObserver {
+    pub fn chunk(&self, data: &str) {
+        self.metrics.chunks.get_or_create(&self.labels).inc();
+        self.metrics
+            .chunk_bytes
+            .get_or_create(&self.labels)
+            .observe(data.len() as f64);
+    }
+
+    pub fn stream_error(&self) {
+        self.metrics.stream_errors.get_or_create(&self.labels).inc();
+    }
+}
+
+impl Drop for CompletionObserver {
+    fn drop(&mut self) {
+        self.metrics
+            .completion_seconds
+            .get_or_create(&self.labels)
+            .observe(self.started.elapsed().as_secs_f64());
+    }
+}
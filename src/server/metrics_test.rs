@@ -0,0 +1,39 @@
+use std::{
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use http::Method;
+
+use super::metrics::{AuthFailure, CacheResult, LookupResult, Metrics};
+
+#[test]
+fn test_encode() {
+    let metrics = Arc::new(Metrics::new());
+    metrics.record_request(Some("/api/v1/ping"), &Method::GET, 200);
+    metrics.record_request(None, &Method::from_bytes(b"BREW").unwrap(), 404);
+    metrics.record_auth_failure(AuthFailure::UsageLimit);
+    metrics.record_revocation_lookup(LookupResult::NotRevoked);
+    metrics.record_key_cache_lookup(CacheResult::Hit);
+
+    {
+        let observer =
+            metrics.observe_completion("provider", Instant::now(), Duration::from_millis(20));
+        observer.chunk("{\"id\":\"1\"}");
+        observer.chunk("{\"id\":\"2\"}");
+    }
+
+    let text = metrics.encode().unwrap();
+    for line in [
+        "aimo_http_requests_total{route=\"/api/v1/ping\",method=\"GET\",status=\"200\"} 1",
+        "aimo_http_requests_total{route=\"unmatched\",method=\"OTHER\",status=\"404\"} 1",
+        "aimo_auth_failures_total{reason=\"usage_limit\"} 1",
+        "aimo_revocation_lookups_total{result=\"not_revoked\"} 1",
+        "aimo_key_cache_lookups_total{result=\"hit\"} 1",
+        "aimo_completion_chunks_total{service=\"provider\"} 2",
+        "aimo_completion_first_byte_seconds_count{service=\"provider\"} 1",
+        "aimo_completion_seconds_count{service=\"provider\"} 1",
+    ] {
+        assert!(text.contains(line), "missing {line} in\n{text}");
+    }
+}
@@ -0,0 +1,37 @@
+use axum::{
+    extract::{Request, State},
+    http::StatusCode,
+    middleware::Next,
+    response::Response,
+};
+
+use crate::server::api::state::ApiState;
+
+/// Header of the admin token
+pub const ADMIN_TOKEN: &str = "x-admin-token";
+
+/// Check the `X-Admin-Token` header of admin routes against the configured `admin_token`.
+/// Their bearer is a secret key with the `admin` scope, checked by `auth_layer`.
+pub async fn admin_layer(
+    State(state): State<ApiState>,
+    req: Request,
+    next: Next,
+) -> Result<Response, (StatusCode, String)> {
+    let Some(admin_token) = state.options().admin_token.clone() else {
+        return Err((StatusCode::FORBIDDEN, "Admin API disabled".to_string()));
+    };
+
+    let token = req
+        .headers()
+        .get(ADMIN_TOKEN)
+        .map_or(&[][..], |token| token.as_bytes());
+    if !constant_time_eq(token, admin_token.as_bytes()) {
+        return Err((StatusCode::UNAUTHORIZED, "Invalid admin token".to_string()));
+    }
+
+    Ok(next.run(req).await)
+}
+
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |acc, (x, y)| acc | (x ^ y)) == 0
+}
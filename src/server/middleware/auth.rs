@@ -1,5 +1,7 @@
+use std::net::SocketAddr;
+
 use axum::{
-    extract::{Request, State},
+    extract::{ConnectInfo, Request, State},
     http::StatusCode,
     middleware::Next,
     response::Response,
@@ -8,54 +10,172 @@ use axum_extra::{
     TypedHeader,
     headers::{Authorization, authorization::Bearer},
 };
+use chrono::{DateTime, Utc};
+
+use crate::{
+    core::keys::{Scope, SecretKeyV2},
+    server::{
+        api::state::ApiState,
+        metrics::{AuthFailure, CacheResult, LookupResult},
+    },
+};
+
+/// State of `auth_layer`: the API state and the scopes a route requires
+#[derive(Clone)]
+pub struct AuthState {
+    pub state: ApiState,
+    pub scopes: &'static [Scope],
+}
 
-use crate::{core::keys::SecretKeyV1, server::api::state::ApiState};
+impl AuthState {
+    pub fn new(state: ApiState, scopes: &'static [Scope]) -> Self {
+        Self { state, scopes }
+    }
+}
 
-/// Validate a secret key and forward secret key payload to axum's extension extractor
+/// Validate a secret key holding all scopes the route requires and forward secret key
+/// payload to axum's extension extractor
 pub async fn auth_layer(
-    State(ApiState { state_db, .. }): State<ApiState>,
+    State(AuthState { state, scopes }): State<AuthState>,
     TypedHeader(Authorization(bearer)): TypedHeader<Authorization<Bearer>>,
     mut req: Request,
     next: Next,
 ) -> Result<Response, (StatusCode, String)> {
-    let sk = bearer.token();
+    let metrics = &state.metrics;
+    let payload = authenticate(&state, bearer.token())?;
+
+    if let Some(scope) = scopes
+        .iter()
+        .find(|scope| !payload.metadata.has_scope(**scope))
+    {
+        metrics.record_auth_failure(AuthFailure::Scope);
+        return Err((
+            StatusCode::FORBIDDEN,
+            format!("Secret key lacks the `{scope}` scope"),
+        ));
+    }
+
+    let restrictions = &payload.metadata.restrictions;
+    let address = req
+        .extensions()
+        .get::<ConnectInfo<SocketAddr>>()
+        .map(|ConnectInfo(addr)| addr.ip());
+    if let Err(err) = restrictions
+        .check_time(Utc::now())
+        .and_then(|_| restrictions.check_address(address))
+    {
+        metrics.record_auth_failure(AuthFailure::Restricted);
+        return Err((StatusCode::FORBIDDEN, err.to_string()));
+    }
 
-    let (scope, payload) = SecretKeyV1::decode(sk).map_err(|_| {
+    // Secret key is valid
+    tracing::Span::current().record("signer", payload.signer_fingerprint());
+    req.extensions_mut().insert(payload);
+
+    Ok(next.run(req).await)
+}
+
+/// Decode and verify a secret key, or take it from the verified key cache
+fn authenticate(state: &ApiState, sk: &str) -> Result<SecretKeyV2, (StatusCode, String)> {
+    let metrics = &state.metrics;
+    let check_tag = |scope: &str, payload: &SecretKeyV2| {
+        state
+            .options()
+            .keys
+            .check_tag(scope, payload.metadata.tag.as_deref())
+            .map_err(|err| {
+                metrics.record_auth_failure(AuthFailure::KeyTag);
+                (StatusCode::UNAUTHORIZED, err.to_string())
+            })
+    };
+
+    let generation = state.key_cache.generation();
+    let encoded = SecretKeyV2::hash_encoded(sk).ok();
+    if let Some((scope, hash)) = &encoded
+        && let Some(payload) = state
+            .key_cache
+            .get(hash, scope, Utc::now().timestamp_millis())
+    {
+        metrics.record_key_cache_lookup(CacheResult::Hit);
+        // Accepted tags may have been reloaded since the key was cached
+        check_tag(scope, &payload)?;
+        return Ok(payload);
+    }
+    metrics.record_key_cache_lookup(CacheResult::Miss);
+
+    let (scope, payload) = SecretKeyV2::decode(sk).map_err(|_| {
+        metrics.record_auth_failure(AuthFailure::Decode);
         (
             StatusCode::UNAUTHORIZED,
             "Failed to decode secret key payload".to_string(),
         )
     })?;
 
-    if state_db
+    let revoked = state
+        .state_db
         .revocation
         .is_key_revoked(&payload)
         .map_err(|err| {
+            metrics.record_revocation_lookup(LookupResult::Error);
             (
                 StatusCode::INTERNAL_SERVER_ERROR,
                 format!("Failed to check key revocation: {err}"),
             )
-        })?
-    {
+        })?;
+
+    if revoked {
+        metrics.record_revocation_lookup(LookupResult::Revoked);
+        metrics.record_auth_failure(AuthFailure::Revoked);
         return Err((StatusCode::UNAUTHORIZED, "Key already revoked".to_string()));
     }
+    metrics.record_revocation_lookup(LookupResult::NotRevoked);
 
-    if scope != "dev" {
+    let watermark = state
+        .state_db
+        .watermarks
+        .revoked_before(&payload)
+        .map_err(|err| {
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                format!("Failed to check revocation watermarks: {err}"),
+            )
+        })?;
+    if let Some(watermark) = watermark {
+        metrics.record_auth_failure(AuthFailure::Revoked);
+        // Compromised wallets revoke every key they may ever sign
+        let reason = match DateTime::from_timestamp_millis(watermark) {
+            Some(time) => format!("Keys issued before {} are revoked", time.to_rfc3339()),
+            None => "All keys of the signer are revoked".to_string(),
+        };
+        return Err((StatusCode::UNAUTHORIZED, reason));
+    }
+
+    check_tag(&scope, &payload)?;
+
+    // Every key the delegate signs carries the grant's signature, so anyone holding one
+    // could present the grant
+    if payload.is_delegation_grant() {
+        metrics.record_auth_failure(AuthFailure::Restricted);
         return Err((
-            StatusCode::UNAUTHORIZED,
-            format!("Scope {scope} not supported"),
+            StatusCode::FORBIDDEN,
+            "Keys naming a delegate can only sign delegated keys".to_string(),
         ));
     }
 
     if let Err(err) = payload.verify_signature() {
+        metrics.record_auth_failure(AuthFailure::Signature);
         return Err((
             StatusCode::UNAUTHORIZED,
             format!("Failed to verify secret key: {err}"),
         ));
     }
 
-    // Secret key is valid
-    req.extensions_mut().insert(payload);
+    if let Some((_, hash)) = encoded {
+        if let Err(err) = state.key_cache.insert(hash, &scope, &payload, generation) {
+            tracing::warn!("Failed to cache verified key: {err}");
+        }
+        metrics.key_cache_entries.set(state.key_cache.len() as i64);
+    }
 
-    Ok(next.run(req).await)
+    Ok(payload)
 }
@@ -1,15 +1,68 @@
-use http::Method;
-use tower_http::cors::{Any, CorsLayer};
+use std::time::Duration;
 
-use crate::config::ServerOptions;
+use http::{HeaderName, HeaderValue, Method};
+use tower_http::cors::{AllowHeaders, AllowMethods, AllowOrigin, Any, CorsLayer, ExposeHeaders};
 
-pub fn cors_layer(_options: &ServerOptions) -> CorsLayer {
-    // TODO: Make this configurable
+use crate::config::{CORS_WILDCARD, RouteGroup, ServerOptions};
 
-    CorsLayer::new()
-        // allow `GET` and `POST` when accessing the resource
-        .allow_methods([Method::GET, Method::POST])
-        // allow requests from any origin
-        .allow_origin(Any)
-        .allow_headers(Any)
+/// CORS layer of a route group, or of ungrouped routes with `None`.
+///
+/// Entries that fail to parse are skipped, `ServerOptions::validate` reports them at startup.
+pub fn cors_layer(options: &ServerOptions, group: Option<RouteGroup>) -> CorsLayer {
+    let policy = options.cors.for_group(group);
+    let is_wildcard = |values: &[String]| values.iter().any(|v| v == CORS_WILDCARD);
+
+    let allow_origin = if is_wildcard(&policy.allowed_origins) {
+        AllowOrigin::from(Any)
+    } else {
+        AllowOrigin::list(
+            policy
+                .allowed_origins
+                .iter()
+                .filter_map(|origin| HeaderValue::from_str(origin).ok()),
+        )
+    };
+
+    let allow_methods = if is_wildcard(&policy.allowed_methods) {
+        AllowMethods::from(Any)
+    } else {
+        AllowMethods::list(
+            policy
+                .allowed_methods
+                .iter()
+                .filter_map(|method| Method::from_bytes(method.as_bytes()).ok()),
+        )
+    };
+
+    let allow_headers = if is_wildcard(&policy.allowed_headers) {
+        AllowHeaders::from(Any)
+    } else {
+        AllowHeaders::list(parse_header_names(&policy.allowed_headers))
+    };
+
+    let expose_headers = if is_wildcard(&policy.exposed_headers) {
+        ExposeHeaders::from(Any)
+    } else {
+        ExposeHeaders::list(parse_header_names(&policy.exposed_headers))
+    };
+
+    let mut layer = CorsLayer::new()
+        .allow_origin(allow_origin)
+        .allow_methods(allow_methods)
+        .allow_headers(allow_headers)
+        .expose_headers(expose_headers)
+        .allow_credentials(policy.allow_credentials);
+
+    if let Some(max_age) = policy.max_age {
+        layer = layer.max_age(Duration::from_secs(max_age));
+    }
+
+    layer
+}
+
+fn parse_header_names(names: &[String]) -> Vec<HeaderName> {
+    names
+        .iter()
+        .filter_map(|name| HeaderName::from_bytes(name.as_bytes()).ok())
+        .collect()
 }
@@ -0,0 +1,33 @@
+use axum::{
+    body::Body,
+    extract::{Request, State},
+    http::StatusCode,
+    middleware::Next,
+    response::Response,
+};
+use futures_util::StreamExt;
+
+use crate::server::api::state::ApiState;
+
+/// Refuse requests once the node is shutting down, and count the others as in flight
+/// until their response bodies are finished
+pub async fn drain_layer(
+    State(state): State<ApiState>,
+    req: Request,
+    next: Next,
+) -> Result<Response, (StatusCode, String)> {
+    let Some(in_flight) = state.shutdown.track() else {
+        return Err((
+            StatusCode::SERVICE_UNAVAILABLE,
+            "Node is shutting down".to_string(),
+        ));
+    };
+
+    let (parts, body) = next.run(req).await.into_parts();
+    let body = Body::from_stream(body.into_data_stream().map(move |chunk| {
+        let _ = &in_flight;
+        chunk
+    }));
+
+    Ok(Response::from_parts(parts, body))
+}
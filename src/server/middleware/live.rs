@@ -0,0 +1,67 @@
+use std::{
+    convert::Infallible,
+    sync::Arc,
+    task::{Context, Poll},
+};
+
+use axum::{extract::Request, response::Response};
+use futures_util::future::BoxFuture;
+use tower::{Layer, Service, ServiceExt};
+
+use crate::config::{ConfigReloader, ServerOptions};
+
+/// Wraps routes in a layer built from the running server options for every request,
+/// so reloaded options apply without rebuilding the router.
+#[derive(Clone)]
+pub struct LiveLayer<F> {
+    config: Arc<ConfigReloader>,
+    make_layer: F,
+}
+
+impl<F> LiveLayer<F> {
+    pub fn new(config: Arc<ConfigReloader>, make_layer: F) -> Self {
+        Self { config, make_layer }
+    }
+}
+
+impl<S, F: Clone> Layer<S> for LiveLayer<F> {
+    type Service = Live<S, F>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        Live {
+            inner,
+            config: self.config.clone(),
+            make_layer: self.make_layer.clone(),
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct Live<S, F> {
+    inner: S,
+    config: Arc<ConfigReloader>,
+    make_layer: F,
+}
+
+impl<S, F, L> Service<Request> for Live<S, F>
+where
+    S: Clone,
+    F: Fn(&ServerOptions) -> L,
+    L: Layer<S>,
+    L::Service: Service<Request, Response = Response, Error = Infallible> + Send + 'static,
+    <L::Service as Service<Request>>::Future: Send,
+{
+    type Response = Response;
+    type Error = Infallible;
+    type Future = BoxFuture<'static, Result<Response, Infallible>>;
+
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        // Readiness is checked by `oneshot` on the service built for each request
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, req: Request) -> Self::Future {
+        let service = (self.make_layer)(&self.config.options()).layer(self.inner.clone());
+        Box::pin(service.oneshot(req))
+    }
+}
@@ -0,0 +1,23 @@
+use axum::{
+    extract::{MatchedPath, Request, State},
+    middleware::Next,
+    response::Response,
+};
+
+use crate::server::api::state::ApiState;
+
+/// Count requests by matched route, method and status
+pub async fn metrics_layer(State(state): State<ApiState>, req: Request, next: Next) -> Response {
+    let route = req
+        .extensions()
+        .get::<MatchedPath>()
+        .map(|path| path.as_str().to_string());
+    let method = req.method().clone();
+
+    let response = next.run(req).await;
+    state
+        .metrics
+        .record_request(route.as_deref(), &method, response.status().as_u16());
+
+    response
+}
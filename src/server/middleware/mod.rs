@@ -1,7 +1,17 @@
+mod admin;
 mod auth;
 mod cors;
+mod drain;
+mod live;
+mod metrics;
+mod rate_limit;
 mod timeout;
 
-pub use auth::auth_layer;
+pub use admin::admin_layer;
+pub use auth::{AuthState, auth_layer};
 pub use cors::cors_layer;
+pub use drain::drain_layer;
+pub use live::LiveLayer;
+pub use metrics::metrics_layer;
+pub use rate_limit::rate_limit_layer;
 pub use timeout::timeout_layer;
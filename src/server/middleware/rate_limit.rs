@@ -0,0 +1,69 @@
+use axum::{
+    Extension,
+    body::Body,
+    extract::{Request, State},
+    http::StatusCode,
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use futures_util::StreamExt;
+use serde_json::Value;
+
+use crate::{
+    core::keys::SecretKeyV2,
+    server::{
+        api::{
+            chat::{LAST_EVENT_ID, completion_target},
+            state::ApiState,
+        },
+        limiter::{SharedPermit, request_subjects},
+    },
+};
+
+/// Request bodies are buffered to find the target provider, up to axum's default body limit
+const MAX_BODY_BYTES: usize = 2 * 1024 * 1024;
+
+/// Apply rate limits of the secret key, its signer and the requested provider.
+///
+/// Must be layered inside `auth_layer`, which provides the secret key payload.
+pub async fn rate_limit_layer(
+    State(ApiState { limiter, .. }): State<ApiState>,
+    Extension(payload): Extension<SecretKeyV2>,
+    req: Request,
+    next: Next,
+) -> Result<Response, Response> {
+    let (parts, body) = req.into_parts();
+    let bytes = axum::body::to_bytes(body, MAX_BODY_BYTES)
+        .await
+        .map_err(|_| (StatusCode::PAYLOAD_TOO_LARGE, "Request body too large").into_response())?;
+
+    let provider = serde_json::from_slice::<Value>(&bytes)
+        .ok()
+        .and_then(|body| completion_target(&body).map(str::to_string));
+
+    // Resumed streams already hold their concurrent stream slot
+    let subjects = request_subjects(&payload, provider.as_deref());
+    let permit = if parts.headers.contains_key(LAST_EVENT_ID) {
+        limiter.acquire_request(subjects)
+    } else {
+        limiter.acquire(subjects, payload.metadata.restrictions.max_streams)
+    }
+    .map_err(IntoResponse::into_response)?;
+    let headers = permit.status.headers();
+    let permit = SharedPermit::new(permit);
+
+    let mut req = Request::from_parts(parts, Body::from(bytes));
+    req.extensions_mut().insert(permit.clone());
+    let mut response = next.run(req).await;
+    response.headers_mut().extend(headers);
+
+    // Hold the permit until the response body is finished, unless the handler took it to
+    // hold until a buffered stream ends
+    let (parts, body) = response.into_parts();
+    let body = Body::from_stream(body.into_data_stream().map(move |chunk| {
+        let _ = &permit;
+        chunk
+    }));
+
+    Ok(Response::from_parts(parts, body))
+}
@@ -1,10 +1,14 @@
-use std::time::Duration;
-
+use tower::{
+    layer::util::Identity,
+    util::{Either, option_layer},
+};
 use tower_http::timeout::TimeoutLayer;
 
 use crate::config::ServerOptions;
 
-pub fn timeout_layer(_options: &ServerOptions) -> TimeoutLayer {
-    //TODO: make this configurable
-    TimeoutLayer::new(Duration::from_secs(30))
+/// Timeout of routes that don't stream.
+///
+/// Streaming routes time out on their own, see `TimeoutOptions`.
+pub fn timeout_layer(options: &ServerOptions) -> Either<TimeoutLayer, Identity> {
+    option_layer(options.timeouts.request().map(TimeoutLayer::new))
 }
@@ -1,9 +1,27 @@
 mod api;
+mod buffer;
 mod context;
 mod grpc;
+mod key_cache;
+mod limiter;
+mod metrics;
 mod middleware;
 mod serve;
+mod shutdown;
 mod types;
 
+pub use api::state::ApiState;
 pub use context::ServiceContext;
 pub use serve::serve;
+pub use shutdown::Shutdown;
+
+#[cfg(test)]
+mod buffer_test;
+#[cfg(test)]
+mod key_cache_test;
+#[cfg(test)]
+mod limiter_test;
+#[cfg(test)]
+mod metrics_test;
+#[cfg(test)]
+mod shutdown_test;
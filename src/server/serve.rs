@@ -1,18 +1,27 @@
-use std::sync::Arc;
+use std::net::SocketAddr;
 
-use axum::Router;
+use axum::{Router, middleware};
 
-use crate::{
-    config::ServerOptions,
-    db::StateDb,
-    server::{api::api_v1, context::ServiceContext, grpc::grpc_v1},
+use crate::server::{
+    api::{api_v1, probes, state::ApiState},
+    grpc::grpc_v1,
+    middleware::metrics_layer,
 };
 
-pub async fn serve(options: &ServerOptions, ctx: ServiceContext, state_db: Arc<StateDb>) {
+/// Serve the API until its shutdown drains and every request in flight is finished
+///
+/// The state outlives the server, so rate limits, stream buffers, cached keys and
+/// metrics carry over when it restarts.
+pub async fn serve(state: ApiState) {
+    // The listen address can't change on reload
+    let options = state.options();
+    let shutdown = state.shutdown.clone();
     let router = Router::new()
         // Setup router groups
-        .nest("/api/v1", api_v1(options, ctx, state_db))
-        .nest("/grpc/v1", grpc_v1());
+        .nest("/api/v1", api_v1(state.clone()))
+        .nest("/grpc/v1", grpc_v1())
+        .merge(probes(state.clone()))
+        .layer(middleware::from_fn_with_state(state, metrics_layer));
 
     tracing::info!("Server instance built");
 
@@ -25,8 +34,17 @@ pub async fn serve(options: &ServerOptions, ctx: ServiceContext, state_db: Arc<S
 
     tracing::info!("API server running and listening at {socket_addr}");
 
-    axum::serve(listener, router)
-        .await
-        .inspect_err(|err| tracing::error!("{err}"))
-        .unwrap();
+    let draining = shutdown.clone();
+    // Client addresses are checked against secret key restrictions
+    axum::serve(
+        listener,
+        router.into_make_service_with_connect_info::<SocketAddr>(),
+    )
+    .with_graceful_shutdown(async move { draining.draining().await })
+    .await
+    .inspect_err(|err| tracing::error!("{err}"))
+    .unwrap();
+
+    // WebSockets are upgraded out of the server's hands, wait for their requests too
+    shutdown.drained().await;
 }
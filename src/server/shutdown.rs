@@ -0,0 +1,93 @@
+//! Draining of in-flight requests on shutdown
+
+use std::sync::Arc;
+
+use tokio::sync::watch;
+
+#[derive(Debug, Default, Clone, Copy)]
+struct DrainState {
+    draining: bool,
+    in_flight: usize,
+}
+
+/// Coordinates a graceful shutdown: once draining, new requests are refused and the
+/// server stops when the requests in flight are finished.
+#[derive(Clone)]
+pub struct Shutdown {
+    state: Arc<watch::Sender<DrainState>>,
+}
+
+impl Default for Shutdown {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Shutdown {
+    pub fn new() -> Self {
+        let (state, _) = watch::channel(DrainState::default());
+        Self {
+            state: Arc::new(state),
+        }
+    }
+
+    /// Stop accepting requests
+    pub fn drain(&self) {
+        self.state.send_modify(|state| state.draining = true);
+    }
+
+    pub fn is_draining(&self) -> bool {
+        self.state.borrow().draining
+    }
+
+    pub fn in_flight(&self) -> usize {
+        self.state.borrow().in_flight
+    }
+
+    /// Resolves once draining starts
+    pub async fn draining(&self) {
+        let _ = self
+            .state
+            .subscribe()
+            .wait_for(|state| state.draining)
+            .await;
+    }
+
+    /// Resolves once draining started and no request is in flight
+    pub async fn drained(&self) {
+        let _ = self
+            .state
+            .subscribe()
+            .wait_for(|state| state.draining && state.in_flight == 0)
+            .await;
+    }
+
+    /// Count a request as in flight until the guard is dropped, or refuse it when draining
+    pub fn track(&self) -> Option<InFlight> {
+        let mut admitted = false;
+        self.state.send_if_modified(|state| {
+            admitted = !state.draining;
+            if admitted {
+                state.in_flight += 1;
+            }
+            admitted
+        });
+
+        admitted.then(|| InFlight {
+            shutdown: self.clone(),
+        })
+    }
+}
+
+/// A request in flight, see `Shutdown::track`
+pub struct InFlight {
+    shutdown: Shutdown,
+}
+
+impl Drop for InFlight {
+    fn drop(&mut self) {
+        self.shutdown
+            .state
+            .send_modify(|state| state.in_flight = state.in_flight.saturating_sub(1));
+    }
+}
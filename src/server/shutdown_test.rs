@@ -0,0 +1,34 @@
+use std::time::Duration;
+
+use tokio::time::timeout;
+
+use super::shutdown::Shutdown;
+
+#[tokio::test]
+async fn test_drain() {
+    let shutdown = Shutdown::new();
+    let in_flight = shutdown.track().unwrap();
+    assert_eq!(shutdown.in_flight(), 1);
+
+    // Not drained before draining starts
+    assert!(
+        timeout(Duration::from_millis(50), shutdown.drained())
+            .await
+            .is_err()
+    );
+
+    shutdown.drain();
+    assert!(shutdown.track().is_none());
+    assert!(
+        timeout(Duration::from_millis(50), shutdown.drained())
+            .await
+            .is_err()
+    );
+
+    drop(in_flight);
+    assert!(
+        timeout(Duration::from_millis(50), shutdown.drained())
+            .await
+            .is_ok()
+    );
+}
@@ -0,0 +1,7 @@
+use serde::Deserialize;
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct SetWatermarkRequest {
+    /// Revoke keys created before this unix timestamp in milliseconds
+    pub issued_before: i64,
+}
@@ -1,15 +1,26 @@
 use serde::{Deserialize, Serialize};
 
-use crate::core::keys::{MetadataV1, SecretKeyV1};
+use crate::{
+    core::keys::{MetadataV2, SecretKeyV2},
+    db::KeyUsage,
+};
 
 #[derive(Debug, Clone, Deserialize)]
 pub struct MetadataBytesRequest {
-    pub metadata: MetadataV1,
+    pub metadata: MetadataV2,
+
+    /// Version of the key to sign the metadata for. Defaults to 1, which earlier clients sign.
+    #[serde(default = "v1")]
+    pub version: u8,
+}
+
+fn v1() -> u8 {
+    1
 }
 
 #[derive(Debug, Clone, Deserialize)]
 pub struct GenerateKeyRequest {
-    pub payload: SecretKeyV1,
+    pub payload: SecretKeyV2,
 }
 
 #[derive(Debug, Clone, Serialize)]
@@ -26,12 +37,22 @@ pub struct VerifyKeyRequest {
 pub struct VerifyKeyResponse {
     pub result: bool,
     pub reason: Option<String>,
-    pub payload: SecretKeyV1,
+    pub payload: SecretKeyV2,
 }
 
-#[derive(Debug, Clone, Deserialize)]
-pub struct RevokeKeyRequest {
-    pub signer: String,
-    pub signature: String,
-    pub secret_key: String,
+#[derive(Debug, Clone, Serialize)]
+pub struct KeyUsageResponse {
+    #[serde(flatten)]
+    pub usage: KeyUsage,
+    pub usage_limit: u64,
+
+    /// Tokens left before the key is rejected, `None` if the key has no limit
+    pub remaining: Option<u64>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct RevokeSignerResponse {
+    /// Keys of the signer created before this time are revoked, as a unix timestamp in
+    /// milliseconds
+    pub issued_before: i64,
 }
@@ -1 +1,3 @@
+pub mod admin;
 pub mod keys;
+pub mod ws;
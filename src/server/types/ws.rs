@@ -0,0 +1,34 @@
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+/// Messages a client sends over `/ws`
+#[derive(Debug, Clone, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ClientMessage {
+    /// Start a chat completion, `body` being the same as `POST /chat/completions`
+    Request { id: String, body: Value },
+
+    /// Cancel an in-flight request
+    Cancel { id: String },
+}
+
+/// Messages the node sends over `/ws`, multiplexed by the client's request id
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ServerMessage {
+    /// A `chat.completion.chunk` of a streaming request
+    Chunk { id: String, data: Value },
+
+    /// The `chat.completion` object of a non-streaming request
+    Response { id: String, body: Value },
+
+    /// The request finished, no more messages will be sent for it
+    Done { id: String },
+
+    /// The request failed or the message couldn't be handled
+    Error {
+        id: Option<String>,
+        status: u16,
+        message: String,
+    },
+}
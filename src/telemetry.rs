@@ -0,0 +1,239 @@
+//! Logging and OpenTelemetry tracing
+//!
+//! Log lines of a request carry the fields of its spans: `request_id`, `service_id` and
+//! `signer`, the fingerprint of the secret key's signer. Secret keys and request bodies
+//! are never logged.
+//!
+//! Trace context travels in W3C `traceparent` and `tracestate` entries: in HTTP headers
+//! between clients, the node and upstream endpoints, and in `transport::Request`'s
+//! `trace_context` between the node and providers.
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    fs::OpenOptions,
+    path::PathBuf,
+    sync::Arc,
+};
+
+use anyhow::Context;
+use clap::{Args, ValueEnum};
+use http::HeaderMap;
+use opentelemetry::{global, propagation::Extractor, trace::TracerProvider};
+use opentelemetry_otlp::{SpanExporter, WithExportConfig};
+use opentelemetry_sdk::{
+    Resource,
+    propagation::TraceContextPropagator,
+    trace::{Sampler, SdkTracerProvider},
+};
+use tracing::{Span, Subscriber};
+use tracing_opentelemetry::OpenTelemetrySpanExt;
+use tracing_subscriber::{
+    EnvFilter, Layer,
+    fmt::{self, writer::BoxMakeWriter},
+    layer::SubscriberExt,
+    registry::LookupSpan,
+    util::SubscriberInitExt,
+};
+
+use crate::config::TelemetryOptions;
+
+/// Headers whose values are replaced in logs
+const REDACTED_HEADERS: [&str; 4] = [
+    "authorization",
+    "cookie",
+    "proxy-authorization",
+    "x-api-key",
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum LogFormat {
+    /// Human-readable lines
+    Pretty,
+
+    /// One JSON object per line, with the fields of the line's spans
+    Json,
+}
+
+/// Logging options, shared by all subcommands
+#[derive(Debug, Clone, Args)]
+pub struct LogOptions {
+    /// Log output format
+    #[arg(long, global = true, value_enum, default_value_t = LogFormat::Pretty)]
+    pub log_format: LogFormat,
+
+    /// Log level, optionally per module
+    #[arg(
+        long,
+        global = true,
+        value_name = "DIRECTIVES",
+        long_help = "Log level, e.g. `debug`, or per-module directives like `info,aimo::router=debug`. Defaults to `RUST_LOG`, or `info` if it isn't set."
+    )]
+    pub log_level: Option<String>,
+
+    /// Append logs to this file instead of printing them
+    #[arg(long, global = true, value_name = "FILE")]
+    pub log_file: Option<PathBuf>,
+}
+
+impl Default for LogOptions {
+    fn default() -> Self {
+        Self {
+            log_format: LogFormat::Pretty,
+            log_level: None,
+            log_file: None,
+        }
+    }
+}
+
+impl LogOptions {
+    pub fn filter(&self) -> anyhow::Result<EnvFilter> {
+        match &self.log_level {
+            Some(directives) => EnvFilter::try_new(directives)
+                .with_context(|| format!("Invalid log level `{directives}`")),
+            None => {
+                Ok(EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("info")))
+            }
+        }
+    }
+
+    pub fn writer(&self) -> anyhow::Result<BoxMakeWriter> {
+        match &self.log_file {
+            Some(path) => {
+                let file = OpenOptions::new()
+                    .create(true)
+                    .append(true)
+                    .open(path)
+                    .with_context(|| format!("Failed to open log file {}", path.display()))?;
+                Ok(BoxMakeWriter::new(Arc::new(file)))
+            }
+            None => Ok(BoxMakeWriter::new(std::io::stdout)),
+        }
+    }
+}
+
+/// Keeps the trace exporter running, flushing it on `shutdown`
+pub struct Telemetry {
+    provider: Option<SdkTracerProvider>,
+}
+
+impl Telemetry {
+    pub fn shutdown(self) {
+        if let Some(provider) = self.provider
+            && let Err(err) = provider.shutdown()
+        {
+            tracing::warn!("Failed to flush traces: {err}");
+        }
+    }
+}
+
+/// Build the tracer provider exporting to `options.otlp_endpoint`, if set
+pub fn tracer_provider(options: &TelemetryOptions) -> anyhow::Result<Option<SdkTracerProvider>> {
+    let Some(endpoint) = &options.otlp_endpoint else {
+        return Ok(None);
+    };
+
+    let exporter = SpanExporter::builder()
+        .with_http()
+        .with_endpoint(format!("{}/v1/traces", endpoint.trim_end_matches('/')))
+        .build()?;
+
+    let provider = SdkTracerProvider::builder()
+        .with_batch_exporter(exporter)
+        .with_sampler(Sampler::ParentBased(Box::new(Sampler::TraceIdRatioBased(
+            options.sample_ratio,
+        ))))
+        .with_resource(
+            Resource::builder()
+                .with_service_name(options.service_name.clone())
+                .build(),
+        )
+        .build();
+
+    Ok(Some(provider))
+}
+
+/// Layer formatting log lines in `format` to `writer`
+pub fn log_layer<S>(
+    format: LogFormat,
+    writer: BoxMakeWriter,
+    ansi: bool,
+) -> Box<dyn Layer<S> + Send + Sync>
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    match format {
+        LogFormat::Pretty => fmt::layer().with_writer(writer).with_ansi(ansi).boxed(),
+        LogFormat::Json => fmt::layer().json().with_writer(writer).boxed(),
+    }
+}
+
+/// Install the global tracing subscriber, exporting spans when an OTLP endpoint is set
+pub fn init(log: &LogOptions, options: &TelemetryOptions) -> anyhow::Result<Telemetry> {
+    global::set_text_map_propagator(TraceContextPropagator::new());
+
+    let provider = tracer_provider(options)?;
+    let otel_layer = provider.as_ref().map(|provider| {
+        tracing_opentelemetry::layer().with_tracer(provider.tracer(options.service_name.clone()))
+    });
+
+    tracing_subscriber::registry()
+        .with(log.filter()?)
+        .with(log_layer(
+            log.log_format,
+            log.writer()?,
+            log.log_file.is_none(),
+        ))
+        .with(otel_layer)
+        .try_init()?;
+
+    Ok(Telemetry { provider })
+}
+
+/// Headers safe to log, with credentials redacted
+pub fn redact_headers(headers: &HashMap<String, String>) -> BTreeMap<&str, &str> {
+    headers
+        .iter()
+        .map(|(key, value)| {
+            let value = if REDACTED_HEADERS.contains(&key.to_ascii_lowercase().as_str()) {
+                "<redacted>"
+            } else {
+                value.as_str()
+            };
+            (key.as_str(), value)
+        })
+        .collect()
+}
+
+/// Trace context of the current span, to be carried to the next hop
+pub fn current_context() -> HashMap<String, String> {
+    let mut carrier = HashMap::new();
+    let cx = Span::current().context();
+    global::get_text_map_propagator(|propagator| propagator.inject_context(&cx, &mut carrier));
+
+    carrier
+}
+
+/// Continue the trace of a `carrier` received from the previous hop in `span`
+pub fn set_parent(span: &Span, carrier: &HashMap<String, String>) {
+    let cx = global::get_text_map_propagator(|propagator| propagator.extract(carrier));
+    let _ = span.set_parent(cx);
+}
+
+/// Continue the trace of incoming HTTP request `headers` in `span`
+pub fn set_parent_from_headers(span: &Span, headers: &HeaderMap) {
+    let cx =
+        global::get_text_map_propagator(|propagator| propagator.extract(&HeaderExtractor(headers)));
+    let _ = span.set_parent(cx);
+}
+
+struct HeaderExtractor<'a>(&'a HeaderMap);
+
+impl Extractor for HeaderExtractor<'_> {
+    fn get(&self, key: &str) -> Option<&str> {
+        self.0.get(key).and_then(|value| value.to_str().ok())
+    }
+
+    fn keys(&self) -> Vec<&str> {
+        self.0.keys().map(|key| key.as_str()).collect()
+    }
+}
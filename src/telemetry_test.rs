@@ -0,0 +1,174 @@
+use std::{
+    collections::HashMap,
+    io::{self, Write},
+    net::SocketAddr,
+    sync::{Arc, Mutex},
+};
+
+use axum::{Router, extract::State, http::Uri, routing::post};
+use opentelemetry::{
+    global,
+    trace::{TraceContextExt, TracerProvider},
+};
+use opentelemetry_sdk::{propagation::TraceContextPropagator, trace::SdkTracerProvider};
+use serde_json::Value;
+use tokio::net::TcpListener;
+use tracing_opentelemetry::OpenTelemetrySpanExt;
+use tracing_subscriber::{fmt::writer::BoxMakeWriter, layer::SubscriberExt};
+
+use crate::{
+    config::TelemetryOptions,
+    telemetry::{self, LogFormat, LogOptions},
+};
+
+/// Log writer collecting lines in memory
+#[derive(Clone, Default)]
+struct Buffer(Arc<Mutex<Vec<u8>>>);
+
+impl Write for Buffer {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.lock().unwrap().write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Stand-in OTLP collector recording the paths of export requests
+async fn collector() -> (SocketAddr, Arc<Mutex<Vec<String>>>) {
+    let received = Arc::new(Mutex::new(Vec::new()));
+    let app = Router::new()
+        .route(
+            "/v1/traces",
+            post(
+                |State(received): State<Arc<Mutex<Vec<String>>>>, uri: Uri| async move {
+                    received.lock().unwrap().push(uri.path().to_string());
+                },
+            ),
+        )
+        .with_state(received.clone());
+
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+
+    (addr, received)
+}
+
+fn subscriber(provider: &SdkTracerProvider) -> impl tracing::Subscriber + Send + Sync {
+    tracing_subscriber::registry()
+        .with(tracing_opentelemetry::layer().with_tracer(provider.tracer("test")))
+}
+
+#[test]
+fn test_no_endpoint() {
+    let provider = telemetry::tracer_provider(&TelemetryOptions::default()).unwrap();
+    assert!(provider.is_none());
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_export() {
+    let (addr, received) = collector().await;
+    let options = TelemetryOptions {
+        otlp_endpoint: Some(format!("http://{addr}/")),
+        ..Default::default()
+    };
+
+    let provider = telemetry::tracer_provider(&options).unwrap().unwrap();
+    tracing::subscriber::with_default(subscriber(&provider), || {
+        let _span = tracing::info_span!("request").entered();
+    });
+
+    tokio::task::spawn_blocking(move || provider.shutdown())
+        .await
+        .unwrap()
+        .unwrap();
+
+    assert_eq!(*received.lock().unwrap(), vec!["/v1/traces".to_string()]);
+}
+
+#[test]
+fn test_propagation() {
+    global::set_text_map_propagator(TraceContextPropagator::new());
+    let provider = SdkTracerProvider::builder().build();
+
+    tracing::subscriber::with_default(subscriber(&provider), || {
+        let parent = tracing::info_span!("request");
+        let carrier = parent.in_scope(telemetry::current_context);
+        let trace_id = parent.context().span().span_context().trace_id();
+        assert!(carrier["traceparent"].contains(&trace_id.to_string()));
+
+        // The next hop continues the same trace
+        let child = tracing::info_span!("proxy_request");
+        telemetry::set_parent(&child, &carrier);
+        assert_eq!(child.context().span().span_context().trace_id(), trace_id);
+    });
+
+    // Nothing to carry outside of a trace
+    assert!(telemetry::current_context().is_empty());
+}
+
+#[test]
+fn test_json_log_fields() {
+    let buffer = Buffer::default();
+    let writer = {
+        let buffer = buffer.clone();
+        BoxMakeWriter::new(move || buffer.clone())
+    };
+    let subscriber =
+        tracing_subscriber::registry().with(telemetry::log_layer(LogFormat::Json, writer, false));
+
+    tracing::subscriber::with_default(subscriber, || {
+        let span = tracing::info_span!(
+            "request",
+            request_id = tracing::field::Empty,
+            service_id = tracing::field::Empty,
+            signer = "fingerprint",
+        );
+        let _entered = span.enter();
+
+        // Fields recorded once known are carried by later lines
+        span.record("request_id", "r1").record("service_id", "s1");
+        tracing::info!("routed");
+    });
+
+    let output = String::from_utf8(buffer.0.lock().unwrap().clone()).unwrap();
+    let line: Value = serde_json::from_str(output.lines().next().unwrap()).unwrap();
+    assert_eq!(line["fields"]["message"], "routed");
+    assert_eq!(line["span"]["request_id"], "r1");
+    assert_eq!(line["span"]["service_id"], "s1");
+    assert_eq!(line["span"]["signer"], "fingerprint");
+}
+
+#[test]
+fn test_log_level() {
+    let options = LogOptions {
+        log_level: Some("info,aimo::router=debug".to_string()),
+        ..Default::default()
+    };
+    assert!(options.filter().is_ok());
+
+    let options = LogOptions {
+        log_level: Some("aimo=loud".to_string()),
+        ..Default::default()
+    };
+    assert!(options.filter().is_err());
+}
+
+#[test]
+fn test_redact_headers() {
+    let headers = HashMap::from([
+        (
+            "Authorization".to_string(),
+            "Bearer aimo-sk-dev-xxx".to_string(),
+        ),
+        ("x-api-key".to_string(), "upstream".to_string()),
+        ("content-type".to_string(), "application/json".to_string()),
+    ]);
+
+    let redacted = telemetry::redact_headers(&headers);
+    assert_eq!(redacted["Authorization"], "<redacted>");
+    assert_eq!(redacted["x-api-key"], "<redacted>");
+    assert_eq!(redacted["content-type"], "application/json");
+}
@@ -0,0 +1,37 @@
+//! Helpers shared by the unit tests of several modules
+
+use chrono::Utc;
+use solana_sdk::{signature::Keypair, signer::Signer};
+
+use crate::core::keys::{MetadataV2, Scope, SecretKeyV2, Wallet};
+
+/// Metadata of a key created now and valid for a minute, with the completion scope
+pub fn metadata_v2() -> MetadataV2 {
+    MetadataV2 {
+        created_at: Utc::now().timestamp_millis(),
+        valid_for: 60_000,
+        usage_limit: 0,
+        scopes: vec![Scope::CompletionModel],
+        label: None,
+        tag: None,
+        restrictions: Default::default(),
+        delegate: None,
+        parent: None,
+        extensions: vec![],
+    }
+}
+
+/// A V2 key of `metadata` signed by a Solana `keypair`
+pub fn sign_v2(keypair: &Keypair, metadata: MetadataV2) -> SecretKeyV2 {
+    let signature = keypair
+        .sign_message(&metadata.signing_bytes(2).unwrap())
+        .to_string();
+
+    SecretKeyV2 {
+        version: 2,
+        wallet: Wallet::Solana,
+        signer: keypair.pubkey().to_string(),
+        signature,
+        metadata,
+    }
+}
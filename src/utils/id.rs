@@ -1,8 +1,11 @@
-use std::path::PathBuf;
+use std::{fs, path::PathBuf};
 
 use anyhow::anyhow;
+use libsecp256k1::SecretKey;
 use solana_sdk::signature::{Keypair, read_keypair_file};
 
+use crate::core::ethereum::decode_hex;
+
 /// Create a solana keypair from `id.json` file.
 ///
 /// If `path` is `None`, use the default path `~/.config/solana/id.json`
@@ -21,3 +24,13 @@ pub fn create_keypair_from_file(path: Option<PathBuf>) -> anyhow::Result<Keypair
 
     read_keypair_file(path).map_err(|err| anyhow!("Failed to read keypair file: {err}"))
 }
+
+/// Read an Ethereum private key from a file of 32 bytes hex, as exported by wallets
+pub fn read_ethereum_key_file(path: PathBuf) -> anyhow::Result<SecretKey> {
+    let content = fs::read_to_string(&path)
+        .map_err(|err| anyhow!("Failed to read private key file: {err}"))?;
+    let bytes = decode_hex(content.trim())
+        .map_err(|err| anyhow!("Private key file isn't valid hex: {err}"))?;
+
+    SecretKey::parse_slice(&bytes).map_err(|err| anyhow!("Invalid private key: {err:?}"))
+}